@@ -1,34 +1,53 @@
 use anyhow::Context;
 use cargo::{
-	core::{package::Package, Verbosity, Workspace},
+	core::{package::Package, Workspace},
 	util::{config::Config as CargoConfig, interning::InternedString},
 };
 use flexi_logger::Logger;
 use log::trace;
 use regex::Regex;
 use semver::{BuildMetadata, Prerelease, Version};
-use std::{fs, path::PathBuf, str::FromStr};
-use structopt::{
-	clap::{arg_enum, AppSettings::*},
-	StructOpt,
+use std::{
+	collections::HashSet,
+	env, fs,
+	io::Read,
+	path::{Path, PathBuf},
+	str::FromStr,
+	time::Duration,
 };
+use structopt::{clap::AppSettings::*, StructOpt};
 use toml_edit::Value;
 
-use crate::{commands, util};
+#[cfg(feature = "gen-readme")]
+use cargo_unleash::options::GenerateReadmeMode;
+use cargo_unleash::{
+	commands, config, dry_run, metrics,
+	options::{BuildOptions, OutputFormat, StatsFormat, VersionReqStyle},
+	util,
+};
 
 fn parse_regex(src: &str) -> Result<Regex, anyhow::Error> {
 	Regex::new(src).context("Parsing Regex failed")
 }
 
-arg_enum! {
-	#[derive(Debug, PartialEq, Eq)]
-	pub enum GenerateReadmeMode {
-		// Generate Readme only if it is missing.
-		IfMissing,
-		// Generate Readme & append to existing file.
-		Append,
-		// Generate Readme & overwrite existing file.
-		Overwrite,
+fn parse_edge(src: &str) -> Result<(String, String), anyhow::Error> {
+	match src.split_once(':') {
+		Some((from, to)) if !from.is_empty() && !to.is_empty() =>
+			Ok((from.to_owned(), to.to_owned())),
+		_ => anyhow::bail!("Expected `<from>:<to>`, got {}", src),
+	}
+}
+
+fn parse_owner(src: &str) -> Result<String, anyhow::Error> {
+	commands::validate_owner(src)?;
+	Ok(src.to_owned())
+}
+
+fn parse_range(src: &str) -> Result<(String, String), anyhow::Error> {
+	match src.split_once("..") {
+		Some((from, to)) if !from.is_empty() && !to.is_empty() =>
+			Ok((from.to_owned(), to.to_owned())),
+		_ => anyhow::bail!("Expected `<from>..<to>`, got {}", src),
 	}
 }
 
@@ -41,6 +60,13 @@ pub struct PackageSelectOptions {
 	/// ignore-version-pre.
 	#[structopt(short, long, parse(from_str))]
 	pub packages: Vec<InternedString>,
+	/// Only use the packages named in this file
+	///
+	/// One name per line, empty lines and ones starting with `#` are ignored. Use `-` to read
+	/// them from stdin. Names that aren't members of the workspace are warned about and left
+	/// out. Same as passing each with `--packages`, can be combined with it.
+	#[structopt(long, parse(from_os_str))]
+	pub package_list: Option<PathBuf>,
 	/// Skip the package names matching ...
 	///
 	/// Provide one or many regular expression that, if the package name matches, means we skip
@@ -60,6 +86,13 @@ pub struct PackageSelectOptions {
 	/// regardless, set this flag.
 	#[structopt(long)]
 	ignore_publish: bool,
+	/// Select only the packages left out because of `publish`
+	///
+	/// The opposite of the default: only packages with `publish` set to false or any registry
+	/// are selected, e.g. to edit the internal ones. Mutually exclusive with
+	/// `--ignore-publish`.
+	#[structopt(long, conflicts_with = "ignore-publish")]
+	pub only_unpublishable: bool,
 	/// Automatically detect the packages, which changed compared to the given git commit.
 	///
 	/// Compares the current git `head` to the reference given, identifies which files changed
@@ -68,9 +101,46 @@ pub struct PackageSelectOptions {
 	/// (and up to date) locally.
 	#[structopt(short = "c", long = "changed-since")]
 	pub changed_since: Option<String>,
+	/// Compare to this git reference instead of the current `head`
+	///
+	/// Only together with `--changed-since`, to find the packages changed between the two
+	/// references, e.g. the previous and the candidate release tag.
+	#[structopt(long = "changed-until", requires = "changed-since")]
+	pub changed_until: Option<String>,
+	/// Automatically detect the packages, which changed between two git references.
+	///
+	/// Shorthand for `--changed-since <from> --changed-until <to>` with the references given
+	/// as `<from>..<to>`, e.g. two release tags. Mutually exclusive with those two.
+	#[structopt(
+		long = "changed-between",
+		parse(try_from_str = parse_range),
+		conflicts_with_all = &["changed-since", "changed-until"]
+	)]
+	pub changed_between: Option<(String, String)>,
+	/// Consider all packages changed if a file in the workspace root changed
+	///
+	/// Files like the root `Cargo.toml`, `Cargo.lock` or `rust-toolchain.toml` affect every
+	/// package, but aren't part of any. Otherwise a change is only attributed to the package it
+	/// is within, or whose `build` script or `include` patterns name it.
+	#[structopt(long)]
+	pub changed_include_root: bool,
+	/// Compare the git references directly
+	///
+	/// By default, changes are taken since the later reference (or `head`) branched off of
+	/// the earlier one, like `git diff <from>...<to>`, so changes made on the other branch
+	/// since don't count. With this, all differences count, like `git diff <from>..<to>`.
+	#[structopt(long)]
+	pub changed_two_dot: bool,
 	/// Even if not selected by default, also include depedencies with a pre (cascading)
 	#[structopt(long)]
 	pub include_pre_deps: bool,
+	/// Only consider packages the given rhai script evaluates to `true` for
+	///
+	/// The script is run for each package with `name`, `version`, `path`, `features` and
+	/// `dependencies` set. Applies on top of the other selection options.
+	#[cfg(feature = "select-script")]
+	#[structopt(long)]
+	pub select_script: Option<PathBuf>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -110,6 +180,20 @@ pub enum VersionCommand {
 		#[structopt()]
 		pre_tag: Option<String>,
 	},
+	/// Smart bumping of crates for the next breaking release as a release candidate
+	///
+	/// Bumps to the next breaking version with an `-rc.1` pre-release. If the crate already is
+	/// a release candidate, only the trailing number is increased. Other pre-releases, e.g.
+	/// `-dev`, keep their version and become its first release candidate.
+	BumpToRc {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+		/// Force an update of dependencies
+		///
+		/// Hard set to the new version, do not check whether the given one still matches
+		#[structopt(long)]
+		force_update: bool,
+	},
 	/// Increase the pre-release suffix, keep prefix, set to `.1` if no suffix is present
 	BumpPre {
 		#[structopt(flatten)]
@@ -162,6 +246,31 @@ pub enum VersionCommand {
 		#[structopt(long)]
 		force_update: bool,
 	},
+	/// Hard set all selected crates to one shared version
+	///
+	/// The primitive for workspaces versioning their crates in lockstep. Works like `set`, but
+	/// afterwards verifies all selected crates share the version and warns about crates that
+	/// weren't selected but are depended upon with a different version.
+	SetAll {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+		/// The version all crates are set to
+		version: Version,
+		/// Force an update of dependencies
+		///
+		/// Hard set to the new version, do not check whether the given one still matches
+		#[structopt(long)]
+		force_update: bool,
+	},
+	/// Sync the requirements on local crates to their current versions
+	///
+	/// Rewrite the version requirement of every path dependency of the selected crates to the
+	/// current version of the crate it points to, without changing any crate's own version.
+	/// Useful to repair requirements that drifted after manual edits.
+	SyncDeps {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+	},
 	/// Set the pre-release to string
 	SetPre {
 		#[structopt(flatten)]
@@ -204,9 +313,18 @@ pub enum Command {
 		#[structopt(short, long, default_value = "package")]
 		root_key: String,
 		/// Name of the field
+		///
+		/// Use dots to set a field in a nested table, e.g. `metadata.docs.rs.all-features`.
+		/// Missing tables are created along the way.
 		name: String,
 		/// Value to set it, too
 		value: String,
+		/// Only set the field where it isn't present yet
+		///
+		/// Leave manifests that already define the field untouched, rather than overwriting
+		/// their value.
+		#[structopt(long)]
+		only_if_missing: bool,
 	},
 	/// Rename a package
 	///
@@ -217,27 +335,103 @@ pub enum Command {
 		old_name: String,
 		/// Value to set it, too
 		new_name: String,
+		/// Also update git and registry dependencies
+		///
+		/// By default only path dependencies are considered to reference the package in
+		/// question and thus updated.
+		#[structopt(long)]
+		include_non_path: bool,
 	},
 	/// Messing with versioning
 	///
 	/// Change versions as requested, then update all package's dependencies
 	/// to ensure they are still matching
 	Version {
+		/// Show the latest version published on crates.io next to each bump
+		///
+		/// Helps to spot crates that are unexpectedly ahead of (or behind) the registry.
+		#[structopt(long)]
+		show_published: bool,
+		/// Write the dependency requirements rewritten to this file
+		///
+		/// One line per requirement, as `<dependent>: dep <dependency> <old> -> <new>`.
+		#[structopt(long)]
+		report: Option<PathBuf>,
+		/// Commit the manifests changed
+		///
+		/// Only the manifests the command changed are staged, other changes in the working
+		/// tree are left alone. Skipped if the workspace isn't within a git repository.
+		#[structopt(long)]
+		commit: bool,
+		/// The message to commit with, `Bump {count} crates` by default
+		///
+		/// `{count}` is replaced by the number of crates bumped and `{crates}` by the list of
+		/// them as `name version`.
+		#[structopt(long, requires = "commit")]
+		commit_message: Option<String>,
 		#[structopt(subcommand)]
 		cmd: VersionCommand,
 	},
 	/// Add owners for a lot of crates
+	///
+	/// Also removes owners with `--remove` and prints the current ones with `--list`.
 	AddOwner {
 		#[structopt(flatten)]
 		pkg_opts: PackageSelectOptions,
 		/// Owner to add to the packages
-		owner: String,
+		///
+		/// Either a user login or a team as `github:<org>:<team>`.
+		#[structopt(required_unless_one(&["owners", "remove", "list"]))]
+		#[structopt(parse(try_from_str = parse_owner))]
+		owner: Option<String>,
+		/// Further owner to add to the packages
+		///
+		/// Can be given multiple times, all owners are added to a package in one request.
+		#[structopt(long = "owner", value_name = "owner", number_of_values = 1)]
+		#[structopt(parse(try_from_str = parse_owner))]
+		owners: Vec<String>,
+		/// Owner to remove from the packages
+		///
+		/// Packages the owner isn't an owner of are reported, but don't fail the command.
+		/// Can be given multiple times.
+		#[structopt(long, parse(try_from_str = parse_owner))]
+		remove: Vec<String>,
+		/// Print the current owners of each package
+		///
+		/// Printed after any changes were applied.
+		#[structopt(long)]
+		list: bool,
+		/// the crates.io token to use for API access
+		///
+		/// If this is nor the environment variable are set, this falls
+		/// back to the default value provided in the user directory
+		#[structopt(long, env = "CRATES_TOKEN", hide_env_values = true)]
+		token: Option<String>,
+		/// The registry to manage the owners on
+		///
+		/// Name of a registry configured in your cargo config, crates.io is used if not given.
+		#[structopt(long)]
+		registry: Option<String>,
+	},
+	/// Add the owners configured in `.unleash.toml`
+	///
+	/// The `[owners]` table maps globs on the crate name to the owners to add, e.g.
+	/// `"pallet-*" = ["github:org:runtime"]`. Every selected crate gets the owners of all
+	/// globs it matches.
+	ApplyOwners {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
 		/// the crates.io token to use for API access
 		///
 		/// If this is nor the environment variable are set, this falls
 		/// back to the default value provided in the user directory
 		#[structopt(long, env = "CRATES_TOKEN", hide_env_values = true)]
 		token: Option<String>,
+		/// The registry to manage the owners on
+		///
+		/// Name of a registry configured in your cargo config, crates.io is used if not given.
+		#[structopt(long)]
+		registry: Option<String>,
 	},
 	/// Deactivate the `[dev-dependencies]`
 	///
@@ -246,6 +440,22 @@ pub enum Command {
 	DeDevDeps {
 		#[structopt(flatten)]
 		pkg_opts: PackageSelectOptions,
+		/// Remove dependency sections left empty
+		///
+		/// Also covers the ones within `[target.*]`, which are removed if nothing remains in them.
+		#[structopt(long)]
+		prune_empty: bool,
+		/// Keep this dev-dependency
+		///
+		/// Can be given multiple times, all other dev-dependencies are still removed.
+		#[structopt(long = "keep-dev-dep")]
+		keep_dev: Vec<String>,
+		/// Restore the `[dev-dependencies]` instead
+		///
+		/// Puts back the section as it is in the git HEAD, leaving the rest of the manifest as
+		/// it is. Fails if a manifest isn't tracked in git.
+		#[structopt(long, conflicts_with_all = &["prune-empty", "keep-dev"])]
+		revert: bool,
 	},
 	/// Check the package(s) for unused dependencies
 	CleanDeps {
@@ -256,6 +466,94 @@ pub enum Command {
 		/// Abort if you found unused dependencies
 		#[structopt(long = "check")]
 		check_only: bool,
+		/// Remove dependency sections left empty
+		///
+		/// Also covers the ones within `[target.*]`, which are removed if nothing remains in them.
+		#[structopt(long)]
+		prune_empty: bool,
+	},
+	/// Normalize the version requirements of external dependencies
+	///
+	/// Rewrite all caret-equivalent requirements (e.g. `1.0`, `^1.0`, `>=1, <2`) of non-path
+	/// dependencies into one canonical form. Path dependencies are left to the `version`
+	/// commands.
+	NormalizeVersions {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+		/// The form to rewrite the requirements to
+		#[structopt(long, default_value = "bare")]
+		#[structopt(possible_values = &VersionReqStyle::variants(), case_insensitive = true)]
+		style: VersionReqStyle,
+		/// Do only check if you'd normalize.
+		///
+		/// Abort if you found requirements not in the canonical form
+		#[structopt(long = "check")]
+		check_only: bool,
+	},
+	/// Run the soft checks of `check` on the manifests only
+	///
+	/// Check the metadata crates.io requires and that git dependencies also give a version,
+	/// reporting all problems found. Doesn't package or build anything, so it is fast enough
+	/// to run on every change.
+	Validate {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+	},
+	/// Check the metadata crates.io requires of each crate
+	///
+	/// Report the crates with a missing or bad `description`, `repository`, `license` or
+	/// `keywords`, and fail if there are any.
+	CheckMetadata {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+		/// Fill in the missing fields first
+		///
+		/// `license` and `description` come from the `[metadata-defaults]` of
+		/// `.unleash.toml`, where `{name}` in the description is replaced by the crate name.
+		/// `repository` comes from there as well, or the url of the `origin` git remote. Fields
+		/// already set are left alone.
+		#[structopt(long)]
+		fix_metadata: bool,
+	},
+	/// Report which fields and dependencies each member inherits from the workspace
+	///
+	/// Show per crate whether `version`, `edition`, `license` and `authors` are inherited
+	/// from `[workspace.package]`, defined locally or missing, and how many of its
+	/// dependencies come from `[workspace.dependencies]`. Only reads the manifests.
+	WorkspaceInheritanceReport,
+	/// Draft release notes from the git history
+	///
+	/// Print the subjects of the commits since the given git reference touching each of the
+	/// selected crates, as Markdown with a heading per crate. Crates without any commits are
+	/// left out.
+	Changelog {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+		/// The git reference to list the commits since, e.g. the last release tag
+		since: String,
+	},
+	/// List the selected packages
+	///
+	/// Print the name, version, folder and whether it may be published to crates.io of each
+	/// package the selection options match, without doing anything else. Use `--output json`
+	/// for scripting.
+	List {
+		#[structopt(flatten)]
+		pkg_opts: PackageSelectOptions,
+	},
+	/// Print the dependency tree of a single crate
+	///
+	/// Show the workspace-internal dependencies the given crate needs for publishing as a tree,
+	/// rooted at that crate. Crates taking part in a dependency cycle are marked.
+	Tree {
+		/// Name of the crate to show the tree for
+		name: String,
+		/// Also show dependencies that aren't part of the workspace
+		#[structopt(long)]
+		external: bool,
+		/// Only show the tree up to this depth
+		#[structopt(long)]
+		depth: Option<usize>,
 	},
 	/// Calculate the packages and the order in which to release
 	///
@@ -267,6 +565,11 @@ pub enum Command {
 		/// By default we disable dev-dependencies before the run.
 		#[structopt(long = "include-dev-deps")]
 		include_dev: bool,
+		/// Keep this dev-dependency when disabling the others
+		///
+		/// Can be given multiple times, all other dev-dependencies are still removed.
+		#[structopt(long = "keep-dev-dep", conflicts_with = "include-dev")]
+		keep_dev: Vec<String>,
 		#[structopt(flatten)]
 		pkg_opts: PackageSelectOptions,
 		/// Consider no package matching the criteria an error
@@ -277,6 +580,30 @@ pub enum Command {
 		/// to the given path.
 		#[structopt(long = "dot-graph")]
 		dot_graph: Option<PathBuf>,
+		/// Ignore the dependency of crate `from` on crate `to` for the release order
+		///
+		/// Given as `<from>:<to>`, can be passed multiple times. Allows to break dependency
+		/// cycles known to be fine. Broken edges are shown dashed in the dot graph.
+		#[structopt(long = "break-edge", parse(try_from_str = parse_edge))]
+		break_edges: Vec<(String, String)>,
+		/// Leave out crates in dependency cycles instead of failing
+		///
+		/// The crates forming a cycle and all crates depending on them are reported as skipped,
+		/// the remaining ones are handled in release order as usual.
+		#[structopt(long)]
+		isolate_cycles: bool,
+		/// Print statistics of the release graph
+		///
+		/// The number of crates and dependencies, the longest chain of crates that have to be
+		/// published one after another, the most depended upon crates and the number of
+		/// independent components. As `text` (the default) or `json`, before the crates.
+		#[structopt(long, possible_values = &StatsFormat::variants(), case_insensitive = true)]
+		graph_stats: Option<Option<StatsFormat>>,
+		/// Write the crates to release, in order, to the given path
+		///
+		/// To review before `em-dragons --plan-in` publishes exactly these.
+		#[structopt(long)]
+		plan_out: Option<PathBuf>,
 	},
 	/// Check whether crates can be packaged
 	///
@@ -288,14 +615,15 @@ pub enum Command {
 		/// By default we disable dev-dependencies before the run.
 		#[structopt(long = "include-dev-deps")]
 		include_dev: bool,
+		/// Keep this dev-dependency when disabling the others
+		///
+		/// Can be given multiple times, all other dev-dependencies are still removed.
+		#[structopt(long = "keep-dev-dep", conflicts_with = "include-dev")]
+		keep_dev: Vec<String>,
 		#[structopt(flatten)]
 		pkg_opts: PackageSelectOptions,
-		/// Actually build the package
-		///
-		/// By default, this only runs `cargo check` against the package
-		/// build. Set this flag to have it run an actual `build` instead.
-		#[structopt(long)]
-		build: bool,
+		#[structopt(flatten)]
+		build_opts: BuildOptions,
 		/// Generate & verify whether the Readme file has changed.
 		///
 		/// When enabled, this will generate a Readme file from
@@ -303,6 +631,14 @@ pub enum Command {
 		/// check whether the existing Readme (if any) matches.
 		#[structopt(long)]
 		check_readme: bool,
+		/// Where the docs of the crates are hosted, for the links in Readme files
+		///
+		/// Relative links in the doc comments are pointed to
+		/// `<url><crate>/latest/<crate_name>/`, `https://docs.rs/` by default. If it contains
+		/// `{crate}` or `{version}`, these are replaced by the ones of the crate linked to
+		/// instead, giving the folder of its docs, e.g. `https://docs.example.com/{crate}/{version}/`.
+		#[structopt(long, requires = "check-readme")]
+		doc_base_url: Option<String>,
 		/// Consider no package matching the criteria an error
 		#[structopt(long)]
 		empty_is_failure: bool,
@@ -310,6 +646,49 @@ pub enum Command {
 		/// Write a graphviz dot file to the given destination
 		#[structopt(long = "dot-graph")]
 		dot_graph: Option<PathBuf>,
+		/// Only verify the crates changed since the given git reference
+		///
+		/// All selected crates are still packaged, but only those with changes compared to the
+		/// reference are compiled. Unchanged crates are assumed to have been verified by a
+		/// previous run.
+		#[structopt(long)]
+		skip_verified_since: Option<String>,
+		/// Also verify the crates depending on a changed crate
+		///
+		/// Only has an effect together with `--skip-verified-since`.
+		#[structopt(long)]
+		verify_dependents: bool,
+		/// Report all path and git dependencies before checking
+		///
+		/// Prints a table of every dependency of the selected crates not coming from a
+		/// registry and what publishing will do with it. Fails if any of them prevents
+		/// publishing.
+		#[structopt(long)]
+		dependency_source_audit: bool,
+		/// Check the requirements on external dependencies against the version policy
+		///
+		/// Fails on requirements that aren't specific enough, wildcards or requirements on
+		/// pre-releases in stable crates. Configure the policy in the
+		/// `[dependency-version-policy]` table of `.unleash.toml`.
+		#[structopt(long)]
+		dependency_version_policy: bool,
+		/// Only run the checks on metadata, dependencies and readme
+		///
+		/// Skip packing and compiling the crates, which is the expensive part.
+		#[structopt(long)]
+		metadata_only: bool,
+		/// Ignore the dependency of crate `from` on crate `to` for the release order
+		///
+		/// Given as `<from>:<to>`, can be passed multiple times. Allows to break dependency
+		/// cycles known to be fine. Broken edges are shown dashed in the dot graph.
+		#[structopt(long = "break-edge", parse(try_from_str = parse_edge))]
+		break_edges: Vec<(String, String)>,
+		/// Leave out crates in dependency cycles instead of failing
+		///
+		/// The crates forming a cycle and all crates depending on them are reported as skipped,
+		/// the remaining ones are handled in release order as usual.
+		#[structopt(long)]
+		isolate_cycles: bool,
 	},
 	/// Generate Readme files
 	///
@@ -323,12 +702,32 @@ pub enum Command {
 		///
 		/// Depending on the chosen option, this will generate a Readme
 		/// file from the crate's doc comments (using cargo-readme).
-		#[structopt(long)]
+		#[structopt(long, required_unless = "check", conflicts_with = "check")]
 		#[structopt(
             possible_values = &GenerateReadmeMode::variants(),
             case_insensitive = true
         )]
-		readme_mode: GenerateReadmeMode,
+		readme_mode: Option<GenerateReadmeMode>,
+		/// Only check whether the Readme files are up-to-date
+		///
+		/// Reports per crate whether its Readme is missing, needs an update or is up-to-date
+		/// compared to overwriting it, and fails if any isn't up-to-date. No files are changed.
+		#[structopt(long)]
+		check: bool,
+		/// Where the docs of the crates are hosted, for the links in Readme files
+		///
+		/// Relative links in the doc comments are pointed to
+		/// `<url><crate>/latest/<crate_name>/`, `https://docs.rs/` by default. If it contains
+		/// `{crate}` or `{version}`, these are replaced by the ones of the crate linked to
+		/// instead, giving the folder of its docs, e.g. `https://docs.example.com/{crate}/{version}/`.
+		#[structopt(long)]
+		doc_base_url: Option<String>,
+		/// Use this template for the Readme of every selected crate
+		///
+		/// By default the `README.tpl` closest to the crate is used, looking from its folder
+		/// up to the workspace root.
+		#[structopt(long, parse(from_os_str))]
+		readme_template: Option<PathBuf>,
 		/// Consider no package matching the criteria an error
 		#[structopt(long)]
 		empty_is_failure: bool,
@@ -342,14 +741,15 @@ pub enum Command {
 		/// By default we disable dev-dependencies before the run.
 		#[structopt(long = "include-dev-deps")]
 		include_dev: bool,
+		/// Keep this dev-dependency when disabling the others
+		///
+		/// Can be given multiple times, all other dev-dependencies are still removed.
+		#[structopt(long = "keep-dev-dep", conflicts_with = "include-dev")]
+		keep_dev: Vec<String>,
 		#[structopt(flatten)]
 		pkg_opts: PackageSelectOptions,
-		/// Actually build the package in check
-		///
-		/// By default, this only runs `cargo check` against the package
-		/// build. Set this flag to have it run an actual `build` instead.
-		#[structopt(long)]
-		build: bool,
+		#[structopt(flatten)]
+		build_opts: BuildOptions,
 		/// dry run
 		#[structopt(long)]
 		dry_run: bool,
@@ -357,7 +757,7 @@ pub enum Command {
 		#[structopt(long)]
 		no_check: bool,
 		/// Ensure we have the owner set as well
-		#[structopt(long = "owner")]
+		#[structopt(long = "owner", parse(try_from_str = parse_owner))]
 		add_owner: Option<String>,
 		/// the crates.io token to use for uploading
 		///
@@ -365,6 +765,82 @@ pub enum Command {
 		/// back to the default value provided in the user directory
 		#[structopt(long, env = "CRATES_TOKEN", hide_env_values = true)]
 		token: Option<String>,
+		/// The registry to publish to and manage the owners on
+		///
+		/// Name of a registry configured in your cargo config. If not given, crates whose
+		/// `publish` list doesn't include crates.io go to the first registry it names (their
+		/// token is taken from the cargo config), all others to crates.io. Such crates are
+		/// only selected with `--ignore-publish`.
+		#[structopt(long)]
+		registry: Option<String>,
+		/// Don't publish to this registry in this run
+		///
+		/// Removed from the registries each crate may be published to (its `publish` list, if
+		/// given). Crates that can't be published to the target registry anymore are skipped.
+		/// Can be given multiple times.
+		#[structopt(long)]
+		exclude_registry: Vec<String>,
+		/// Skip the crates whose version is already published to the registry
+		///
+		/// Makes it possible to just run the same release again, if it was interrupted.
+		#[structopt(long)]
+		skip_published: bool,
+		/// Seconds to wait for a published crate to show up in the registry's index
+		///
+		/// Crates depended upon by other crates of the release are only followed by their
+		/// dependents once the registry serves them, polling with a growing interval. Fails if
+		/// that takes longer.
+		#[structopt(long, default_value = "300")]
+		index_wait_timeout: u64,
+		/// Don't wait for published crates to show up in the registry's index
+		#[structopt(long)]
+		no_wait: bool,
+		/// Publish the crates of the plan written by `to-release --plan-out`
+		///
+		/// Exactly these crates are released, in the order given, instead of calculating
+		/// them. Fails if any of them isn't in the workspace at the planned version anymore.
+		#[structopt(long, conflicts_with_all = &["dot-graph", "break-edges", "isolate-cycles"])]
+		plan_in: Option<PathBuf>,
+		/// Create an annotated git tag for each crate once it is published
+		///
+		/// The tags point to the current `HEAD`. With `--dry-run` the tags are only printed.
+		#[structopt(long)]
+		tag: bool,
+		/// Put this in front of each tag name
+		#[structopt(long, requires = "tag")]
+		tag_prefix: Option<String>,
+		/// The name of each tag, `{name}-v{version}` by default
+		///
+		/// `{name}` and `{version}` are replaced by the ones of the crate published.
+		#[structopt(long, requires = "tag")]
+		tag_template: Option<String>,
+		/// Refuse to publish more than this many crates
+		///
+		/// Guards against a selection catching far more crates than intended. Checked right
+		/// after the crates to release are found, pass `--yes` to publish them anyway.
+		#[structopt(long)]
+		max_publish: Option<usize>,
+		/// Publish without asking for confirmation
+		///
+		/// By default the crates to publish are listed and publishing only starts once confirmed
+		/// on the terminal, running without one fails. Also publishes more crates than
+		/// `--max-publish`. Not needed with `--dry-run`.
+		#[structopt(long, alias = "force")]
+		yes: bool,
+		/// Publish up to this many crates at the same time
+		///
+		/// Only crates not depending on each other are published together: one level of the
+		/// dependency graph after the other, once the crates of the previous one are all
+		/// published. If any of a level fail, the rest of it is still published, but nothing
+		/// after.
+		#[structopt(long, default_value = "1")]
+		publish_concurrency: usize,
+		/// Make sure each crate is on the registry as uploaded, before publishing its dependents
+		///
+		/// Once a crate shows up in the index, its checksum there has to match the one of the
+		/// file uploaded. Skipped with `--dry-run`.
+		#[structopt(long, conflicts_with = "no-wait")]
+		verify_published: bool,
 		/// Generate & verify whether the Readme file has changed.
 		///
 		/// When enabled, this will generate a Readme file from
@@ -372,6 +848,14 @@ pub enum Command {
 		/// check whether the existing Readme (if any) matches.
 		#[structopt(long)]
 		check_readme: bool,
+		/// Where the docs of the crates are hosted, for the links in Readme files
+		///
+		/// Relative links in the doc comments are pointed to
+		/// `<url><crate>/latest/<crate_name>/`, `https://docs.rs/` by default. If it contains
+		/// `{crate}` or `{version}`, these are replaced by the ones of the crate linked to
+		/// instead, giving the folder of its docs, e.g. `https://docs.example.com/{crate}/{version}/`.
+		#[structopt(long, requires = "check-readme")]
+		doc_base_url: Option<String>,
 		/// Consider no package matching the criteria an error
 		#[structopt(long)]
 		empty_is_failure: bool,
@@ -379,6 +863,18 @@ pub enum Command {
 		/// Write a graphviz dot file to the given destination
 		#[structopt(long = "dot-graph")]
 		dot_graph: Option<PathBuf>,
+		/// Ignore the dependency of crate `from` on crate `to` for the release order
+		///
+		/// Given as `<from>:<to>`, can be passed multiple times. Allows to break dependency
+		/// cycles known to be fine. Broken edges are shown dashed in the dot graph.
+		#[structopt(long = "break-edge", parse(try_from_str = parse_edge))]
+		break_edges: Vec<(String, String)>,
+		/// Leave out crates in dependency cycles instead of failing
+		///
+		/// The crates forming a cycle and all crates depending on them are reported as skipped,
+		/// the remaining ones are handled in release order as usual.
+		#[structopt(long)]
+		isolate_cycles: bool,
 	},
 }
 
@@ -398,66 +894,267 @@ pub struct Opt {
 	/// Show verbose cargo output
 	#[structopt(short, long)]
 	pub verbose: bool,
+	/// Run without accessing the network, like `cargo --offline`
+	///
+	/// Nothing is considered published to crates.io already when finding the crates to
+	/// release.
+	#[structopt(long)]
+	pub offline: bool,
+	/// Require Cargo.lock and cache to be up-to-date and run offline, like `cargo --frozen`
+	#[structopt(long)]
+	pub frozen: bool,
+	/// Query the registry every time the published versions of crates are needed
+	///
+	/// By default, the versions found are kept for the rest of the run, e.g. to not query
+	/// them again for `em-dragons --skip-published`. Waiting for crates to show up in the
+	/// index always queries.
+	#[structopt(long)]
+	pub no_registry_cache: bool,
+	/// Don't print a status line for every package
+	///
+	/// Commands going through all members of the workspace show a counter updated in place
+	/// instead, the totals and findings are still reported.
+	#[structopt(short, long, conflicts_with = "verbose")]
+	pub quiet: bool,
+	/// Follow path dependencies outside of the workspace only this many levels deep
+	///
+	/// Path dependencies of members are the first level, their own path dependencies the
+	/// second and so on. All of them are followed by default.
+	#[structopt(long)]
+	pub dependency_depth: Option<usize>,
+	/// Write the duration of each stage of the run to the given JSON file
+	///
+	/// Records member discovery, registry sync, graph build and toposort, as well as packing,
+	/// verifying and publishing of each crate, with the number of items each handled.
+	#[structopt(long, parse(from_os_str))]
+	pub emit_metrics: Option<PathBuf>,
+	/// How to print the results of the commands
+	///
+	/// `json` prints structured records instead of prose to stdout, e.g. the crates to
+	/// release as an array of `{"name", "version"}` and each version bump as
+	/// `{"crate", "from", "to"}`.
+	#[structopt(long, default_value = "human")]
+	#[structopt(possible_values = &OutputFormat::variants(), case_insensitive = true)]
+	pub output: OutputFormat,
+	/// Refuse to change any manifests if the git working tree isn't clean
+	///
+	/// Aborts before doing anything if there are uncommitted changes, so they don't get mixed
+	/// up with the ones made by the command. Ignored if the workspace isn't within a git
+	/// repository.
+	#[structopt(long)]
+	pub require_clean: bool,
+	/// Show the changes to the manifests as a diff instead of writing them
+	///
+	/// Commands editing manifests, e.g. `version`, `set` or `de-dev-deps`, run as usual but
+	/// leave the files untouched. See `em-dragons --dry-run` for publishing.
+	#[structopt(long)]
+	pub dry_run: bool,
 
 	#[structopt(subcommand)]
 	pub cmd: Command,
 }
 
+impl Command {
+	/// Whether running this command leaves manifests of the workspace changed
+	fn changes_manifests(&self) -> bool {
+		match self {
+			Command::Set { .. } |
+			Command::Rename { .. } |
+			Command::Version { .. } |
+			Command::DeDevDeps { .. } => true,
+			Command::CleanDeps { check_only, .. } |
+			Command::NormalizeVersions { check_only, .. } => !check_only,
+			Command::CheckMetadata { fix_metadata, .. } => *fix_metadata,
+			// publishing leaves the manifests with the dev-dependencies disabled
+			Command::EmDragons { include_dev, .. } => !include_dev,
+			_ => false,
+		}
+	}
+}
+
+/// List `packages` and ask on the terminal whether to publish them. Fails unless confirmed, or
+/// if stdin isn't a terminal.
+fn confirm_publish(packages: &[Package]) -> Result<(), anyhow::Error> {
+	if !atty::is(atty::Stream::Stdin) {
+		anyhow::bail!(
+			"Not publishing without confirmation, pass `--yes` when running non-interactively"
+		)
+	}
+	eprintln!("About to publish {} crates:", packages.len());
+	for pkg in packages {
+		eprintln!("  {} {}", pkg.name(), pkg.version());
+	}
+	eprint!("Publish them? [y/N] ");
+	let mut answer = String::new();
+	std::io::stdin().read_line(&mut answer)?;
+	if !matches!(answer.trim().to_lowercase().as_str(), "y" | "yes") {
+		anyhow::bail!("Publishing aborted")
+	}
+	Ok(())
+}
+
+/// Report that no package matched the selection, as an empty list with `OutputFormat::Json`
+fn print_nothing_selected(output: OutputFormat) {
+	match output {
+		OutputFormat::Human => println!("No packages selected. All good. Exiting."),
+		OutputFormat::Json => println!("[]"),
+	}
+}
+
+/// Read the package names listed in the file at `path`, or stdin for `-`, leaving out the ones
+/// not in the workspace
+fn read_package_list(
+	ws: &Workspace<'_>,
+	path: &Path,
+) -> Result<HashSet<InternedString>, anyhow::Error> {
+	let content = if path == Path::new("-") {
+		let mut content = String::new();
+		std::io::stdin().read_to_string(&mut content).context("Reading stdin failed")?;
+		content
+	} else {
+		fs::read_to_string(path)
+			.with_context(|| format!("Reading package list {} failed", path.display()))?
+	};
+	let members = util::members_deep(ws)?.iter().map(|p| p.name()).collect::<HashSet<_>>();
+
+	let mut listed = HashSet::new();
+	for name in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+		let name = InternedString::new(name);
+		if members.contains(&name) {
+			listed.insert(name);
+		} else {
+			ws.config()
+				.shell()
+				.warn(format!("{} from the package list isn't in the workspace, ignoring", name))?;
+		}
+	}
+	Ok(listed)
+}
+
 fn make_pkg_predicate(
 	ws: &Workspace<'_>,
 	args: PackageSelectOptions,
+	defaults: &config::SelectDefaults,
 ) -> Result<impl Fn(&Package) -> bool, anyhow::Error> {
 	let PackageSelectOptions {
 		packages,
+		package_list,
 		skip,
 		ignore_pre_version,
 		ignore_publish,
+		only_unpublishable,
 		changed_since,
+		changed_until,
+		changed_between,
+		changed_include_root,
+		changed_two_dot,
 		include_pre_deps,
+		#[cfg(feature = "select-script")]
+		select_script,
 	} = args;
+	let (changed_since, changed_until) = match changed_between {
+		Some((from, to)) => (Some(from), Some(to)),
+		None => (changed_since, changed_until),
+	};
 
-	if !packages.is_empty() {
+	// the configured skipping only applies if the selection isn't given explicitly
+	let explicit = !packages.is_empty() || package_list.is_some() || changed_since.is_some();
+	let skip = if skip.is_empty() && !explicit {
+		defaults
+			.skip
+			.iter()
+			.map(|s| parse_regex(s).with_context(|| format!("Invalid `skip` {} in config", s)))
+			.collect::<Result<Vec<_>, _>>()?
+	} else {
+		skip
+	};
+	let ignore_pre_version = if ignore_pre_version.is_empty() && !explicit {
+		defaults.ignore_pre_version.clone()
+	} else {
+		ignore_pre_version
+	};
+	let ignore_publish = !only_unpublishable && (ignore_publish || defaults.ignore_publish);
+	let include_pre_deps = include_pre_deps || defaults.include_pre_deps;
+
+	let selected = match package_list {
+		Some(path) => Some(
+			read_package_list(ws, &path)?
+				.into_iter()
+				.chain(packages)
+				.collect::<HashSet<_>>(),
+		),
+		None if !packages.is_empty() => Some(packages.into_iter().collect()),
+		None => None,
+	};
+
+	if selected.is_some() {
 		if !skip.is_empty() || !ignore_pre_version.is_empty() {
 			anyhow::bail!(
-				"-p/--packages is mutually exlusive to using -s/--skip and -i/--ignore-version-pre"
+				"-p/--packages and --package-list are mutually exlusive to using -s/--skip and -i/--ignore-version-pre"
 			);
 		}
 		if changed_since.is_some() {
-			anyhow::bail!("-p/--packages is mutually exlusive to using -c/--changed-since");
+			anyhow::bail!(
+				"-p/--packages and --package-list are mutually exlusive to using -c/--changed-since and --changed-between"
+			);
 		}
 	}
 
 	let publish = move |p: &Package| {
 		// If publish is set to false or any registry, it is ignored by default
 		// unless overriden.
-		let value = ignore_publish || p.publish().is_none();
+		let value = if only_unpublishable {
+			p.publish().is_some()
+		} else {
+			ignore_publish || p.publish().is_none()
+		};
 
 		trace!("{:}.publish={}", p.name(), value);
 		value
 	};
-	let check_version = move |p: &Package| return include_pre_deps && !p.version().pre.is_empty();
+	let check_version = move |p: &Package| include_pre_deps && !p.version().pre.is_empty();
 
 	let changed = if let Some(changed_since) = &changed_since {
 		if !skip.is_empty() || !ignore_pre_version.is_empty() {
 			anyhow::bail!("-c/--changed-since is mutually exlusive to using -s/--skip and -i/--ignore-version-pre",);
 		}
-		Some(util::changed_packages(ws, changed_since)?)
+		Some(util::changed_packages(
+			ws,
+			changed_since,
+			changed_until.as_deref(),
+			changed_include_root,
+			changed_two_dot,
+		)?)
 	} else {
 		None
 	};
 
+	#[cfg(feature = "select-script")]
+	let scripted = match select_script {
+		Some(path) =>
+			Some(cargo_unleash::script::select_by_script(&path, &util::members_deep(ws)?)?),
+		None => None,
+	};
+
 	Ok(move |p: &Package| {
 		if !publish(p) {
 			return false
 		}
 
+		#[cfg(feature = "select-script")]
+		if let Some(scripted) = &scripted {
+			if !scripted.contains(&p.name()) {
+				return false
+			}
+		}
+
 		if let Some(changed) = &changed {
 			return changed.contains(p) || check_version(p)
 		}
 
-		if !packages.is_empty() {
-			trace!("going for matching against {:?}", packages);
-			return packages.contains(&p.name()) || check_version(p)
+		if let Some(selected) = &selected {
+			trace!("going for matching against {:?}", selected);
+			return selected.contains(&p.name()) || check_version(p)
 		}
 
 		if !skip.is_empty() || !ignore_pre_version.is_empty() {
@@ -476,6 +1173,41 @@ fn make_pkg_predicate(
 	})
 }
 
+/// Bump to the next breaking version: major for `>= 1.0`, minor for `0.x` and patch for `0.0.x`
+fn bump_breaking(v: &mut Version) {
+	if v.major != 0 {
+		v.major += 1;
+		v.minor = 0;
+		v.patch = 0;
+	} else if v.minor != 0 {
+		v.minor += 1;
+		v.patch = 0;
+	} else {
+		// 0.0.x means each patch is breaking, see:
+		// https://doc.rust-lang.org/cargo/reference/semver.html#change-categories
+
+		v.patch += 1;
+		// no helper, have to reset the metadata ourselves
+		v.build = BuildMetadata::EMPTY;
+	}
+}
+
+/// Bump the pre-release: an empty one becomes `1`, a trailing numeric segment is incremented
+/// (`rc.1` -> `rc.2`, `alpha.2.3` -> `alpha.2.4`, `1` -> `2`), otherwise `.1` is appended
+/// (`dev` -> `dev.1`).
+fn bump_pre(pre: &Prerelease) -> Option<Prerelease> {
+	if pre.is_empty() {
+		return Some(Prerelease::new("1").expect("Static will work"))
+	}
+
+	let mut items = pre.as_str().split('.').map(|s| s.to_string()).collect::<Vec<_>>();
+	match items.last().and_then(|u| u.parse::<u64>().ok()) {
+		Some(num) => *items.last_mut().expect("Checked before. qed") = format!("{}", num + 1),
+		None => items.push("1".to_owned()),
+	}
+	Prerelease::new(&items.join(".")).ok()
+}
+
 fn verify_readme_feature() -> Result<(), anyhow::Error> {
 	if cfg!(feature = "gen-readme") {
 		Ok(())
@@ -484,22 +1216,72 @@ fn verify_readme_feature() -> Result<(), anyhow::Error> {
 	}
 }
 
+/// Use the given token or resolve the one configured for the registry, fail if `required` but
+/// none could be found
+fn get_token(
+	c: &CargoConfig,
+	t: Option<String>,
+	registry: Option<&str>,
+	required: bool,
+) -> Result<Option<String>, anyhow::Error> {
+	if t.is_some() {
+		return Ok(t)
+	}
+	let token = util::resolve_token(c, registry)?;
+	if token.is_none() && required {
+		anyhow::bail!(
+			"No token found for {}. Please run `cargo login{}` or pass `--token`",
+			registry.unwrap_or("crates.io"),
+			registry.map(|r| format!(" --registry {}", r)).unwrap_or_default()
+		)
+	}
+	Ok(token)
+}
+
 pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 	let _ = Logger::try_with_str(args.log.clone())?.start()?;
+	let emit_metrics = args.emit_metrics.clone();
+	if emit_metrics.is_some() {
+		metrics::enable();
+	}
+	util::set_quiet(args.quiet);
+	util::set_dependency_depth(args.dependency_depth);
+	if args.no_registry_cache {
+		util::disable_registry_cache();
+	}
+	let dry_run = args.dry_run;
+	if dry_run {
+		if let Command::EmDragons { .. } = args.cmd {
+			anyhow::bail!("Publishing can't be previewed this way, use `em-dragons --dry-run`")
+		}
+		dry_run::enable();
+	}
+	let res = run_command(args);
+	if let Some(path) = emit_metrics {
+		metrics::write(&path).context("Writing metrics failed")?;
+	}
+	if dry_run && res.is_ok() {
+		print!("{}", dry_run::diffs(&env::current_dir()?));
+	}
+	res
+}
+
+fn run_command(args: Opt) -> Result<(), anyhow::Error> {
 	let mut c = CargoConfig::default().expect("Couldn't create cargo config");
+	c.configure(
+		args.verbose as u32,
+		false,
+		None,
+		args.frozen,
+		false,
+		args.offline,
+		&None,
+		&[],
+		&[],
+	)?;
 	c.values()?;
 	c.load_credentials()?;
 
-	let get_token = |t| -> Result<Option<String>, anyhow::Error> {
-		Ok(match t {
-			None => c.get_string("registry.token")?.map(|x| x.val),
-			_ => t,
-		})
-	};
-
-	c.shell()
-		.set_verbosity(if args.verbose { Verbosity::Verbose } else { Verbosity::Normal });
-
 	let root_manifest = {
 		let mut path = args.manifest_path.clone();
 		if path.is_dir() {
@@ -508,44 +1290,102 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 		fs::canonicalize(path)?
 	};
 
-	let ws = Workspace::new(&root_manifest, &c).context("Reading workspace failed")?;
+	if args.require_clean && !args.dry_run && args.cmd.changes_manifests() {
+		util::ensure_clean_tree(&root_manifest)?;
+	}
 
-	let maybe_patch =
-		|ws, shouldnt_patch, predicate: &dyn Fn(&Package) -> bool| -> anyhow::Result<Workspace> {
-			if shouldnt_patch {
-				return Ok(ws)
-			}
+	if let Command::WorkspaceInheritanceReport = args.cmd {
+		// cargo might not be able to load the workspace if it uses inheritance
+		println!("{}", commands::inheritance_report(&root_manifest)?);
+		return Ok(())
+	}
 
-			c.shell().status("Preparing", "Disabling Dev Dependencies")?;
+	// the command line takes precedence, see `config::SelectDefaults` and `PublishDefaults`
+	let config = config::load(&root_manifest)?;
+	let ws = util::reload_workspace(&root_manifest, &c)?;
+
+	let maybe_patch = |ws,
+	                   shouldnt_patch,
+	                   keep: &[String],
+	                   predicate: &dyn Fn(&Package) -> bool|
+	 -> anyhow::Result<(Workspace, util::ManifestBackup)> {
+		if shouldnt_patch {
+			return Ok((ws, util::ManifestBackup::snapshot(std::iter::empty())?))
+		}
+
+		c.shell().status("Preparing", "Disabling Dev Dependencies")?;
+		// put the manifests back once done, so the working tree isn't left dirty
+		let backup = util::ManifestBackup::snapshot(ws.members().filter(|p| predicate(p)))?;
 
+		let packages = ws.members().filter(|p| predicate(p)).collect::<Vec<_>>();
+		let progress = util::PackageProgress::new(&c, "Patching", packages.len());
+		// even on a dry run, the workspace is re-read from disk below
+		dry_run::bypass(|| {
 			commands::deactivate_dev_dependencies(
-				ws.members()
-					.filter(|p| predicate(p) && c.shell().status("Patching", p.name()).is_ok()),
-			)?;
-			// assure to re-read the workspace, otherwise `fn to_release` will still find cycles
-			// (rightfully so!)
-			Workspace::new(&root_manifest, &c).context("Reading workspace failed")
-		};
+				packages.into_iter().filter(|p| progress.tick(p).is_ok()),
+				false,
+				&keep.iter().cloned().collect(),
+			)
+		})?;
+		// assure to re-read the workspace, otherwise `fn to_release` will still find cycles
+		// (rightfully so!)
+		backup.reset_mtimes();
+		Ok((util::reload_workspace(&root_manifest, &c)?, backup))
+	};
 
+	let output = args.output;
 	match args.cmd {
-		Command::CleanDeps { pkg_opts, check_only } => {
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			commands::clean_up_unused_dependencies(&ws, predicate, check_only)
+		Command::CleanDeps { pkg_opts, check_only, prune_empty } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			commands::clean_up_unused_dependencies(&ws, predicate, check_only, prune_empty)
 		},
-		Command::AddOwner { owner, token, pkg_opts } => {
-			let t = get_token(token)?;
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+		Command::AddOwner { owner, owners, remove, list, token, registry, pkg_opts } => {
+			let t = get_token(ws.config(), token, registry.as_deref(), true)?;
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let to_add = owner.into_iter().chain(owners).collect::<Vec<_>>();
 
 			for pkg in ws.members().filter(|p| predicate(p)) {
-				commands::add_owner(ws.config(), pkg, owner.clone(), t.clone())?;
+				if !to_add.is_empty() {
+					commands::add_owner(
+						ws.config(),
+						pkg,
+						to_add.clone(),
+						t.clone(),
+						registry.clone(),
+					)?;
+				}
+				for owner in remove.iter() {
+					commands::remove_owner(
+						ws.config(),
+						pkg,
+						owner.clone(),
+						t.clone(),
+						registry.clone(),
+					)?;
+				}
+				if list {
+					commands::list_owners(ws.config(), pkg, t.clone(), registry.clone())?;
+				}
 			}
 			Ok(())
 		},
-		Command::Set { root_key, name, value, pkg_opts } => {
+		Command::ApplyOwners { token, registry, pkg_opts } => {
+			let t = get_token(ws.config(), token, registry.as_deref(), true)?;
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+
+			commands::apply_owners(
+				ws.config(),
+				ws.members().filter(|p| predicate(p)),
+				&config.owners,
+				t,
+				registry,
+			)
+		},
+		Command::Set { root_key, name, value, pkg_opts, only_if_missing } => {
 			if name == "name" {
 				anyhow::bail!("To change the name please use the rename command!");
 			}
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 			let type_value = {
 				if let Ok(v) = bool::from_str(&value) {
 					Value::from(v)
@@ -556,70 +1396,86 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 				}
 			};
 
-			commands::set_field(
+			let (written, skipped) = commands::set_field(
 				ws.members()
 					.filter(|p| predicate(p) && c.shell().status("Setting on", p.name()).is_ok()),
 				root_key,
 				name,
 				type_value,
-			)
+				only_if_missing,
+			)?;
+			c.shell().status("Done", format!("{} written, {} skipped", written, skipped))?;
+			Ok(())
 		},
-		Command::Rename { old_name, new_name } => {
+		Command::Rename { old_name, new_name, include_non_path } => {
 			let predicate = |p: &Package| p.name().to_string().trim() == old_name;
 			let renamer = |_p: &Package| Some(new_name.clone());
 
-			commands::rename(&ws, predicate, renamer)
+			commands::rename(&ws, predicate, renamer, include_non_path)
 		},
-		Command::Version { cmd } => {
+		Command::Version { cmd, show_published, report, commit, commit_message } => {
+			let before = if commit { Some(commands::snapshot(&ws)?) } else { None };
 			match cmd {
 				VersionCommand::Set { pkg_opts, force_update, version } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
 						|_| Some(version.clone()),
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
+				VersionCommand::SetAll { pkg_opts, force_update, version } => {
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+					let selected = util::members_deep(&ws)?
+						.iter()
+						.filter(|p| predicate(p))
+						.map(|p| p.name())
+						.collect::<HashSet<_>>();
+					commands::set_version(
+						&ws,
+						|p| predicate(p),
+						|_| Some(version.clone()),
+						force_update,
+						show_published,
+						report.as_deref(),
+						output,
+					)?;
+					if dry_run::enabled() {
+						c.shell()
+							.status("Skipping", "lockstep check, nothing was written (dry run)")?;
+						Ok(())
+					} else {
+						// re-read to see the changes we just made
+						let ws = util::reload_workspace(&root_manifest, &c)?;
+						commands::check_lockstep(&ws, &selected, &version)
+					}
+				},
+				VersionCommand::SyncDeps { pkg_opts } => {
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+					commands::sync_dependencies(&ws, predicate, report.as_deref())
+				},
 				VersionCommand::BumpPre { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
 						|p| {
 							let mut v = p.version().clone();
-							if v.pre.is_empty() {
-								v.pre = Prerelease::new("1").expect("Static will work");
-							} else if let Ok(num) = v.pre.as_str().parse::<u32>() {
-								v.pre = Prerelease::new(&format!("{}", num + 1))
-									.expect("Knwon to work");
-							} else {
-								let mut items = v
-									.pre
-									.as_str()
-									.split('.')
-									.map(|s| s.to_string())
-									.collect::<Vec<_>>();
-								if let Some(num) = items.last().and_then(|u| u.parse::<u32>().ok())
-								{
-									let _ = items.pop();
-									items.push(format!("{}", num + 1));
-								} else {
-									items.push("1".to_owned());
-								}
-								if let Ok(pre) = Prerelease::new(&items.join(".")) {
-									v.pre = pre;
-								} else {
-									return None
-								}
-							}
+							v.pre = bump_pre(&v.pre)?;
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::BumpPatch { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -630,10 +1486,13 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::BumpMinor { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -645,10 +1504,13 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::BumpMajor { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -661,69 +1523,75 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::BumpBreaking { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
 						|p| {
 							let mut v = p.version().clone();
 							v.pre = Prerelease::EMPTY;
-							if v.major != 0 {
-								v.major += 1;
-								v.minor = 0;
-								v.patch = 0;
-							} else if v.minor != 0 {
-								v.minor += 1;
-								v.patch = 0;
-							} else {
-								// 0.0.x means each patch is breaking, see:
-								// https://doc.rust-lang.org/cargo/reference/semver.html#change-categories
-
-								v.patch += 1;
-								// no helper, have to reset the metadata ourselves
-								v.build = BuildMetadata::EMPTY;
-							}
+							bump_breaking(&mut v);
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::BumpToDev { pkg_opts, force_update, pre_tag } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					let pre_val = pre_tag.unwrap_or_else(|| "dev".to_owned());
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
 						|p| {
 							let mut v = p.version().clone();
-							if v.major != 0 {
-								v.major += 1;
-								v.minor = 0;
-								v.patch = 0
-							} else if v.minor != 0 {
-								v.minor += 1;
-								v.patch = 0;
-							} else {
-								// 0.0.x means each patch is breaking, see:
-								// https://doc.rust-lang.org/cargo/reference/semver.html#change-categories
-
-								v.patch += 1;
-								// no helper, have to reset the metadata ourselves
-								v.build = BuildMetadata::EMPTY;
-							}
+							bump_breaking(&mut v);
 							// force the pre
 							v.pre = Prerelease::new(&pre_val.clone())
 								.expect("Static or expected to work");
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
+					)
+				},
+				VersionCommand::BumpToRc { pkg_opts, force_update } => {
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+					commands::set_version(
+						&ws,
+						|p| predicate(p),
+						|p| {
+							let mut v = p.version().clone();
+							if v.pre.as_str() == "rc" || v.pre.as_str().starts_with("rc.") {
+								// already a release candidate, just go to the next one
+								v.pre = bump_pre(&v.pre)?;
+							} else {
+								// other pre-releases, e.g. `-dev`, already are of the next version
+								if v.pre.is_empty() {
+									bump_breaking(&mut v);
+								}
+								v.pre = Prerelease::new("rc.1").expect("Static will work");
+							}
+							Some(v)
+						},
+						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::SetPre { pre, pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -734,10 +1602,13 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::SetBuild { meta, pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -748,10 +1619,13 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
 				VersionCommand::Release { pkg_opts, force_update } => {
-					let predicate = make_pkg_predicate(&ws, pkg_opts)?;
+					let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
 					commands::set_version(
 						&ws,
 						|p| predicate(p),
@@ -762,126 +1636,439 @@ pub fn run(args: Opt) -> Result<(), anyhow::Error> {
 							Some(v)
 						},
 						force_update,
+						show_published,
+						report.as_deref(),
+						output,
 					)
 				},
+			}?;
+			if let Some(before) = before {
+				commands::commit_changes(
+					&ws,
+					&before,
+					commit_message.as_deref().unwrap_or("Bump {count} crates"),
+				)?;
+			}
+			Ok(())
+		},
+		Command::DeDevDeps { pkg_opts, prune_empty, keep_dev, revert } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let packages = ws.members().filter(|p| predicate(p)).collect::<Vec<_>>();
+			if revert {
+				c.shell().status("Preparing", "Restoring Dev Dependencies")?;
+				let progress = util::PackageProgress::new(&c, "Restoring", packages.len());
+				return commands::restore_dev_dependencies(
+					packages.into_iter().filter(|p| progress.tick(p).is_ok()),
+				)
 			}
+			c.shell().status("Preparing", "Disabling Dev Dependencies")?;
+			let progress = util::PackageProgress::new(&c, "Patching", packages.len());
+			commands::deactivate_dev_dependencies(
+				packages.into_iter().filter(|p| progress.tick(p).is_ok()),
+				prune_empty,
+				&keep_dev.into_iter().collect(),
+			)
+		},
+		Command::NormalizeVersions { pkg_opts, style, check_only } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			commands::normalize_versions(&ws, predicate, style, check_only)
+		},
+		Command::Validate { pkg_opts } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let packages = util::members_deep(&ws)?
+				.into_iter()
+				.filter(|p| predicate(p))
+				.collect::<Vec<_>>();
+			commands::validate(&packages, &ws)
+		},
+		Command::CheckMetadata { pkg_opts, fix_metadata } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			commands::fix_metadata(&ws, predicate, &config.metadata_defaults, fix_metadata)
 		},
-		Command::DeDevDeps { pkg_opts } => {
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			let _ = maybe_patch(ws, false, &predicate)?;
+		Command::WorkspaceInheritanceReport => unreachable!("Handled before loading. qed"),
+		Command::Changelog { pkg_opts, since } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let packages = util::members_deep(&ws)?;
+			print!(
+				"{}",
+				commands::changelog(&ws, packages.iter().filter(|p| predicate(p)), &since)?
+			);
+			Ok(())
+		},
+		Command::List { pkg_opts } => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			println!("{}", commands::list(&ws, predicate, output)?);
 			Ok(())
 		},
-		Command::ToRelease { include_dev, pkg_opts, empty_is_failure, dot_graph } => {
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			let ws = maybe_patch(ws, include_dev, &predicate)?;
+		Command::Tree { name, external, depth } => {
+			println!("{}", commands::dependency_tree(&ws, &name, external, depth)?);
+			Ok(())
+		},
+		Command::ToRelease {
+			include_dev,
+			keep_dev,
+			pkg_opts,
+			empty_is_failure,
+			dot_graph,
+			break_edges,
+			isolate_cycles,
+			graph_stats,
+			plan_out,
+		} => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let (ws, _backup) = maybe_patch(ws, include_dev, &keep_dev, &predicate)?;
 
-			let packages = commands::packages_to_release(&ws, predicate, dot_graph)?;
+			let packages = commands::packages_to_release(
+				&ws,
+				predicate,
+				dot_graph,
+				&break_edges,
+				isolate_cycles,
+				graph_stats.map(|f| f.unwrap_or(StatsFormat::Text)),
+			)?;
+			if let Some(path) = plan_out {
+				commands::write_plan(&path, &packages)?;
+			}
 			if packages.is_empty() {
 				if empty_is_failure {
 					anyhow::bail!("No Packages matching criteria. Exiting");
 				} else {
-					println!("No packages selected. All good. Exiting.");
+					print_nothing_selected(output);
 					return Ok(())
 				}
 			}
-			println!(
-				"{:}",
-				packages
-					.iter()
-					.map(|p| format!("{} ({})", p.name(), p.version()))
-					.collect::<Vec<String>>()
-					.join(", ")
-			);
+			match output {
+				OutputFormat::Human => println!(
+					"{:}",
+					packages
+						.iter()
+						.map(|p| format!("{} ({})", p.name(), p.version()))
+						.collect::<Vec<String>>()
+						.join(", ")
+				),
+				OutputFormat::Json => println!(
+					"{}",
+					serde_json::to_string(
+						&packages
+							.iter()
+							.map(|p| serde_json::json!({
+								"name": p.name().as_str(),
+								"version": p.version().to_string(),
+							}))
+							.collect::<Vec<_>>()
+					)?
+				),
+			}
 
 			Ok(())
 		},
 		Command::Check {
 			include_dev,
-			build,
+			keep_dev,
+			build_opts,
 			pkg_opts,
 			check_readme,
+			doc_base_url,
 			empty_is_failure,
 			dot_graph,
+			skip_verified_since,
+			verify_dependents,
+			dependency_source_audit,
+			dependency_version_policy,
+			metadata_only,
+			break_edges,
+			isolate_cycles,
 		} => {
 			if check_readme {
 				verify_readme_feature()?;
 			}
 
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			let ws = maybe_patch(ws, include_dev, &predicate)?;
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let (ws, _backup) = maybe_patch(ws, include_dev, &keep_dev, &predicate)?;
 
-			let packages = commands::packages_to_release(&ws, predicate, dot_graph)?;
+			let packages = commands::packages_to_release(
+				&ws,
+				predicate,
+				dot_graph,
+				&break_edges,
+				isolate_cycles,
+				None,
+			)?;
 			if packages.is_empty() {
 				if empty_is_failure {
 					anyhow::bail!("No Packages matching criteria. Exiting");
 				} else {
-					println!("No packages selected. All good. Exiting.");
+					print_nothing_selected(output);
 					return Ok(())
 				}
 			}
 
-			commands::check(&packages, &ws, build, check_readme)
+			if dependency_source_audit {
+				commands::audit_dependency_sources(&packages, &ws)?;
+			}
+			if dependency_version_policy {
+				commands::check_version_policy(&packages, &config.dependency_version_policy)?;
+			}
+
+			let verify_only = skip_verified_since
+				.map(|since| -> anyhow::Result<_> {
+					let changed = util::changed_packages(&ws, &since, None, false, false)?
+						.iter()
+						.map(|p| p.name())
+						.collect::<HashSet<_>>();
+					// packages are in release order, so dependencies are always seen first
+					let mut selected = HashSet::new();
+					for pkg in packages.iter() {
+						if changed.contains(&pkg.name()) ||
+							(verify_dependents &&
+								pkg.dependencies()
+									.iter()
+									.any(|d| selected.contains(&d.package_name())))
+						{
+							selected.insert(pkg.name());
+						}
+					}
+					Ok(selected)
+				})
+				.transpose()?;
+
+			commands::check(
+				&packages,
+				&ws,
+				&build_opts,
+				check_readme,
+				doc_base_url.as_deref(),
+				metadata_only,
+				verify_only.as_ref(),
+			)
 		},
 		#[cfg(feature = "gen-readme")]
-		Command::GenReadme { pkg_opts, readme_mode, empty_is_failure } => {
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			let ws = maybe_patch(ws, false, &predicate)?;
+		Command::GenReadme {
+			pkg_opts,
+			readme_mode,
+			check,
+			doc_base_url,
+			readme_template,
+			empty_is_failure,
+		} => {
+			let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+			let (ws, _backup) = maybe_patch(ws, false, &[], &predicate)?;
 
-			let packages = commands::packages_to_release(&ws, predicate, None)?;
+			let packages = commands::packages_to_release(&ws, predicate, None, &[], false, None)?;
 			if packages.is_empty() {
 				if empty_is_failure {
 					anyhow::bail!("No Packages matching criteria. Exiting");
 				} else {
-					println!("No packages selected. All good. Exiting.");
+					print_nothing_selected(output);
 					return Ok(())
 				}
 			}
 
-			commands::gen_all_readme(packages, &ws, readme_mode)
+			match readme_mode {
+				Some(readme_mode) if !check => commands::gen_all_readme(
+					packages,
+					&ws,
+					readme_mode,
+					doc_base_url.as_deref(),
+					readme_template.as_deref(),
+				),
+				_ => commands::check_all_readme(
+					&packages,
+					&ws,
+					doc_base_url.as_deref(),
+					readme_template.as_deref(),
+				),
+			}
 		},
 		Command::EmDragons {
 			dry_run,
 			no_check,
 			token,
+			registry,
+			exclude_registry,
 			include_dev,
+			keep_dev,
 			add_owner,
-			build,
+			build_opts,
 			pkg_opts,
 			check_readme,
+			doc_base_url,
 			empty_is_failure,
 			dot_graph,
+			break_edges,
+			isolate_cycles,
+			skip_published,
+			index_wait_timeout,
+			no_wait,
+			plan_in,
+			tag,
+			tag_prefix,
+			tag_template,
+			max_publish,
+			yes,
+			publish_concurrency,
+			verify_published,
 		} => {
-			let predicate = make_pkg_predicate(&ws, pkg_opts)?;
-			let ws = maybe_patch(ws, include_dev, &predicate)?;
+			let registry = registry.or_else(|| config.publish.registry.clone());
+			let exclude_registry = if exclude_registry.is_empty() {
+				config.publish.exclude_registry.clone()
+			} else {
+				exclude_registry
+			};
+			let token =
+				token.or_else(|| config.publish.token_env.as_ref().and_then(|e| env::var(e).ok()));
+			let skip_published = skip_published || config.publish.skip_published;
 
-			let packages = commands::packages_to_release(&ws, predicate, dot_graph)?;
+			let (packages, ws) = if let Some(plan) = plan_in {
+				let planned = commands::read_plan(&ws, &plan)?
+					.iter()
+					.map(|p| p.name())
+					.collect::<HashSet<_>>();
+				let (ws, backup) = maybe_patch(ws, include_dev, &keep_dev, &|p: &Package| {
+					planned.contains(&p.name())
+				})?;
+				// publishing works off the patched manifests
+				backup.keep_changes();
+				// read again to see the patched manifests
+				(commands::read_plan(&ws, &plan)?, ws)
+			} else {
+				let predicate = make_pkg_predicate(&ws, pkg_opts, &config.select)?;
+				let (ws, backup) = maybe_patch(ws, include_dev, &keep_dev, &predicate)?;
+				backup.keep_changes();
+				let packages = commands::packages_to_release(
+					&ws,
+					predicate,
+					dot_graph,
+					&break_edges,
+					isolate_cycles,
+					None,
+				)?;
+				(packages, ws)
+			};
 			if packages.is_empty() {
 				if empty_is_failure {
 					anyhow::bail!("No Packages matching criteria. Exiting");
 				} else {
-					println!("No packages selected. All good. Exiting.");
+					print_nothing_selected(output);
 					return Ok(())
 				}
 			}
+			match max_publish {
+				Some(max) if packages.len() > max && !yes => anyhow::bail!(
+					"{} crates selected for publishing, {} more than `--max-publish {}` allows. \
+					Narrow down the selection or pass `--yes` to publish them anyway.",
+					packages.len(),
+					packages.len() - max,
+					max
+				),
+				_ => {},
+			}
+			if publish_concurrency == 0 {
+				anyhow::bail!("`--publish-concurrency` needs to be at least 1")
+			}
 
 			if !no_check {
 				if check_readme {
 					verify_readme_feature()?;
 				}
 
-				commands::check(&packages, &ws, build, check_readme)?;
+				commands::check(
+					&packages,
+					&ws,
+					&build_opts,
+					check_readme,
+					doc_base_url.as_deref(),
+					false,
+					None,
+				)?;
+			}
+
+			let packages = commands::filter_excluded_registries(
+				packages,
+				&ws,
+				registry.as_deref(),
+				&exclude_registry,
+			)?;
+			let packages = if skip_published {
+				commands::skip_published(packages, &ws, registry.as_deref())?
+			} else {
+				packages
+			};
+			if packages.is_empty() {
+				println!("No packages left to publish. Exiting.");
+				return Ok(())
 			}
 
 			ws.config().shell().status(
 				"Releasing",
-				&packages
+				packages
 					.iter()
 					.map(|p| format!("{} ({})", p.name(), p.version()))
 					.collect::<Vec<String>>()
 					.join(", "),
 			)?;
+			if !dry_run && !yes {
+				confirm_publish(&packages)?;
+			}
 
-			commands::release(packages, ws, dry_run, get_token(token)?, add_owner)
+			// crates routed to the registry in their `publish` list use the token configured there
+			let required = !dry_run &&
+				packages
+					.iter()
+					.any(|p| commands::target_registry(p, registry.as_deref()) == registry);
+			let token = get_token(ws.config(), token, registry.as_deref(), required)?;
+			let index_wait =
+				if no_wait { None } else { Some(Duration::from_secs(index_wait_timeout)) };
+			let tag = if tag {
+				Some(format!(
+					"{}{}",
+					tag_prefix.unwrap_or_default(),
+					tag_template.as_deref().unwrap_or("{name}-v{version}")
+				))
+			} else {
+				None
+			};
+			commands::release(
+				packages,
+				ws,
+				dry_run,
+				token,
+				add_owner,
+				registry,
+				index_wait,
+				tag,
+				publish_concurrency,
+				verify_published,
+				build_opts.target_dir.as_deref(),
+			)
 		},
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn bumped(pre: &str) -> String {
+		bump_pre(&Prerelease::new(pre).unwrap()).unwrap().to_string()
+	}
+
+	#[test]
+	fn bump_pre_empty() {
+		assert_eq!(bumped(""), "1");
+		assert_eq!(bumped("1"), "2");
+	}
+
+	#[test]
+	fn bump_pre_single_token() {
+		assert_eq!(bumped("dev"), "dev.1");
+		assert_eq!(bumped("dev.1"), "dev.2");
+	}
+
+	#[test]
+	fn bump_pre_numbered() {
+		assert_eq!(bumped("rc.1"), "rc.2");
+		assert_eq!(bumped("rc.9"), "rc.10");
+		assert_eq!(bumped("alpha.2.3"), "alpha.2.4");
+	}
+}
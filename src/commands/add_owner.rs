@@ -1,24 +1,63 @@
+use crate::util::render_table;
+use anyhow::Context;
 use cargo::{
 	core::package::Package,
 	ops::{modify_owners, OwnersOptions},
 	util::config::Config,
 };
+use std::collections::BTreeMap;
 
+/// Whether `name` could be a GitHub login (or organisation): alphanumeric with single inner
+/// dashes, at most 39 characters.
+fn is_login(name: &str) -> bool {
+	!name.is_empty() &&
+		name.len() <= 39 &&
+		name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') &&
+		!name.starts_with('-') &&
+		!name.ends_with('-') &&
+		!name.contains("--")
+}
+
+/// Check `owner` is either a user login or a team given as `github:<org>:<team>`, before
+/// asking the registry about it.
+pub fn validate_owner(owner: &str) -> Result<(), anyhow::Error> {
+	match owner.split(':').collect::<Vec<_>>()[..] {
+		[login] if is_login(login) => Ok(()),
+		[login] => anyhow::bail!("`{}` isn't a valid user login", login),
+		["github", org, team] => {
+			if !is_login(org) {
+				anyhow::bail!("`{}` isn't a valid organisation in team `{}`", org, owner)
+			}
+			if team.is_empty() ||
+				!team.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+			{
+				anyhow::bail!("`{}` isn't a valid team name in team `{}`", team, owner)
+			}
+			Ok(())
+		},
+		_ =>
+			anyhow::bail!("`{}` is neither a user login nor a team as `github:<org>:<team>`", owner),
+	}
+}
+
+/// Add `new_owners` to the owners of `package`, in a single request. Those already owning it
+/// are only reported.
 pub fn add_owner(
 	c: &Config,
 	package: &Package,
-	new_owner: String,
+	new_owners: Vec<String>,
 	token: Option<String>,
+	registry: Option<String>,
 ) -> Result<(), anyhow::Error> {
 	if let Err(e) = modify_owners(
 		c,
 		&OwnersOptions {
-			token,
+			token: token.clone(),
 			krate: Some(package.name().to_string()),
-			to_add: Some(vec![new_owner.clone()]),
+			to_add: Some(new_owners.clone()),
 			to_remove: None,
 			list: false,
-			registry: None,
+			registry: registry.clone(),
 			index: None,
 		},
 	) {
@@ -27,10 +66,180 @@ pub fn add_owner(
 			anyhow::bail!(msg)
 		}
 
+		match &new_owners[..] {
+			[new_owner] => c
+				.shell()
+				.status(
+					"Owner",
+					format!("{:} is already an owner of {:}", new_owner, package.name()),
+				)
+				.expect("Shell worked before. qed"),
+			// the registry rejects the whole batch, find out who's missing one by one
+			_ =>
+				for new_owner in new_owners {
+					add_owner(c, package, vec![new_owner], token.clone(), registry.clone())?;
+				},
+		}
+	}
+
+	Ok(())
+}
+
+/// Remove `owner` from the owners of `package`. Removing someone who isn't an owner (anymore)
+/// is only reported, not an error.
+pub fn remove_owner(
+	c: &Config,
+	package: &Package,
+	owner: String,
+	token: Option<String>,
+	registry: Option<String>,
+) -> Result<(), anyhow::Error> {
+	if let Err(e) = modify_owners(
+		c,
+		&OwnersOptions {
+			token,
+			krate: Some(package.name().to_string()),
+			to_add: None,
+			to_remove: Some(vec![owner.clone()]),
+			list: false,
+			registry,
+			index: None,
+		},
+	) {
+		// the registry's reason is at the end of the chain, below cargo's context
+		let msg = format!("{:#}", e);
+		if !msg.contains("not an owner") && !msg.contains("could not find owner") {
+			anyhow::bail!(msg)
+		}
+
 		c.shell()
-			.status("Owner", format!("{:} is already an owner of {:}", new_owner, package.name()))
+			.status("Owner", format!("{:} is not an owner of {:}", owner, package.name()))
 			.expect("Shell worked before. qed")
 	}
 
 	Ok(())
 }
+
+/// Print the current owners of `package`
+pub fn list_owners(
+	c: &Config,
+	package: &Package,
+	token: Option<String>,
+	registry: Option<String>,
+) -> Result<(), anyhow::Error> {
+	c.shell().status("Owners", package.name())?;
+	modify_owners(
+		c,
+		&OwnersOptions {
+			token,
+			krate: Some(package.name().to_string()),
+			to_add: None,
+			to_remove: None,
+			list: true,
+			registry,
+			index: None,
+		},
+	)
+}
+
+/// Find the owners for crate `name` in `mapping` of crate name globs to owners. Each owner is
+/// only listed once, even if several globs match.
+fn owners_for(name: &str, mapping: &[(glob::Pattern, &Vec<String>)]) -> Vec<String> {
+	let mut owners = Vec::new();
+	for (pattern, pattern_owners) in mapping {
+		if pattern.matches(name) {
+			for owner in pattern_owners.iter() {
+				if !owners.contains(owner) {
+					owners.push(owner.clone());
+				}
+			}
+		}
+	}
+	owners
+}
+
+/// Add the owners configured in `mapping` of crate name globs to owners to each of
+/// `packages`, reporting the applied mapping first.
+pub fn apply_owners<'a>(
+	c: &Config,
+	packages: impl IntoIterator<Item = &'a Package>,
+	mapping: &BTreeMap<String, Vec<String>>,
+	token: Option<String>,
+	registry: Option<String>,
+) -> Result<(), anyhow::Error> {
+	let mapping = mapping
+		.iter()
+		.map(|(glob, owners)| {
+			glob::Pattern::new(glob)
+				.with_context(|| format!("Invalid crate name pattern {}", glob))
+				.map(|p| (p, owners))
+		})
+		.collect::<Result<Vec<_>, _>>()?;
+	for owner in mapping.iter().flat_map(|(_, owners)| owners.iter()) {
+		validate_owner(owner).context("Invalid owner in `[owners]`")?;
+	}
+
+	let resolved = packages
+		.into_iter()
+		.map(|pkg| (pkg, owners_for(pkg.name().as_str(), &mapping)))
+		.filter(|(_, owners)| !owners.is_empty())
+		.collect::<Vec<_>>();
+	if resolved.is_empty() {
+		c.shell().status("Owner", "No configured owners for the selected crates")?;
+		return Ok(())
+	}
+
+	let rows = resolved
+		.iter()
+		.map(|(pkg, owners)| [pkg.name().to_string(), owners.join(", ")])
+		.collect::<Vec<_>>();
+	println!("{}", render_table(["crate", "owners"], &rows));
+
+	for (pkg, owners) in resolved {
+		add_owner(c, pkg, owners, token.clone(), registry.clone())?;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn owners_from_all_matching_globs() {
+		let (runtime, core) = (
+			vec!["team-runtime".to_owned()],
+			vec!["team-core".to_owned(), "team-runtime".to_owned()],
+		);
+		let mapping = vec![
+			(glob::Pattern::new("pallet-*").unwrap(), &runtime),
+			(glob::Pattern::new("*-core").unwrap(), &core),
+		];
+		assert_eq!(owners_for("pallet-balances", &mapping), vec!["team-runtime"]);
+		assert_eq!(owners_for("pallet-core", &mapping), vec!["team-runtime", "team-core"]);
+		assert!(owners_for("sp-io", &mapping).is_empty());
+	}
+
+	#[test]
+	fn owner_specs() {
+		for valid in
+			["gnunicorn", "parity-ci-1", "github:paritytech:crates-admins", "github:org:a_b"]
+		{
+			assert!(validate_owner(valid).is_ok(), "{} should be valid", valid);
+		}
+		for invalid in [
+			"",
+			"-gnunicorn",
+			"gnu--nicorn",
+			"gnu nicorn",
+			"github:paritytech",
+			"github:paritytech:",
+			"github::crates-admins",
+			"gitlab:paritytech:crates-admins",
+			"github:parity_tech:crates-admins",
+			"github:paritytech:crates:admins",
+		] {
+			assert!(validate_owner(invalid).is_err(), "{} should be invalid", invalid);
+		}
+	}
+}
@@ -0,0 +1,56 @@
+use crate::util::affects_package;
+use anyhow::Context;
+use cargo::core::{package::Package, Workspace};
+use git2::{Repository, Sort};
+
+/// Draft release notes for `packages` as Markdown: a heading per crate, listing the subjects
+/// of the commits since `since` up to HEAD touching it, newest first. Merge commits and crates
+/// without any commits are left out.
+pub fn changelog<'a>(
+	ws: &Workspace<'_>,
+	packages: impl IntoIterator<Item = &'a Package>,
+	since: &str,
+) -> Result<String, anyhow::Error> {
+	let repo = Repository::discover(ws.root()).context("Workspace isn't within a git repo")?;
+	let repo_root = repo.workdir().context("Git repository has no working directory")?;
+	let since_commit = repo
+		.revparse_single(since)
+		.and_then(|o| o.peel_to_commit())
+		.with_context(|| format!("Reference {} not found in git repository", since))?;
+
+	let mut walk = repo.revwalk()?;
+	walk.set_sorting(Sort::TOPOLOGICAL | Sort::TIME)?;
+	walk.push_head()?;
+	walk.hide(since_commit.id())?;
+
+	let mut commits = Vec::new();
+	for oid in walk {
+		let commit = repo.find_commit(oid?)?;
+		if commit.parent_count() > 1 {
+			continue
+		}
+		let parent = commit.parents().next().map(|p| p.tree()).transpose()?;
+		let diff = repo.diff_tree_to_tree(parent.as_ref(), Some(&commit.tree()?), None)?;
+		let files = diff
+			.deltas()
+			.filter_map(|d| d.new_file().path().or_else(|| d.old_file().path()))
+			.map(|p| repo_root.join(p))
+			.collect::<Vec<_>>();
+		let subject = commit.summary().unwrap_or_default().to_owned();
+		let id = commit.as_object().short_id()?.as_str().unwrap_or_default().to_owned();
+		commits.push((subject, id, files));
+	}
+
+	let mut sections = Vec::new();
+	for pkg in packages {
+		let entries = commits
+			.iter()
+			.filter(|(_, _, files)| files.iter().any(|f| affects_package(pkg, f)))
+			.map(|(subject, id, _)| format!("- {} ({})\n", subject, id))
+			.collect::<String>();
+		if !entries.is_empty() {
+			sections.push(format!("## {} {}\n\n{}", pkg.name(), pkg.version(), entries));
+		}
+	}
+	Ok(sections.join("\n"))
+}
@@ -1,25 +1,40 @@
 #[cfg(feature = "gen-readme")]
 use crate::commands::readme;
 
-use crate::util::{edit_each_dep, DependencyAction, DependencyEntry};
+use crate::{
+	metrics,
+	options::BuildOptions,
+	util::{
+		config_like, dependency_levels, edit_each_dep, render_table, DependencyAction,
+		DependencyEntry,
+	},
+};
 use anyhow::Context;
 use cargo::{
 	core::{
 		compiler::{BuildConfig, CompileMode, DefaultExecutor, Executor},
+		dependency::DepKind,
 		package::Package,
 		resolver::features::CliFeatures,
-		Feature, SourceId, Workspace,
+		Feature, PackageId, Resolve, SourceId, Target, Workspace,
 	},
 	ops::{self, package, PackageOpts},
 	sources::PathSource,
-	util::{FileLock, OptVersionReq},
+	util::{
+		interning::InternedString, short_hash, CargoResult, Config, FileLock, Filesystem,
+		OptVersionReq,
+	},
 };
+use cargo_util::ProcessBuilder;
 use flate2::read::GzDecoder;
 use log::error;
 use std::{
-	collections::HashMap,
-	fs::{read_to_string, write},
-	sync::Arc,
+	collections::{HashMap, HashSet},
+	fs::{create_dir_all, read_to_string, write},
+	path::Path,
+	sync::{Arc, Mutex},
+	thread,
+	time::Instant,
 };
 use tar::Archive;
 use toml_edit::{Document, Item, Value};
@@ -54,18 +69,106 @@ fn inject_replacement(
 	Ok(())
 }
 
+/// File in the target dir keeping the fingerprints of the packages verified successfully
+const FINGERPRINTS_FILE: &str = "unleash-check-fingerprints.json";
+
+/// Fingerprint `pkg` by its most recently modified file, its dependencies and the build
+/// `setup`. `known` holds the fingerprints of the local packages handled before, so a change
+/// in a dependency invalidates its dependents, too.
+fn fingerprint(
+	c: &Config,
+	pkg: &Package,
+	resolve: Option<&Resolve>,
+	known: &HashMap<String, String>,
+	setup: &str,
+) -> Result<String, anyhow::Error> {
+	// `run_check` can't use the unpacked sources for this, they get a new mtime on every unpack
+	let mut src = PathSource::new(pkg.root(), pkg.package_id().source_id(), c);
+	src.update()?;
+	let last_modified = src.last_modified_file(pkg)?;
+
+	let mut deps = pkg
+		.dependencies()
+		.iter()
+		.map(|d| {
+			let local = known.get(d.package_name().as_str()).map(|s| s.as_str()).unwrap_or("");
+			format!("{} {} {}", d.package_name(), d.version_req(), local)
+		})
+		.collect::<Vec<_>>();
+	if let Some(resolve) = resolve {
+		deps.extend(resolve.deps(pkg.package_id()).map(|(id, _)| id.to_string()));
+	}
+	deps.sort();
+
+	Ok(short_hash(&(last_modified, pkg.version(), deps, setup)))
+}
+
+/// Read the fingerprints stored by a previous run, nothing if there are none or they can't
+/// be read.
+fn load_fingerprints(path: &Path) -> HashMap<String, String> {
+	read_to_string(path)
+		.ok()
+		.and_then(|content| serde_json::from_str(&content).ok())
+		.unwrap_or_default()
+}
+
+/// Runs rustc like the `DefaultExecutor`, but remembers the first error it reports, so it can
+/// be shown along with the crate that failed, rather than getting lost in the build output.
+#[derive(Default)]
+struct ErrorCapture {
+	first_error: Mutex<Option<(PackageId, String)>>,
+}
+
+impl ErrorCapture {
+	fn first_error(&self) -> Option<(PackageId, String)> {
+		self.first_error.lock().expect("Never poisoned. qed").clone()
+	}
+}
+
+impl Executor for ErrorCapture {
+	fn exec(
+		&self,
+		cmd: &ProcessBuilder,
+		id: PackageId,
+		target: &Target,
+		mode: CompileMode,
+		on_stdout_line: &mut dyn FnMut(&str) -> CargoResult<()>,
+		on_stderr_line: &mut dyn FnMut(&str) -> CargoResult<()>,
+	) -> CargoResult<()> {
+		let mut on_stderr_line = |line: &str| {
+			// rustc is run with json diagnostics, anything else is passed along only
+			if let Ok(diagnostic) = serde_json::from_str::<serde_json::Value>(line) {
+				if diagnostic["level"] == "error" {
+					if let Some(rendered) = diagnostic["rendered"].as_str() {
+						let rendered = strip_ansi_escapes::strip(rendered)
+							.map(|r| String::from_utf8_lossy(&r).trim_end().to_owned())?;
+						self.first_error
+							.lock()
+							.expect("Never poisoned. qed")
+							.get_or_insert((id, rendered));
+					}
+				}
+			}
+			on_stderr_line(line)
+		};
+		DefaultExecutor.exec(cmd, id, target, mode, on_stdout_line, &mut on_stderr_line)
+	}
+}
+
 fn run_check<'a>(
 	ws: &Workspace<'a>,
 	tar: &FileLock,
 	opts: &PackageOpts<'_>,
 	build_mode: CompileMode,
 	replace: &HashMap<String, String>,
+	verify: bool,
+	target_dir: Option<&Filesystem>,
 ) -> Result<Workspace<'a>, anyhow::Error> {
 	let config = ws.config();
 	let pkg = ws.current()?;
 
 	let f = GzDecoder::new(tar.file());
-	let dst = tar.parent().join(&format!("{}-{}", pkg.name(), pkg.version()));
+	let dst = tar.parent().join(format!("{}-{}", pkg.name(), pkg.version()));
 	if dst.exists() {
 		std::fs::remove_dir_all(&dst)?;
 	}
@@ -91,8 +194,29 @@ fn run_check<'a>(
 		(src, new_pkg)
 	};
 
-	let pkg_fingerprint = src.last_modified_file(&new_pkg)?;
-	let ws = Workspace::ephemeral(new_pkg, config, None, true)?;
+	if !verify {
+		// unpacked and ready to be injected into others, but nothing to compile
+		return Workspace::ephemeral(new_pkg, config, target_dir.cloned(), true)
+	}
+
+	let ws = Workspace::ephemeral(new_pkg, config, target_dir.cloned(), true)?;
+	compile(&ws, &src, opts.jobs, &opts.targets, &opts.cli_features, build_mode)?;
+	Ok(ws)
+}
+
+/// Compile the package of `ws`, read from `src`, and make sure its build script didn't touch
+/// the sources.
+fn compile(
+	ws: &Workspace<'_>,
+	src: &PathSource<'_>,
+	jobs: Option<u32>,
+	targets: &[String],
+	cli_features: &CliFeatures,
+	build_mode: CompileMode,
+) -> Result<(), anyhow::Error> {
+	let config = ws.config();
+	let pkg = ws.current()?;
+	let pkg_fingerprint = src.last_modified_file(pkg)?;
 
 	let rustc_args =
 		if pkg.manifest().unstable_features().require(Feature::public_dependency()).is_ok() {
@@ -103,13 +227,14 @@ fn run_check<'a>(
 			None
 		};
 
-	let exec: Arc<dyn Executor> = Arc::new(DefaultExecutor);
+	let capture = Arc::new(ErrorCapture::default());
+	let exec: Arc<dyn Executor> = capture.clone();
 	ops::compile_with_exec(
-		&ws,
+		ws,
 		&ops::CompileOptions {
-			build_config: BuildConfig::new(config, opts.jobs, false, &opts.targets, build_mode)?,
+			build_config: BuildConfig::new(config, jobs, false, targets, build_mode)?,
 			spec: ops::Packages::Packages(Vec::new()),
-			cli_features: opts.cli_features.clone(),
+			cli_features: cli_features.clone(),
 			filter: ops::CompileFilter::Default { required_features_filterable: true },
 			target_rustdoc_args: None,
 			target_rustc_args: rustc_args,
@@ -119,10 +244,15 @@ fn run_check<'a>(
 			target_rustc_crate_types: None,
 		},
 		&exec,
-	)?;
+	)
+	.with_context(|| match capture.first_error() {
+		Some((id, error)) =>
+			format!("Verifying {} failed, first error in {}:\n{}", pkg.name(), id, error),
+		None => format!("Verifying {} failed", pkg.name()),
+	})?;
 
 	// Check that `build.rs` didn't modify any files in the `src` directory.
-	let ws_fingerprint = src.last_modified_file(ws.current()?)?;
+	let ws_fingerprint = src.last_modified_file(pkg)?;
 	if pkg_fingerprint != ws_fingerprint {
 		let (_, path) = ws_fingerprint;
 		anyhow::bail!(
@@ -133,52 +263,264 @@ fn run_check<'a>(
 			path
 		);
 	}
+	Ok(())
+}
 
-	Ok(ws)
+/// Compile the package unpacked to `dst` with the `Config` made by `config`, see `compile`
+fn verify_isolated(
+	config: impl FnOnce() -> Result<Config, anyhow::Error>,
+	dst: &Path,
+	build_opts: &BuildOptions,
+	jobs: Option<u32>,
+	target_dir: Option<Filesystem>,
+) -> Result<(), anyhow::Error> {
+	let c = config()?;
+	let mut src = PathSource::new(dst, SourceId::for_path(dst)?, &c);
+	let ws = Workspace::ephemeral(src.root_package()?, &c, target_dir, true)?;
+	let cli_features = CliFeatures::from_command_line(
+		&build_opts.features,
+		build_opts.all_features,
+		!build_opts.no_default_features,
+	)?;
+	compile(&ws, &src, jobs, &build_opts.targets, &cli_features, build_mode(build_opts))
+}
+
+fn build_mode(build_opts: &BuildOptions) -> CompileMode {
+	if build_opts.build {
+		CompileMode::Build
+	} else {
+		CompileMode::Check { test: false }
+	}
 }
 
 fn check_dependencies(package: &Package) -> Result<(), anyhow::Error> {
-	let git_deps = package
-		.dependencies()
-		.iter()
-		.filter(|d| d.source_id().is_git() && d.version_req() == &OptVersionReq::Any)
-		.map(|d| format!("{:}", d.package_name()))
-		.collect::<Vec<_>>();
+	let without_version = |is_source: fn(SourceId) -> bool| {
+		package
+			.dependencies()
+			.iter()
+			.filter(|d| is_source(d.source_id()) && d.version_req() == &OptVersionReq::Any)
+			// dev-dependencies without a version are stripped on publish
+			.filter(|d| d.kind() != DepKind::Development)
+			.map(|d| format!("{:}", d.package_name()))
+			.collect::<Vec<_>>()
+	};
+	let git_deps = without_version(SourceId::is_git);
+	// the path is dropped on publish, for crates that are published at all
+	let path_deps = if package.publish().as_ref().map(|r| r.is_empty()).unwrap_or(false) {
+		Vec::new()
+	} else {
+		without_version(SourceId::is_path)
+	};
+
+	let mut problems = Vec::new();
 	if !git_deps.is_empty() {
-		anyhow::bail!(
-			"{}: has dependencies defined as git without a version: {:}",
-			package.name(),
+		problems.push(format!(
+			"has dependencies defined as git without a version: {:}",
 			git_deps.join(", ")
-		)
+		));
+	}
+	if !path_deps.is_empty() {
+		problems.push(format!(
+			"has dependencies defined as path without a version: {:}",
+			path_deps.join(", ")
+		));
+	}
+	if !problems.is_empty() {
+		anyhow::bail!("{}: {}", package.name(), problems.join("; "))
 	} else {
 		Ok(())
 	}
 }
 
+/// Report, per crate, every dependency that doesn't come from a registry and what publishing
+/// will make of it, as a table. Fails if any of them would keep the crate from being published.
+pub fn audit_dependency_sources(
+	packages: &[Package],
+	ws: &Workspace<'_>,
+) -> Result<(), anyhow::Error> {
+	let released = packages.iter().map(|p| p.name()).collect::<HashSet<_>>();
+	let mut rows = Vec::new();
+	let mut blocking = 0;
+
+	for pkg in packages {
+		for dep in pkg.dependencies() {
+			let source = dep.source_id();
+			let kind = if source.is_path() {
+				"path"
+			} else if source.is_git() {
+				"git"
+			} else {
+				continue
+			};
+			let has_version = dep.version_req() != &OptVersionReq::Any;
+			let outcome = if !has_version && dep.kind() == DepKind::Development {
+				"dropped: dev-dependency without version".to_owned()
+			} else if !has_version {
+				blocking += 1;
+				"ERROR: cargo refuses to publish without a version".to_owned()
+			} else if released.contains(&dep.package_name()) {
+				format!("released alongside, published as {}", dep.version_req())
+			} else {
+				format!("{} stripped, must be on the registry as {}", kind, dep.version_req())
+			};
+			rows.push([
+				pkg.name().to_string(),
+				dep.package_name().to_string(),
+				format!("{} ({:?})", kind, dep.kind()),
+				outcome,
+			]);
+		}
+	}
+
+	let c = ws.config();
+	if rows.is_empty() {
+		c.shell().status("Audited", "All dependencies come from a registry")?;
+		return Ok(())
+	}
+
+	println!("{}", render_table(["crate", "dependency", "source", "on publish"], &rows));
+
+	if blocking > 0 {
+		anyhow::bail!("{} dependencies would keep their crate from being published", blocking)
+	}
+	Ok(())
+}
+
+/// The category slugs crates.io knows, see https://crates.io/category_slugs
+const CATEGORY_SLUGS: &[&str] = &[
+	"accessibility",
+	"aerospace",
+	"aerospace::drones",
+	"aerospace::protocols",
+	"aerospace::simulation",
+	"aerospace::space-protocols",
+	"aerospace::unmanned-aerial-vehicles",
+	"algorithms",
+	"api-bindings",
+	"asynchronous",
+	"authentication",
+	"caching",
+	"command-line-interface",
+	"command-line-utilities",
+	"compilers",
+	"compression",
+	"computer-vision",
+	"concurrency",
+	"config",
+	"cryptography",
+	"cryptography::cryptocurrencies",
+	"data-structures",
+	"database",
+	"database-implementations",
+	"date-and-time",
+	"development-tools",
+	"development-tools::build-utils",
+	"development-tools::cargo-plugins",
+	"development-tools::debugging",
+	"development-tools::ffi",
+	"development-tools::procedural-macro-helpers",
+	"development-tools::profiling",
+	"development-tools::testing",
+	"email",
+	"embedded",
+	"emulators",
+	"encoding",
+	"external-ffi-bindings",
+	"filesystem",
+	"finance",
+	"game-development",
+	"game-engines",
+	"games",
+	"graphics",
+	"gui",
+	"hardware-support",
+	"internationalization",
+	"localization",
+	"mathematics",
+	"memory-management",
+	"multimedia",
+	"multimedia::audio",
+	"multimedia::encoding",
+	"multimedia::images",
+	"multimedia::video",
+	"network-programming",
+	"no-std",
+	"no-std::no-alloc",
+	"os",
+	"os::android-apis",
+	"os::freebsd-apis",
+	"os::linux-apis",
+	"os::macos-apis",
+	"os::unix-apis",
+	"os::windows-apis",
+	"parser-implementations",
+	"parsing",
+	"rendering",
+	"rendering::data-formats",
+	"rendering::engine",
+	"rendering::graphics-api",
+	"rust-patterns",
+	"science",
+	"science::bioinformatics",
+	"science::geo",
+	"science::neuroscience",
+	"science::robotics",
+	"simulation",
+	"template-engine",
+	"text-editors",
+	"text-processing",
+	"value-formatting",
+	"virtualization",
+	"visualization",
+	"wasm",
+	"web-programming",
+	"web-programming::http-client",
+	"web-programming::http-server",
+	"web-programming::websocket",
+];
+
 // ensure metadata is set
 // https://doc.rust-lang.org/cargo/reference/publishing.html#before-publishing-a-new-crate
-fn check_metadata(package: &Package) -> Result<(), anyhow::Error> {
+pub(crate) fn metadata_problems(package: &Package) -> Vec<String> {
 	let metadata = package.manifest().metadata();
 	let mut bad_fields = Vec::new();
 	match metadata.description.as_deref() {
-		Some("") => bad_fields.push("description is empty"),
-		None => bad_fields.push("description is missing"),
+		Some("") => bad_fields.push("description is empty".to_owned()),
+		None => bad_fields.push("description is missing".to_owned()),
 		_ => {},
 	}
 	match metadata.repository.as_deref() {
-		Some("") => bad_fields.push("repository is empty"),
-		None => bad_fields.push("repository is missing"),
+		Some("") => bad_fields.push("repository is empty".to_owned()),
+		None => bad_fields.push("repository is missing".to_owned()),
 		_ => {},
 	}
 	match (metadata.license.as_ref(), metadata.license_file.as_ref()) {
 		(Some(s), None) | (None, Some(s)) if !s.is_empty() => {},
-		(Some(_), Some(_)) => bad_fields.push("You can't have license AND license_file"),
-		_ => bad_fields.push("Neither license nor license_file is provided"),
+		(Some(_), Some(_)) => bad_fields.push("You can't have license AND license_file".to_owned()),
+		_ => bad_fields.push("Neither license nor license_file is provided".to_owned()),
 	}
 	if metadata.keywords.len() > 5 {
-		bad_fields.push("crates.io only allows up to 5 keywords")
+		bad_fields.push("crates.io only allows up to 5 keywords".to_owned())
+	}
+	for keyword in metadata.keywords.iter().filter(|k| k.chars().count() > 20) {
+		bad_fields.push(format!("keyword {} is longer than 20 characters", keyword))
+	}
+	if metadata.categories.len() > 5 {
+		bad_fields.push("crates.io only allows up to 5 categories".to_owned())
 	}
+	for category in metadata.categories.iter().filter(|c| !CATEGORY_SLUGS.contains(&c.as_str())) {
+		bad_fields.push(format!("category {} isn't known to crates.io", category))
+	}
+	if let Some(readme) = &metadata.readme {
+		if !package.root().join(readme).is_file() {
+			bad_fields.push(format!("readme {} doesn't exist", readme))
+		}
+	}
+	bad_fields
+}
 
+fn check_metadata(package: &Package) -> Result<(), anyhow::Error> {
+	let bad_fields = metadata_problems(package);
 	if bad_fields.is_empty() {
 		Ok(())
 	} else {
@@ -187,68 +529,91 @@ fn check_metadata(package: &Package) -> Result<(), anyhow::Error> {
 }
 
 #[cfg(feature = "gen-readme")]
-fn check_readme<'a>(ws: &Workspace<'a>, pkg: &Package) -> Result<(), anyhow::Error> {
-	let pkg_path = pkg.manifest_path().parent().expect("Folder exists");
-	readme::check_pkg_readme(ws, pkg_path, pkg.manifest())
+fn check_readme<'a>(
+	ws: &Workspace<'a>,
+	packages: &[Package],
+	doc_base_url: Option<&str>,
+) -> Vec<String> {
+	let links = match readme::DocLinks::new(ws, doc_base_url) {
+		Ok(links) => links,
+		Err(e) => return vec![format!("Checking Readme files failed with: {:}", e)],
+	};
+	packages.iter().fold(Vec::new(), |mut res, pkg| {
+		if let Err(e) = readme::check_pkg_readme(ws, pkg, &links) {
+			res.push(format!("{:}: Checking Readme file failed with: {:}", pkg.name(), e));
+		}
+		res
+	})
 }
 
 #[cfg(not(feature = "gen-readme"))]
-fn check_readme<'a>(_ws: &Workspace<'a>, _pkg: &Package) -> Result<(), anyhow::Error> {
+fn check_readme<'a>(
+	_ws: &Workspace<'a>,
+	_packages: &[Package],
+	_doc_base_url: Option<&str>,
+) -> Vec<String> {
 	unreachable!()
 }
 
+/// Run the soft checks on the manifests of `packages`: their metadata and that git dependencies
+/// have a version. Reports all problems found before failing.
+pub fn validate(packages: &[Package], ws: &Workspace<'_>) -> Result<(), anyhow::Error> {
+	ws.config().shell().status("Checking", "Metadata & Dependencies")?;
+
+	let errors = packages.iter().fold(Vec::new(), |mut res, pkg| {
+		if let Err(e) = check_metadata(pkg) {
+			res.push(e);
+		}
+		if let Err(e) = check_dependencies(pkg) {
+			res.push(e);
+		}
+		res
+	});
+
+	errors.iter().for_each(|s| error!("{:#?}", s));
+	if !errors.is_empty() {
+		anyhow::bail!("Soft checkes failed with {} errors (see above)", errors.len())
+	}
+	Ok(())
+}
+
+/// Pack each of `packages` and verify it builds on its own, with the other ones it depends on
+/// as if they were published already.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
 pub fn check<'a>(
 	packages: &[Package],
 	ws: &Workspace<'a>,
-	build: bool,
+	build_opts: &BuildOptions,
 	check_readme: bool,
+	doc_base_url: Option<&str>,
+	metadata_only: bool,
+	verify_only: Option<&HashSet<InternedString>>,
 ) -> Result<(), anyhow::Error> {
 	let c = ws.config();
 
-	// FIXME: make build config configurable
-	//        https://github.com/paritytech/cargo-unleash/issues/20
 	let opts = PackageOpts {
 		config: c,
 		verify: false,
 		check_metadata: true,
 		list: false,
 		allow_dirty: true,
-		jobs: None,
+		jobs: build_opts.jobs,
 		to_package: ops::Packages::Default,
-		targets: Default::default(),
-		cli_features: CliFeatures {
-			features: Default::default(),
-			all_features: false,
-			uses_default_features: true,
-		},
-		keep_going: false,
+		targets: build_opts.targets.clone(),
+		cli_features: CliFeatures::from_command_line(
+			&build_opts.features,
+			build_opts.all_features,
+			!build_opts.no_default_features,
+		)?,
+		keep_going: build_opts.keep_going,
 	};
 
-	c.shell().status("Checking", "Metadata & Dependencies")?;
-
-	let errors = packages.iter().fold(Vec::new(), |mut res, pkg| {
-		if let Err(e) = check_metadata(pkg) {
-			res.push(e);
-		}
-		if let Err(e) = check_dependencies(pkg) {
-			res.push(e);
-		}
-		res
-	});
-
-	errors.iter().for_each(|s| error!("{:#?}", s));
-	if !errors.is_empty() {
-		anyhow::bail!("Soft checkes failed with {} errors (see above)", errors.len())
-	}
+	validate(packages, ws)?;
 
 	if check_readme {
 		c.shell().status("Checking", "Readme files")?;
-		let errors = packages.iter().fold(Vec::new(), |mut res, pkg| {
-			if let Err(e) = self::check_readme(ws, pkg) {
-				res.push(format!("{:}: Checking Readme file failed with: {:}", pkg.name(), e));
-			}
-			res
-		});
+		let errors = self::check_readme(ws, packages, doc_base_url);
 
 		errors.iter().for_each(|s| error!("{:#?}", s));
 		if !errors.is_empty() {
@@ -256,12 +621,23 @@ pub fn check<'a>(
 		}
 	}
 
+	if metadata_only {
+		c.shell().status("Skipping", "Packing & Verifying (metadata only)")?;
+		return Ok(())
+	}
+
+	// by default the crates are packed into the workspace's `target` and built within their
+	// unpacked folder each
+	let custom_target_dir =
+		build_opts.target_dir.as_ref().map(|p| Filesystem::new(c.cwd().join(p)));
+	let target_dir = custom_target_dir.clone().unwrap_or_else(|| ws.target_dir());
+
 	let builds = packages.iter().map(|pkg| {
 		check_metadata(pkg)?;
 
-		let pkg_ws = Workspace::ephemeral(pkg.clone(), c, Some(ws.target_dir()), true)?;
-		c.shell().status("Packing", &pkg)?;
-		match package(&pkg_ws, &opts) {
+		let pkg_ws = Workspace::ephemeral(pkg.clone(), c, Some(target_dir.clone()), true)?;
+		c.shell().status("Packing", pkg)?;
+		match metrics::time("pack", Some(&pkg.name()), |_| 1, || package(&pkg_ws, &opts)) {
 			Ok(Some(mut rw_lock)) if rw_lock.len() == 1 =>
 				Ok((pkg_ws, rw_lock.pop().expect("we checked the counter"))),
 			Ok(Some(_rw_lock)) =>
@@ -283,7 +659,7 @@ pub fn check<'a>(
 		anyhow::bail!("Packing failed with {} errors (see above)", errors.len());
 	};
 
-	let build_mode = if build { CompileMode::Build } else { CompileMode::Check { test: false } };
+	let build_mode = build_mode(build_opts);
 
 	c.shell().status("Checking", "Packages")?;
 
@@ -293,22 +669,221 @@ pub fn check<'a>(
 	// the packages can be build free of the workspace they orginated but together
 	// with the other packages queued for release.
 	let mut replaces = HashMap::new();
+	let packed = successes.into_iter().filter_map(Result::ok).collect::<Vec<_>>();
+	// crates not depending on each other are verified at the same time, sharing the jobs
+	let batch_size = build_opts.jobs.unwrap_or(1).max(1) as usize;
+
+	let cache_path = target_dir.as_path_unlocked().join(FINGERPRINTS_FILE);
+	let mut cached =
+		if build_opts.no_cache { HashMap::new() } else { load_fingerprints(&cache_path) };
+	let resolve = ops::load_pkg_lockfile(ws)?;
+	let setup = short_hash(&(
+		build_opts.build,
+		&build_opts.features,
+		build_opts.all_features,
+		build_opts.no_default_features,
+		&build_opts.targets,
+	));
+	let mut fingerprints = HashMap::new();
+	let (mut errors, mut failed) = (Vec::new(), HashSet::new());
+
+	for level in dependency_levels(packages) {
+		let mut unpacked = Vec::new();
+		for batch in level.chunks(batch_size) {
+			let (mut running, mut verified) = (Vec::new(), Vec::new());
+			for &idx in batch {
+				let (pkg_ws, rw_lock) = &packed[idx];
+				let pkg = pkg_ws.current().expect("We've build localised workspaces. qed");
+				// it would get the failed one from the registry instead, if at all
+				if pkg.dependencies().iter().any(|d| failed.contains(&d.package_name())) {
+					c.shell().status("Skipping", format!("{} (dependency failed)", pkg))?;
+					failed.insert(pkg.name());
+					continue
+				}
+				// the ones it depends on are all of earlier levels
+				let fingerprint = fingerprint(c, pkg, resolve.as_ref(), &fingerprints, &setup)?;
+				let fresh = cached.get(pkg.name().as_str()) == Some(&fingerprint);
+				let mut verify = verify_only.map(|v| v.contains(&pkg.name())).unwrap_or(true);
+				if !verify {
+					c.shell().status("Skipping", format!("{} (unchanged)", pkg))?;
+				} else if fresh {
+					c.shell().status("Fresh", pkg)?;
+					verify = false;
+				} else {
+					c.shell().status("Verfying", pkg)?;
+				}
+				fingerprints.insert(pkg.name().as_str().to_owned(), fingerprint.clone());
+				if batch.len() == 1 || !verify {
+					let ws = metrics::time(
+						"verify",
+						Some(&pkg.name()),
+						|_| verify as usize,
+						|| {
+							run_check(
+								pkg_ws,
+								rw_lock,
+								&opts,
+								build_mode,
+								&replaces,
+								verify,
+								custom_target_dir.as_ref(),
+							)
+						},
+					);
+					match ws {
+						Ok(ws) => unpacked.push(ws),
+						Err(e) if build_opts.keep_going => {
+							error!("{:?}", e);
+							errors.push(e);
+							failed.insert(pkg.name());
+							continue
+						},
+						Err(e) => return Err(e),
+					}
+					if verify {
+						verified.push((pkg.name(), fingerprint));
+					}
+					continue
+				}
+
+				// unpack here, as the others of the level are added to `replaces` only after
+				let ws = run_check(
+					pkg_ws,
+					rw_lock,
+					&opts,
+					build_mode,
+					&replaces,
+					false,
+					custom_target_dir.as_ref(),
+				)?;
+				let dst = unpacked_dir(&ws).to_path_buf();
+				unpacked.push(ws);
+				let (config, build_opts) = (config_like(c), build_opts.clone());
+				let target_dir = custom_target_dir.clone();
+				let jobs = build_opts.jobs.map(|j| (j / batch.len() as u32).max(1));
+				let handle = thread::spawn(move || {
+					let start = Instant::now();
+					(verify_isolated(config, &dst, &build_opts, jobs, target_dir), start.elapsed())
+				});
+				running.push((pkg.name(), fingerprint, handle));
+			}
 
-	for (pkg_ws, rw_lock) in successes.iter().filter_map(|e| e.as_ref().ok()) {
-		c.shell()
-			.status("Verfying", pkg_ws.current().expect("We've build localised workspaces. qed"))?;
-		let ws = run_check(pkg_ws, rw_lock, &opts, build_mode, &replaces)?;
-		let new_pkg = ws.current().expect("Each workspace is for a package!");
-		replaces.insert(
-			new_pkg.name().as_str().to_owned(),
-			new_pkg
-				.manifest_path()
-				.parent()
-				.expect("Folder exists")
-				.to_str()
-				.expect("Is stringifiable")
-				.to_owned(),
-		);
+			for (name, fingerprint, handle) in running {
+				let (res, duration) = handle.join().unwrap_or_else(|_| {
+					(Err(anyhow::anyhow!("Verifying {} panicked", name)), Default::default())
+				});
+				metrics::record("verify", Some(&name), 1, duration);
+				match res {
+					Ok(()) => verified.push((name, fingerprint)),
+					Err(e) if build_opts.keep_going => {
+						error!("{:?}", e);
+						errors.push(e);
+						failed.insert(name);
+					},
+					Err(e) => return Err(e),
+				}
+			}
+
+			if !verified.is_empty() {
+				cached.extend(verified.into_iter().map(|(name, f)| (name.as_str().to_owned(), f)));
+				create_dir_all(cache_path.parent().expect("Is within the target dir. qed"))?;
+				write(&cache_path, serde_json::to_string_pretty(&cached)?)
+					.context("Could not write fingerprints")?;
+			}
+		}
+
+		// only once all of a level is through, so crates get all they depend on at once
+		for ws in unpacked {
+			let new_pkg = ws.current().expect("Each workspace is for a package!");
+			if failed.contains(&new_pkg.name()) {
+				continue
+			}
+			replaces.insert(
+				new_pkg.name().as_str().to_owned(),
+				unpacked_dir(&ws).to_str().expect("Is stringifiable").to_owned(),
+			);
+		}
+	}
+
+	if !errors.is_empty() {
+		anyhow::bail!("Verifying failed with {} errors (see above)", errors.len());
 	}
 	Ok(())
 }
+
+/// The folder of the package `ws` was made for
+fn unpacked_dir<'w>(ws: &'w Workspace<'_>) -> &'w Path {
+	ws.current()
+		.expect("Each workspace is for a package!")
+		.manifest_path()
+		.parent()
+		.expect("Folder exists")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use cargo::ops::read_package;
+
+	/// The problems `metadata_problems` finds with a crate of the given `[package]` fields
+	fn problems_with(fields: &str) -> Vec<String> {
+		let temp = assert_fs::TempDir::new().unwrap();
+		let manifest = temp.path().join("Cargo.toml");
+		write(
+			&manifest,
+			format!(
+				"[package]\nname = \"checked\"\nversion = \"0.1.0\"\ndescription = \"d\"\n\
+				 repository = \"https://example.com\"\nlicense = \"MIT\"\n{}\n",
+				fields
+			),
+		)
+		.unwrap();
+		create_dir_all(temp.path().join("src")).unwrap();
+		write(temp.path().join("src/lib.rs"), "").unwrap();
+		write(temp.path().join("README.md"), "").unwrap();
+
+		let config = Config::default().unwrap();
+		let source = SourceId::for_path(temp.path()).unwrap();
+		let (pkg, _) = read_package(&manifest, source, &config).unwrap();
+		metadata_problems(&pkg)
+	}
+
+	#[test]
+	fn valid_metadata() {
+		assert!(problems_with("").is_empty());
+		assert!(problems_with(
+			r#"keywords = ["a", "b"]
+categories = ["development-tools::cargo-plugins"]
+readme = "README.md""#
+		)
+		.is_empty());
+	}
+
+	#[test]
+	fn long_keywords() {
+		assert_eq!(
+			problems_with(r#"keywords = ["short", "much-longer-than-twenty"]"#),
+			vec!["keyword much-longer-than-twenty is longer than 20 characters"]
+		);
+	}
+
+	#[test]
+	fn categories() {
+		assert_eq!(
+			problems_with(r#"categories = ["gui", "no-such-category"]"#),
+			vec!["category no-such-category isn't known to crates.io"]
+		);
+		assert_eq!(
+			problems_with(r#"categories = ["gui", "os", "games", "email", "config", "wasm"]"#),
+			vec!["crates.io only allows up to 5 categories"]
+		);
+	}
+
+	#[test]
+	fn missing_readme() {
+		assert_eq!(
+			problems_with(r#"readme = "MISSING.md""#),
+			vec!["readme MISSING.md doesn't exist"]
+		);
+	}
+}
@@ -1,12 +1,20 @@
-use crate::util::{edit_each, edit_each_dep, members_deep, DependencyAction};
+use crate::util::{
+	edit_each, edit_each_dep, members_deep, prune_empty_sections, DependencyAction, PackageProgress,
+};
 use cargo::core::{package::Package, Workspace};
 // use log::trace;
 use std::process::Command;
 
+/// Remove the dependencies of the packages matching `predicate` not used in their sources,
+/// as found by `rg`. With `check_only`, nothing is changed, but unused ones fail the check.
+///
+/// Follows `util::set_dependency_depth`, `util::set_quiet`, `dry_run::enable` and `metrics::enable`
+/// as set on the calling thread.
 pub fn clean_up_unused_dependencies<P>(
 	ws: &Workspace<'_>,
 	predicate: P,
 	check_only: bool,
+	prune_empty: bool,
 ) -> Result<(), anyhow::Error>
 where
 	P: Fn(&Package) -> bool,
@@ -14,17 +22,19 @@ where
 	let c = ws.config();
 
 	// inspired by https://gist.github.com/sinkuu/8083240257c485c9f928744b41bbac98
-	let total = edit_each(members_deep(ws).iter().filter(|p| predicate(p)), |p, doc| {
-		c.shell().status("Checking", p.name())?;
+	let packages = members_deep(ws)?.into_iter().filter(|p| predicate(p)).collect::<Vec<_>>();
+	let progress = PackageProgress::new(c, "Checking", packages.len());
+	let total = edit_each(packages.iter(), |p, doc| {
+		progress.tick(p)?;
 		let source_path = p.root();
 		let root = doc.as_table_mut();
-		Ok(edit_each_dep(root, |p_name, alias, _table, _| {
+		let removed = edit_each_dep(root, |p_name, alias, _table, _| {
 			let name = alias.unwrap_or(p_name);
 			let found = Command::new("rg")
-				.args(&["--type", "rust"])
+				.args(["--type", "rust"])
 				.arg("-qw")
 				.arg(name.replace('-', "_"))
-				.arg(&source_path)
+				.arg(source_path)
 				.status()
 				.unwrap()
 				.success();
@@ -40,7 +50,11 @@ where
 			} else {
 				DependencyAction::Untouched
 			}
-		}))
+		});
+		if prune_empty && !check_only {
+			prune_empty_sections(root);
+		}
+		Ok(removed)
 	})
 	.map(|v| v.iter().sum::<u32>());
 
@@ -48,7 +62,8 @@ where
 		t if t > 0 && check_only => {
 			anyhow::bail!("Aborting: {:} unused dependencies found. See shell output for more.", t)
 		},
-		_ => {},
+		0 => c.shell().status("Done", "No unused dependencies")?,
+		t => c.shell().status("Done", format!("{} unused dependencies removed", t))?,
 	}
 	Ok(())
 }
@@ -0,0 +1,93 @@
+use crate::util::{members_deep, reload_workspace};
+use anyhow::Context;
+use cargo::{core::Workspace, util::interning::InternedString};
+use git2::{build::TreeUpdateBuilder, FileMode, Repository};
+use semver::Version;
+use std::{collections::HashMap, fs, path::PathBuf};
+
+/// The manifests and versions of the workspace members before a command changed them
+pub struct Snapshot {
+	manifests: HashMap<PathBuf, String>,
+	versions: HashMap<InternedString, Version>,
+}
+
+/// Keep the manifests and versions of all members of `ws` as they are now, along with the root
+/// manifest the shared `[workspace.package]` and `[workspace.dependencies]` are in
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn snapshot(ws: &Workspace<'_>) -> Result<Snapshot, anyhow::Error> {
+	let members = members_deep(ws)?;
+	let mut manifests = HashMap::new();
+	let paths = members.iter().map(|p| p.manifest_path()).chain(Some(ws.root_manifest()));
+	for path in paths {
+		manifests.insert(path.to_path_buf(), fs::read_to_string(path)?);
+	}
+	let versions = members.iter().map(|p| (p.name(), p.version().clone())).collect();
+	Ok(Snapshot { manifests, versions })
+}
+
+/// Commit the manifests changed since `before` with the message `template`, in which
+/// `{count}` is replaced by the number of crates with a new version and `{crates}` by them as
+/// `name version`, comma separated. Other changes in the working tree aren't staged, the ones
+/// staged before aren't committed. Skipped if the workspace isn't within a git repository.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn commit_changes(
+	ws: &Workspace<'_>,
+	before: &Snapshot,
+	template: &str,
+) -> Result<(), anyhow::Error> {
+	let c = ws.config();
+	let repo = match Repository::discover(ws.root()) {
+		Ok(repo) => repo,
+		Err(_) => {
+			c.shell().warn("Not committing, the workspace isn't within a git repository")?;
+			return Ok(())
+		},
+	};
+	let repo_root = repo.workdir().context("Git repository has no working directory")?;
+
+	let mut changed = Vec::new();
+	for (path, content) in before.manifests.iter() {
+		if &fs::read_to_string(path)? != content {
+			changed.push(path.strip_prefix(repo_root)?.to_path_buf());
+		}
+	}
+	if changed.is_empty() {
+		c.shell().status("Skipping", "commit, no manifests were changed")?;
+		return Ok(())
+	}
+	changed.sort();
+
+	let ws = reload_workspace(ws.root_manifest(), c)?;
+	let mut bumped = members_deep(&ws)?
+		.iter()
+		.filter(|p| before.versions.get(&p.name()) != Some(p.version()))
+		.map(|p| format!("{} {}", p.name(), p.version()))
+		.collect::<Vec<_>>();
+	bumped.sort();
+	let message = template
+		.replace("{count}", &bumped.len().to_string())
+		.replace("{crates}", &bumped.join(", "));
+
+	// the tree of `HEAD` with just the manifests changed, not whatever else is staged
+	let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+	let baseline = match &parent {
+		Some(parent) => parent.tree()?,
+		None => repo.find_tree(repo.treebuilder(None)?.write()?)?,
+	};
+	let mut update = TreeUpdateBuilder::new();
+	let mut index = repo.index()?;
+	for path in changed.iter() {
+		update.upsert(path.as_path(), repo.blob_path(&repo_root.join(path))?, FileMode::Blob);
+		index.add_path(path)?;
+	}
+	index.write()?;
+	let tree = repo.find_tree(update.create_updated(&repo, &baseline)?)?;
+	let sig = repo.signature().context("No git user configured to commit as")?;
+	let oid =
+		repo.commit(Some("HEAD"), &sig, &sig, &message, &tree, &parent.iter().collect::<Vec<_>>())?;
+	c.shell()
+		.status("Committed", format!("{} manifests as {:.7}", changed.len(), oid))?;
+	Ok(())
+}
@@ -1,11 +1,101 @@
-use crate::util::edit_each;
+use crate::util::{edit_each, prune_empty_sections};
+use anyhow::Context;
 use cargo::core::package::Package;
+use git2::Repository;
+use std::{
+	collections::{HashMap, HashSet},
+	path::PathBuf,
+};
+use toml_edit::{Document, Item};
 
 /// Deactivate the Dev Dependencies Section of the given toml
-pub fn deactivate_dev_dependencies<'a, I>(iter: I) -> Result<(), anyhow::Error>
+///
+/// Dev dependencies listed in `keep` stay in place, the section is only removed entirely
+/// if none are kept.
+///
+/// Follows `dry_run::enable` as set on the calling thread.
+pub fn deactivate_dev_dependencies<'a, I>(
+	iter: I,
+	prune_empty: bool,
+	keep: &HashSet<String>,
+) -> Result<(), anyhow::Error>
 where
 	I: Iterator<Item = &'a Package>,
 {
-	edit_each(iter, |_, doc| Ok(doc.as_table_mut().remove("dev-dependencies")))?;
+	edit_each(iter, |_, doc| {
+		let root = doc.as_table_mut();
+		if let Some(deps) = root.get_mut("dev-dependencies").and_then(|d| d.as_table_like_mut()) {
+			let remove = deps
+				.iter()
+				.map(|(k, _)| k.to_owned())
+				.filter(|k| !keep.contains(k))
+				.collect::<Vec<_>>();
+			for k in remove {
+				deps.remove(&k);
+			}
+			if deps.is_empty() {
+				root.remove("dev-dependencies");
+			}
+		}
+		if prune_empty {
+			prune_empty_sections(root);
+		}
+		Ok(())
+	})?;
+	Ok(())
+}
+
+/// Restore the Dev Dependencies Section of the given packages' toml as it is in the git HEAD
+///
+/// Only `[dev-dependencies]` is touched, other changes to the manifests stay. Fails without
+/// changing any manifest if one of them isn't tracked in git.
+///
+/// Follows `dry_run::enable` as set on the calling thread.
+pub fn restore_dev_dependencies<'a, I>(iter: I) -> Result<(), anyhow::Error>
+where
+	I: Iterator<Item = &'a Package>,
+{
+	let packages = iter.collect::<Vec<_>>();
+	let first = match packages.first() {
+		Some(pkg) => pkg,
+		None => return Ok(()),
+	};
+	let repo = Repository::discover(first.root()).context("Workspace isn't within a git repo")?;
+	let repo_root = repo.workdir().context("Git repository has no working directory")?;
+	let head = repo
+		.head()
+		.and_then(|h| h.peel_to_tree())
+		.context("Could not determine current git HEAD")?;
+
+	let mut originals: HashMap<PathBuf, Option<Item>> = HashMap::new();
+	for pkg in packages.iter() {
+		let manifest_path = pkg.manifest_path();
+		let not_tracked = || format!("{} isn't tracked in git", manifest_path.display());
+		let relative = manifest_path.strip_prefix(repo_root).with_context(not_tracked)?;
+		let blob = head
+			.get_path(relative)
+			.and_then(|e| e.to_object(&repo))
+			.and_then(|o| o.peel_to_blob())
+			.with_context(not_tracked)?;
+		let doc: Document = std::str::from_utf8(blob.content())
+			.context("Manifest isn't valid UTF-8")
+			.and_then(|c| Ok(c.parse::<Document>()?))
+			.with_context(|| format!("Could not parse {} of git HEAD", relative.display()))?;
+		originals
+			.insert(manifest_path.to_path_buf(), doc.as_table().get("dev-dependencies").cloned());
+	}
+
+	edit_each(packages.into_iter(), |pkg, doc| {
+		let root = doc.as_table_mut();
+		match originals.get(pkg.manifest_path()).expect("Read for all packages. qed") {
+			Some(deps) => {
+				root.insert("dev-dependencies", deps.clone());
+			},
+			None => {
+				root.remove("dev-dependencies");
+			},
+		}
+		Ok(())
+	})?;
 	Ok(())
 }
@@ -0,0 +1,170 @@
+use crate::{
+	commands::{check::metadata_problems, set_field},
+	config::MetadataDefaults,
+	dry_run,
+	util::{reload_workspace, render_table},
+};
+use anyhow::Context;
+use cargo::core::{package::Package, Workspace};
+use git2::Repository;
+use std::collections::HashMap;
+use toml_edit::Value;
+
+/// Turn the url of a git remote into the one of the repository's web page, e.g.
+/// `git@github.com:org/repo.git` into `https://github.com/org/repo`.
+fn web_url(remote: &str) -> String {
+	let url = remote.trim_end_matches('/').trim_end_matches(".git");
+	if let Some((host, path)) = url.strip_prefix("git@").and_then(|r| r.split_once(':')) {
+		format!("https://{}/{}", host, path)
+	} else if let Some(rest) = url.strip_prefix("ssh://git@") {
+		format!("https://{}", rest)
+	} else {
+		url.to_owned()
+	}
+}
+
+/// The `repository` to fill in: the configured one or the url of the `origin` remote
+fn default_repository(
+	ws: &Workspace<'_>,
+	defaults: &MetadataDefaults,
+) -> Result<Option<String>, anyhow::Error> {
+	if defaults.repository.is_some() {
+		return Ok(defaults.repository.clone())
+	}
+	let repo = match Repository::discover(ws.root()) {
+		Ok(repo) => repo,
+		Err(_) => return Ok(None),
+	};
+	let url = match repo.find_remote("origin") {
+		Ok(remote) => remote.url().map(web_url),
+		Err(_) => None,
+	};
+	Ok(url)
+}
+
+/// Report the missing or bad package metadata of the packages matching predicate. With `fix`,
+/// the missing `description`, `repository` and `license` are filled in from `defaults` first,
+/// fields already set are left alone. Fails if any problems remain.
+pub fn fix_metadata<P>(
+	ws: &Workspace<'_>,
+	predicate: P,
+	defaults: &MetadataDefaults,
+	fix: bool,
+) -> Result<(), anyhow::Error>
+where
+	P: Fn(&Package) -> bool,
+{
+	let c = ws.config();
+	let selected = ws.members().filter(|p| predicate(p)).cloned().collect::<Vec<_>>();
+
+	let mut filled: HashMap<String, Vec<&str>> = HashMap::new();
+	if fix {
+		let repository = default_repository(ws, defaults)?;
+		for pkg in selected.iter() {
+			let metadata = pkg.manifest().metadata();
+			let mut fields = Vec::new();
+			if metadata.description.is_none() {
+				if let Some(template) = &defaults.description {
+					fields.push(("description", template.replace("{name}", &pkg.name())));
+				}
+			}
+			if metadata.repository.is_none() {
+				if let Some(repository) = &repository {
+					fields.push(("repository", repository.clone()));
+				}
+			}
+			if metadata.license.is_none() && metadata.license_file.is_none() {
+				if let Some(license) = &defaults.license {
+					fields.push(("license", license.clone()));
+				}
+			}
+
+			for (field, value) in fields {
+				set_field(
+					std::iter::once(pkg),
+					"package".to_owned(),
+					field.to_owned(),
+					Value::from(value),
+					true,
+				)
+				.with_context(|| format!("Setting {} of {} failed", field, pkg.name()))?;
+				filled.entry(pkg.name().to_string()).or_default().push(field);
+			}
+		}
+	}
+
+	// on a dry run nothing was written to re-read, leave out the problems fixed in memory
+	let ws = if filled.is_empty() || dry_run::enabled() {
+		None
+	} else {
+		Some(reload_workspace(ws.root_manifest(), c)?)
+	};
+	let packages = match &ws {
+		Some(ws) => ws
+			.members()
+			.filter(|p| selected.iter().any(|s| s.name() == p.name()))
+			.cloned()
+			.collect::<Vec<_>>(),
+		None => selected,
+	};
+
+	let mut remaining = 0;
+	let rows = packages
+		.iter()
+		.filter_map(|pkg| {
+			let fields = filled.get(pkg.name().as_str());
+			let problems = metadata_problems(pkg)
+				.into_iter()
+				.filter(|p| ws.is_some() || !fixed_by(p, fields.map(|f| &f[..]).unwrap_or(&[])))
+				.collect::<Vec<_>>();
+			remaining += problems.len();
+			if problems.is_empty() && fields.is_none() {
+				return None
+			}
+			Some([
+				pkg.name().to_string(),
+				fields.map(|f| f.join(", ")).unwrap_or_default(),
+				problems.join("; "),
+			])
+		})
+		.collect::<Vec<_>>();
+
+	if rows.is_empty() {
+		c.shell().status("Done", "All metadata present")?;
+		return Ok(())
+	}
+	println!("{}", render_table(["crate", "filled", "needs attention"], &rows));
+	if remaining > 0 {
+		anyhow::bail!("{} metadata problems need manual attention", remaining)
+	}
+	Ok(())
+}
+
+/// Whether filling in `fields` fixes the metadata `problem`
+fn fixed_by(problem: &str, fields: &[&str]) -> bool {
+	fields.iter().any(|f| match *f {
+		"license" => problem.starts_with("Neither license nor license_file"),
+		field => problem == format!("{} is missing", field),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn remote_urls() {
+		assert_eq!(
+			web_url("git@github.com:paritytech/cargo-unleash.git"),
+			"https://github.com/paritytech/cargo-unleash"
+		);
+		assert_eq!(
+			web_url("https://github.com/paritytech/cargo-unleash.git"),
+			"https://github.com/paritytech/cargo-unleash"
+		);
+		assert_eq!(
+			web_url("ssh://git@gitlab.com/parity/unleash"),
+			"https://gitlab.com/parity/unleash"
+		);
+	}
+}
@@ -0,0 +1,121 @@
+use crate::util::{render_table, DependencySection};
+use anyhow::Context;
+use std::{
+	fs::read_to_string,
+	path::{Path, PathBuf},
+};
+use toml_edit::{Document, Item};
+
+/// The `[package]` fields reported on
+const FIELDS: [&str; 4] = ["version", "edition", "license", "authors"];
+
+/// Whether the entry is `{ workspace = true }` or `field.workspace = true`
+fn is_inherited(item: &Item) -> bool {
+	item.get("workspace").and_then(|w| w.as_bool()).unwrap_or(false)
+}
+
+/// Find the manifests of the workspace members from the root manifest alone, so we don't
+/// need cargo to understand the inheritance.
+fn member_manifests(root_manifest: &Path, root: &Document) -> Result<Vec<PathBuf>, anyhow::Error> {
+	let base = root_manifest.parent().expect("Manifest is always within a folder. qed");
+	let patterns = |key: &str| -> Vec<String> {
+		root.get("workspace")
+			.and_then(|w| w.get(key))
+			.and_then(|m| m.as_array())
+			.map(|a| a.iter().filter_map(|v| v.as_str().map(|s| s.to_owned())).collect())
+			.unwrap_or_default()
+	};
+
+	let mut excluded = Vec::new();
+	for pattern in patterns("exclude") {
+		excluded.push(base.join(pattern));
+	}
+
+	let mut manifests = Vec::new();
+	if root.get("package").is_some() {
+		manifests.push(root_manifest.to_path_buf());
+	}
+	for pattern in patterns("members") {
+		let full = base.join(&pattern);
+		let paths = glob::glob(&full.to_string_lossy())
+			.with_context(|| format!("Invalid members pattern {}", pattern))?;
+		for path in paths {
+			let path = path?;
+			let manifest = path.join("Cargo.toml");
+			if manifest.is_file() && !excluded.iter().any(|e| path.starts_with(e)) {
+				manifests.push(manifest);
+			}
+		}
+	}
+	Ok(manifests)
+}
+
+/// Count the dependency entries in `root` (and its `target` tables): (inherited, total)
+fn count_deps(root: &Item) -> (usize, usize) {
+	let mut tables = vec![root];
+	if let Some(targets) = root.get("target").and_then(|t| t.as_table_like()) {
+		tables.extend(targets.iter().map(|(_, t)| t));
+	}
+
+	let (mut inherited, mut total) = (0, 0);
+	for table in tables {
+		for section in
+			[DependencySection::Regular, DependencySection::Dev, DependencySection::Build]
+		{
+			if let Some(deps) = table.get(section.key()).and_then(|d| d.as_table_like()) {
+				for (_, dep) in deps.iter() {
+					total += 1;
+					if is_inherited(dep) {
+						inherited += 1;
+					}
+				}
+			}
+		}
+	}
+	(inherited, total)
+}
+
+/// Report which of the common `[package]` fields and how many of the dependencies each
+/// member of the workspace at `root_manifest` inherits from the workspace.
+///
+/// Fields are shown as `inherited`, `local` or `-` if not given at all, dependencies as
+/// `<inherited>/<total>`. This only reads the manifests, no resolution is done.
+pub fn inheritance_report(root_manifest: &Path) -> Result<String, anyhow::Error> {
+	let read = |path: &Path| -> Result<Document, anyhow::Error> {
+		read_to_string(path)
+			.with_context(|| format!("Reading {} failed", path.display()))?
+			.parse::<Document>()
+			.with_context(|| format!("Parsing {} failed", path.display()))
+	};
+	let root = read(root_manifest)?;
+
+	let mut rows = Vec::new();
+	for manifest in member_manifests(root_manifest, &root)? {
+		let doc = read(&manifest)?;
+		let package = doc
+			.get("package")
+			.ok_or_else(|| anyhow::anyhow!("{} has no [package]", manifest.display()))?;
+		let name = package
+			.get("name")
+			.and_then(|n| n.as_str())
+			.ok_or_else(|| anyhow::anyhow!("{} has no package name", manifest.display()))?;
+		let [version, edition, license, authors] = FIELDS.map(|f| match package.get(f) {
+			Some(item) if is_inherited(item) => "inherited".to_owned(),
+			Some(_) => "local".to_owned(),
+			None => "-".to_owned(),
+		});
+		let (inherited, total) = count_deps(doc.as_item());
+
+		rows.push([
+			name.to_owned(),
+			version,
+			edition,
+			license,
+			authors,
+			format!("{}/{}", inherited, total),
+		]);
+	}
+	rows.sort();
+
+	Ok(render_table(["crate", "version", "edition", "license", "authors", "dependencies"], &rows))
+}
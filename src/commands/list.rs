@@ -0,0 +1,57 @@
+use crate::{options::OutputFormat, util::render_table};
+use cargo::{
+	core::{package::Package, Workspace},
+	sources::CRATES_IO_REGISTRY,
+};
+use serde::Serialize;
+
+/// A selected package, as emitted with `OutputFormat::Json`
+#[derive(Serialize)]
+struct Listed {
+	name: String,
+	version: String,
+	path: String,
+	publishable: bool,
+}
+
+/// List the members of `ws` matching `predicate` with their version, folder (relative to the
+/// workspace root) and whether they may be published to crates.io, sorted by name
+pub fn list<P>(
+	ws: &Workspace<'_>,
+	predicate: P,
+	output: OutputFormat,
+) -> Result<String, anyhow::Error>
+where
+	P: Fn(&Package) -> bool,
+{
+	let mut listed = ws
+		.members()
+		.filter(|p| predicate(p))
+		.map(|p| Listed {
+			name: p.name().to_string(),
+			version: p.version().to_string(),
+			path: p
+				.root()
+				.strip_prefix(ws.root())
+				.unwrap_or_else(|_| p.root())
+				.display()
+				.to_string(),
+			publishable: match p.publish() {
+				None => true,
+				Some(registries) => registries.iter().any(|r| r == CRATES_IO_REGISTRY),
+			},
+		})
+		.collect::<Vec<_>>();
+	listed.sort_by(|a, b| a.name.cmp(&b.name));
+
+	Ok(match output {
+		OutputFormat::Json => serde_json::to_string(&listed)?,
+		OutputFormat::Human => render_table(
+			["name", "version", "path", "publishable"],
+			&listed
+				.into_iter()
+				.map(|l| [l.name, l.version, l.path, l.publishable.to_string()])
+				.collect::<Vec<_>>(),
+		),
+	})
+}
@@ -1,25 +1,41 @@
 mod add_owner;
+mod changelog;
 mod check;
 mod clean_deps;
+mod commit;
 mod de_dev_deps;
+mod fix_metadata;
+mod inheritance;
+mod list;
+mod normalize_versions;
 mod release;
 mod rename;
 mod set_field;
 mod to_release;
+mod tree;
 mod version;
+mod version_policy;
 
-pub use add_owner::add_owner;
-pub use check::check;
+pub use add_owner::{add_owner, apply_owners, list_owners, remove_owner, validate_owner};
+pub use changelog::changelog;
+pub use check::{audit_dependency_sources, check, validate};
 pub use clean_deps::clean_up_unused_dependencies;
-pub use de_dev_deps::deactivate_dev_dependencies;
-pub use release::release;
+pub use commit::{commit_changes, snapshot, Snapshot};
+pub use de_dev_deps::{deactivate_dev_dependencies, restore_dev_dependencies};
+pub use fix_metadata::fix_metadata;
+pub use inheritance::inheritance_report;
+pub use list::list;
+pub use normalize_versions::normalize_versions;
+pub use release::{filter_excluded_registries, release, skip_published, target_registry};
 pub use rename::rename;
 pub use set_field::set_field;
-pub use to_release::packages_to_release;
-pub use version::set_version;
+pub use to_release::{packages_to_release, read_plan, write_plan};
+pub use tree::dependency_tree;
+pub use version::{check_lockstep, set_version, sync_dependencies};
+pub use version_policy::check_version_policy;
 
 #[cfg(feature = "gen-readme")]
 mod readme;
 
 #[cfg(feature = "gen-readme")]
-pub use readme::gen_all_readme;
+pub use readme::{check_all_readme, gen_all_readme};
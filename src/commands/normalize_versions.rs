@@ -0,0 +1,201 @@
+use crate::{
+	options::VersionReqStyle,
+	util::{
+		edit_each, edit_each_dep, members_deep, with_target_tables, DependencyAction,
+		DependencyEntry, PackageProgress,
+	},
+};
+use cargo::core::{package::Package, Workspace};
+use semver::{Comparator, Op, VersionReq};
+use toml_edit::{Item, Table, Value};
+
+/// Render the version of the comparator in the requested style, keeping its precision
+fn render(c: &Comparator, style: &VersionReqStyle) -> String {
+	let mut v = format!("{}", c.major);
+	if let Some(minor) = c.minor {
+		v += &format!(".{}", minor);
+	}
+	if let Some(patch) = c.patch {
+		v += &format!(".{}", patch);
+	}
+	if !c.pre.is_empty() {
+		v += &format!("-{}", c.pre);
+	}
+	match style {
+		VersionReqStyle::Bare => v,
+		VersionReqStyle::Caret => format!("^{}", v),
+	}
+}
+
+/// Whether `>= lower, < upper` is exactly what a caret requirement on `lower` means
+fn is_caret_range(lower: &Comparator, upper: &Comparator) -> bool {
+	if lower.op != Op::GreaterEq || upper.op != Op::Less {
+		return false
+	}
+	if !lower.pre.is_empty() || !upper.pre.is_empty() {
+		return false
+	}
+	let expected = if lower.major > 0 || lower.minor.is_none() {
+		(lower.major + 1, 0, 0)
+	} else if lower.minor != Some(0) || lower.patch.is_none() {
+		(0, lower.minor.unwrap_or(0) + 1, 0)
+	} else {
+		(0, 0, lower.patch.unwrap_or(0) + 1)
+	};
+	expected == (upper.major, upper.minor.unwrap_or(0), upper.patch.unwrap_or(0))
+}
+
+/// Find the canonical form of `req` in the given style. Returns `None` if it is canonical
+/// already or has no caret equivalent (e.g. `~1.2`, `=1.0.0` or `*`).
+fn normalize_req(req: &str, style: &VersionReqStyle) -> Option<String> {
+	let parsed = VersionReq::parse(req).ok()?;
+	let normalized = match parsed.comparators.as_slice() {
+		[c] if c.op == Op::Caret => render(c, style),
+		[lower, upper] if is_caret_range(lower, upper) => render(lower, style),
+		[upper, lower] if is_caret_range(lower, upper) => render(lower, style),
+		_ => return None,
+	};
+	if normalized == req.trim() {
+		None
+	} else {
+		Some(normalized)
+	}
+}
+
+/// Replace the string value in place, keeping its surrounding formatting
+fn replace_value(value: &mut Value, new: String) {
+	let decor = value.decor().clone();
+	*value = Value::from(new);
+	*value.decor_mut() = decor;
+}
+
+/// Normalize the version requirements in the dependency sections of `root`, reporting each
+/// through `report`. Return the number of requirements (to be) changed.
+fn normalize_deps<R>(root: &mut Table, style: &VersionReqStyle, check_only: bool, report: R) -> u32
+where
+	R: Fn(&str, &str, &str),
+{
+	let mut counter = 0;
+
+	// `name = "1.0"` isn't a table, so it isn't handed to us by `edit_each_dep`
+	for section in ["dependencies", "dev-dependencies", "build-dependencies"] {
+		if let Some(Item::Table(deps)) = root.get_mut(section) {
+			for (name, item) in deps.iter_mut() {
+				if let Item::Value(value) = item {
+					if let Some(new) = value.as_str().and_then(|r| normalize_req(r, style)) {
+						report(&name, value.as_str().expect("Checked before. qed"), &new);
+						if !check_only {
+							replace_value(value, new);
+						}
+						counter += 1;
+					}
+				}
+			}
+		}
+	}
+
+	counter += edit_each_dep(root, |name, _, entry, _| {
+		let version = match entry {
+			// internal dependencies are managed through the version commands
+			DependencyEntry::Inline(info) if !info.contains_key("path") => info.get_mut("version"),
+			DependencyEntry::Table(info) if !info.contains_key("path") =>
+				info.get_mut("version").and_then(|i| i.as_value_mut()),
+			_ => None,
+		};
+		match version {
+			Some(value) => match value.as_str().and_then(|r| normalize_req(r, style)) {
+				Some(new) => {
+					report(&name, value.as_str().expect("Checked before. qed"), &new);
+					if check_only {
+						// pretend we did, so it is counted
+						return DependencyAction::Mutated
+					}
+					replace_value(value, new);
+					DependencyAction::Mutated
+				},
+				None => DependencyAction::Untouched,
+			},
+			None => DependencyAction::Untouched,
+		}
+	});
+
+	counter
+}
+
+/// Rewrite the requirements of all external dependencies of the packages matching the
+/// predicate into the canonical form of the given style.
+///
+/// Follows `util::set_dependency_depth`, `util::set_quiet`, `dry_run::enable` and `metrics::enable`
+/// as set on the calling thread.
+pub fn normalize_versions<P>(
+	ws: &Workspace<'_>,
+	predicate: P,
+	style: VersionReqStyle,
+	check_only: bool,
+) -> Result<(), anyhow::Error>
+where
+	P: Fn(&Package) -> bool,
+{
+	let c = ws.config();
+
+	let packages = members_deep(ws)?.into_iter().filter(|p| predicate(p)).collect::<Vec<_>>();
+	let progress = PackageProgress::new(c, "Checking", packages.len());
+	let total = edit_each(packages.iter(), |p, doc| {
+		progress.tick(p)?;
+		let root = doc.as_table_mut();
+		let report = |name: &str, old: &str, new: &str| {
+			c.shell()
+				.status(
+					if check_only { "Not normalized" } else { "Normalized" },
+					format!("{}: {} -> {}", name, old, new),
+				)
+				.expect("Writing to Shell works");
+		};
+		Ok(with_target_tables(root, |table| normalize_deps(table, &style, check_only, report)))
+	})?
+	.iter()
+	.sum::<u32>();
+
+	if total > 0 && check_only {
+		anyhow::bail!(
+			"Aborting: {:} requirements not normalized. See shell output for more.",
+			total
+		)
+	}
+	c.shell().status("Done", format!("{} requirements normalized", total))?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn normalize_to_bare() {
+		let style = VersionReqStyle::Bare;
+		assert_eq!(normalize_req("^1.0", &style), Some("1.0".to_owned()));
+		assert_eq!(normalize_req("^1.0.0", &style), Some("1.0.0".to_owned()));
+		assert_eq!(normalize_req(">=1, <2", &style), Some("1".to_owned()));
+		assert_eq!(normalize_req(">=0.3.1, <0.4", &style), Some("0.3.1".to_owned()));
+		assert_eq!(normalize_req("1.0", &style), None);
+	}
+
+	#[test]
+	fn normalize_to_caret() {
+		let style = VersionReqStyle::Caret;
+		assert_eq!(normalize_req("1", &style), Some("^1".to_owned()));
+		assert_eq!(normalize_req("0.0.3", &style), Some("^0.0.3".to_owned()));
+		assert_eq!(normalize_req("<0.0.4, >=0.0.3", &style), Some("^0.0.3".to_owned()));
+		assert_eq!(normalize_req("^2.1", &style), None);
+	}
+
+	#[test]
+	fn keep_non_caret_requirements() {
+		let style = VersionReqStyle::Bare;
+		assert_eq!(normalize_req("~1.2", &style), None);
+		assert_eq!(normalize_req("=1.0.0", &style), None);
+		assert_eq!(normalize_req("*", &style), None);
+		// not the same range as `^1.2`
+		assert_eq!(normalize_req(">=1.2, <3", &style), None);
+	}
+}
@@ -1,10 +1,15 @@
-use crate::{cli::GenerateReadmeMode, commands};
+use crate::{
+	commands, dry_run,
+	options::GenerateReadmeMode,
+	util::{members_deep, render_table},
+};
 use anyhow::{anyhow, bail, Context, Result};
-use cargo::core::{Manifest, Package, Workspace};
+use cargo::core::{Package, Workspace};
 use lazy_static::lazy_static;
 use regex::{Captures, Regex};
 use sha1::{Digest as _, Sha1};
 use std::{
+	collections::HashMap,
 	fmt::Display,
 	fs::{self, File},
 	path::{Path, PathBuf},
@@ -19,14 +24,48 @@ lazy_static! {
 		Regex::new(r#"\[(?P<text>[^\]]+)\]\((?P<url>[^ )]+)(?: "(?P<title>.+)")?\)"#).unwrap();
 }
 
+/// Where the docs of the crates are hosted, to point the relative links in Readme files to
+pub struct DocLinks {
+	/// Prefix of the crate name, or a template with `{crate}` and `{version}` for the folder
+	/// holding the docs of a crate
+	base: String,
+	/// The versions of the workspace members, for `{version}`
+	versions: HashMap<String, String>,
+}
+
+impl DocLinks {
+	/// Links to the docs of the members of `ws` at `base`, docs.rs if `None`
+	pub fn new(ws: &Workspace<'_>, base: Option<&str>) -> Result<Self> {
+		let versions = members_deep(ws)?
+			.iter()
+			.map(|p| (p.name().to_string(), p.version().to_string()))
+			.collect();
+		Ok(DocLinks { base: base.unwrap_or(DEFAULT_DOC_URI).to_owned(), versions })
+	}
+
+	/// The folder holding the docs of `krate`, with a trailing slash
+	fn crate_root(&self, krate: &str) -> String {
+		if !self.base.contains("{crate}") && !self.base.contains("{version}") {
+			return format!("{}{}/latest/", self.base, krate)
+		}
+		let root = self
+			.base
+			.replace("{crate}", krate)
+			.replace("{version}", self.versions.get(krate).map(|v| v.as_str()).unwrap_or("latest"));
+		if root.ends_with('/') {
+			root
+		} else {
+			format!("{}/", root)
+		}
+	}
+}
+
 #[derive(Debug)]
 pub enum CheckReadmeResult {
 	#[allow(dead_code)]
 	Skipped,
 	Missing,
-	#[allow(dead_code)]
 	UpdateNeeded,
-	#[allow(dead_code)]
 	UpToDate,
 }
 
@@ -45,45 +84,108 @@ impl Display for CheckReadmeResult {
 	}
 }
 
-pub fn check_pkg_readme<'a>(
+/// Whether the Readme of `pkg` is the one `gen-readme` would overwrite it with, using
+/// `template` if given
+pub fn readme_status<'a>(
 	ws: &Workspace<'a>,
-	pkg_path: &Path,
-	pkg_manifest: &Manifest,
-) -> Result<()> {
-	let c = ws.config();
-
-	let mut pkg_source = find_entrypoint(pkg_path)?;
+	pkg: &Package,
+	links: &DocLinks,
+	template: Option<&Path>,
+) -> Result<CheckReadmeResult> {
+	let pkg_path = pkg.root();
+	let mut pkg_source = find_entrypoint(pkg)?;
 	let readme_path = pkg_path.join("README.md");
 
-	c.shell().status("Checking", format!("Readme for {}", &pkg_manifest.name()))?;
+	let pkg_readme = match fs::read_to_string(readme_path) {
+		Ok(pkg_readme) => pkg_readme,
+		Err(_err) => return Ok(CheckReadmeResult::Missing),
+	};
+	let template_path = readme_template(ws.root(), pkg_path, template)?;
+
+	let new_readme = generate_readme(pkg_path, &mut pkg_source, template_path)?;
+	// compare to what would be written, links included
+	let doc_uri = pkg.manifest().metadata().documentation.as_deref();
+	let new_readme = rewrite_doc_links(&pkg.name(), &new_readme, doc_uri, links);
+	Ok(
+		if Sha1::digest(normalize_readme(&pkg_readme)) ==
+			Sha1::digest(normalize_readme(&new_readme))
+		{
+			CheckReadmeResult::UpToDate
+		} else {
+			CheckReadmeResult::UpdateNeeded
+		},
+	)
+}
 
-	let pkg_readme = fs::read_to_string(readme_path.clone());
-	match pkg_readme {
-		Ok(pkg_readme) => {
-			// Try to find readme template
-			let template_path = find_readme_template(&ws.root(), &pkg_path)?;
+/// `readme` with LF line endings and without trailing whitespace, so a checkout with CRLF or
+/// an editor adding the final newline doesn't make the Readme outdated
+fn normalize_readme(readme: &str) -> String {
+	readme
+		.lines()
+		.map(str::trim_end)
+		.collect::<Vec<_>>()
+		.join("\n")
+		.trim_end()
+		.to_owned()
+}
 
-			let new_readme = generate_readme(&pkg_path, &mut pkg_source, template_path)?;
-			if Sha1::digest(&pkg_readme) == Sha1::digest(&new_readme) {
-				Ok(())
-			} else {
-				bail!(CheckReadmeResult::UpdateNeeded)
-			}
-		},
-		Err(_err) => bail!(CheckReadmeResult::Missing),
+pub fn check_pkg_readme<'a>(ws: &Workspace<'a>, pkg: &Package, links: &DocLinks) -> Result<()> {
+	ws.config().shell().status("Checking", format!("Readme for {}", pkg.name()))?;
+	match readme_status(ws, pkg, links, None)? {
+		CheckReadmeResult::UpToDate => Ok(()),
+		status => bail!(status),
 	}
 }
 
+/// Report the Readme status of each of `packages`, fail if any isn't up-to-date. Doesn't
+/// change any files.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn check_all_readme<'a>(
+	packages: &[Package],
+	ws: &Workspace<'a>,
+	doc_base_url: Option<&str>,
+	template: Option<&Path>,
+) -> Result<()> {
+	let c = ws.config();
+	let links = DocLinks::new(ws, doc_base_url)?;
+	ensure_template_exists(template)?;
+	c.shell().status("Checking", "Readme files")?;
+	let mut rows = Vec::new();
+	let mut outdated = 0;
+	for pkg in packages {
+		let status = readme_status(ws, pkg, &links, template)
+			.with_context(|| format!("Failure checking Readme for {:}", pkg.name()))?;
+		if !matches!(status, CheckReadmeResult::UpToDate) {
+			outdated += 1;
+		}
+		rows.push([pkg.name().to_string(), status.to_string()]);
+	}
+	println!("{}", render_table(["crate", "readme"], &rows));
+	if outdated > 0 {
+		bail!("{} Readme files need to be generated", outdated)
+	}
+	Ok(())
+}
+
+/// Generate the Readme of each of `packages` from its doc comments, according to `readme_mode`.
+///
+/// Follows `util::set_dependency_depth`, `dry_run::enable` and `metrics::enable` as set on the
+/// calling thread.
 pub fn gen_all_readme<'a>(
 	packages: Vec<Package>,
 	ws: &Workspace<'a>,
 	readme_mode: GenerateReadmeMode,
+	doc_base_url: Option<&str>,
+	template: Option<&Path>,
 ) -> Result<()> {
 	let c = ws.config();
+	ensure_template_exists(template)?;
 	c.shell().status("Generating", "Readme files")?;
+	let links = DocLinks::new(ws, doc_base_url)?;
 	for pkg in packages.into_iter() {
 		let pkg_name = &pkg.name().clone();
-		gen_pkg_readme(ws, pkg, &readme_mode)
+		gen_pkg_readme(ws, pkg, &readme_mode, &links, template)
 			.context(format!("Failure generating Readme for {:}", pkg_name))?
 	}
 
@@ -94,6 +196,8 @@ pub fn gen_pkg_readme<'a>(
 	ws: &Workspace<'a>,
 	pkg: Package,
 	mode: &GenerateReadmeMode,
+	links: &DocLinks,
+	template: Option<&Path>,
 ) -> Result<()> {
 	let c = ws.config();
 	let root_path = ws.root();
@@ -104,10 +208,10 @@ pub fn gen_pkg_readme<'a>(
 	let pkg_name = pkg_manifest.name();
 	let doc_uri = pkg_manifest.metadata().documentation.as_ref();
 
-	let mut pkg_source = find_entrypoint(pkg_path)?;
+	let mut pkg_source = find_entrypoint(&pkg)?;
 	let readme_path = pkg_path.join("README.md");
 
-	let pkg_readme = fs::read_to_string(readme_path.clone());
+	let pkg_readme = dry_run::read(&readme_path);
 	match (mode, pkg_readme) {
 		(GenerateReadmeMode::IfMissing, Ok(_existing_readme)) => {
 			c.shell().status("Skipping", format!("{}: Readme already exists.", &pkg_name))?;
@@ -115,7 +219,7 @@ pub fn gen_pkg_readme<'a>(
 			Ok(())
 		},
 		(mode, existing_res) => {
-			let template_path = find_readme_template(&ws.root(), &pkg_path)?;
+			let template_path = readme_template(ws.root(), pkg_path, template)?;
 			c.shell().status(
 				"Generating",
 				format!(
@@ -132,8 +236,8 @@ pub fn gen_pkg_readme<'a>(
 				*new_readme = format!("{}\n{}", existing_res.unwrap(), new_readme);
 			}
 			let final_readme =
-				&mut rewrite_doc_links(&pkg_name, &new_readme, doc_uri.map(|x| x.as_str()));
-			let res = fs::write(readme_path, final_readme.as_bytes());
+				&mut rewrite_doc_links(&pkg_name, &new_readme, doc_uri.map(|x| x.as_str()), links);
+			let res = dry_run::write(&readme_path, final_readme.clone());
 			set_readme_field(pkg)?;
 			Ok(res?)
 		},
@@ -168,45 +272,72 @@ fn set_readme_field(pkg: Package) -> Result<(), anyhow::Error> {
 		"package".to_owned(),
 		"readme".to_owned(),
 		Value::from("README.md"),
+		false,
 	)
+	.map(|_| ())
 }
 
-/// Find the default entrypoint to read the doc comments from
+/// Find the entrypoint to read the doc comments from
 ///
-/// Try to read entrypoint in the following order:
-/// - src/lib.rs
-/// - src/main.rs
-fn find_entrypoint(current_dir: &Path) -> Result<File> {
-	let entrypoint = find_entrypoint_internal(current_dir)?;
-	let f = File::open(current_dir.join(entrypoint))?;
-	Ok(f)
+/// Uses the targets of the manifest, so custom `[lib]` and `[[bin]]` paths are respected:
+/// - the library target, if any
+/// - the only binary target, or the one at `src/main.rs` if there are several
+fn find_entrypoint(pkg: &Package) -> Result<File> {
+	let entrypoint = find_entrypoint_internal(pkg)?;
+	File::open(&entrypoint)
+		.with_context(|| format!("Could not read entrypoint at {}", entrypoint.display()))
 }
-// #[derive(Debug)]
-// struct ManifestLib {
-// 	pub path: PathBuf,
-// 	pub doc: bool,
-// }
 
-/// Find the default entrypoint to read the doc comments from
-///
-/// Try to read entrypoint in the following order:
-/// - src/lib.rs
-/// - src/main.rs
-fn find_entrypoint_internal(current_dir: &Path) -> Result<PathBuf> {
-	// try lib.rs
-	let lib_rs = current_dir.join("src/lib.rs");
-	if lib_rs.exists() {
-		return Ok(lib_rs)
+fn find_entrypoint_internal(pkg: &Package) -> Result<PathBuf> {
+	let targets = pkg.targets();
+	if let Some(path) = targets.iter().find(|t| t.is_lib()).and_then(|t| t.src_path().path()) {
+		return Ok(path.to_path_buf())
+	}
+
+	let bins = targets
+		.iter()
+		.filter(|t| t.is_bin())
+		.filter_map(|t| t.src_path().path())
+		.collect::<Vec<_>>();
+	match bins.as_slice() {
+		[] => bail!("No entrypoint found"),
+		[bin] => Ok(bin.to_path_buf()),
+		_ => {
+			let main_rs = pkg.root().join("src/main.rs");
+			if let Some(bin) = bins.iter().find(|b| **b == main_rs) {
+				return Ok(bin.to_path_buf())
+			}
+			bail!(
+				"Ambiguous entrypoint, no library and {} binaries: {}",
+				bins.len(),
+				bins.iter()
+					.map(|b| b.strip_prefix(pkg.root()).unwrap_or(b).display().to_string())
+					.collect::<Vec<_>>()
+					.join(", ")
+			)
+		},
 	}
+}
 
-	// try main.rs
-	let main_rs = current_dir.join("src/main.rs");
-	if main_rs.exists() {
-		return Ok(main_rs)
+/// Fail if the `--readme-template` given doesn't exist, before any Readme is touched
+fn ensure_template_exists(template: Option<&Path>) -> Result<()> {
+	match template {
+		Some(p) if !p.is_file() => bail!("Readme template not found at {}", p.display()),
+		_ => Ok(()),
 	}
+}
 
-	// if no entrypoint is found, return an error
-	bail!("No entrypoint found".to_owned())
+/// The template to generate the Readme of the package at `pkg_path` with: `template` if given,
+/// the closest `README.tpl` otherwise
+fn readme_template(
+	root_path: &Path,
+	pkg_path: &Path,
+	template: Option<&Path>,
+) -> Result<Option<PathBuf>> {
+	match template {
+		Some(p) => Ok(Some(p.to_path_buf())),
+		None => find_readme_template(root_path, pkg_path),
+	}
 }
 
 /// Find the template file to be used to generate README files.
@@ -225,49 +356,82 @@ fn find_readme_template<'a>(root_path: &'a Path, pkg_path: &'a Path) -> Result<O
 	Ok(if tpl_path.exists() { Some(tpl_path) } else { None })
 }
 
-fn rewrite_doc_links(pkg_name: &str, readme: &str, doc_uri: Option<&str>) -> String {
+fn rewrite_doc_links(
+	pkg_name: &str,
+	readme: &str,
+	doc_uri: Option<&str>,
+	links: &DocLinks,
+) -> String {
 	RELATIVE_LINKS_REGEX
-		.replace_all(&readme, |caps: &Captures| rewrite_matched_doc_link(caps, pkg_name, doc_uri))
+		.replace_all(&readme, |caps: &Captures| {
+			rewrite_matched_doc_link(caps, pkg_name, doc_uri, links)
+		})
 		.into()
 }
 
-fn rewrite_matched_doc_link(caps: &Captures, pkg_name: &str, doc_uri: Option<&str>) -> String {
+fn rewrite_matched_doc_link(
+	caps: &Captures,
+	pkg_name: &str,
+	doc_uri: Option<&str>,
+	links: &DocLinks,
+) -> String {
 	match caps.name("url") {
 		// Skip absolute links
 		Some(url) if url.as_str().starts_with("http") => caps[0].to_string(),
 		// Handle relative links to sibling crate
 		Some(url) if url.as_str().starts_with("../") =>
-			make_sibling_doc_link(caps.name("text").unwrap().as_str(), &url.as_str()[3..], doc_uri),
+			make_sibling_doc_link(caps.name("text").unwrap().as_str(), &url.as_str()[3..], links),
 		// Handle relative links to current crate
 		Some(url) => make_relative_doc_link(
 			caps.name("text").unwrap().as_str(),
 			if url.as_str().starts_with("./") { &url.as_str()[2..] } else { &url.as_str() },
 			pkg_name,
 			doc_uri,
+			links,
 		),
 		_ => caps[0].to_string(),
 	}
 }
 
-fn make_sibling_doc_link(title: &str, url: &str, doc_uri: Option<&str>) -> String {
-	let sibling_end = url.find('/').unwrap();
-	let sibling = &url[..sibling_end];
+fn make_sibling_doc_link(title: &str, url: &str, links: &DocLinks) -> String {
+	// keep the `#fragment` out of the path handling, it is appended again as is
+	let (path, fragment) = match url.find('#') {
+		Some(idx) => url.split_at(idx),
+		None => (url, ""),
+	};
+	let (sibling, path) = path.split_once('/').unwrap_or((path, ""));
+	// a module's page is linked as its folder
+	let path = match path.rsplit_once('/') {
+		Some((module, "index.html")) => &path[..module.len() + 1],
+		None if path == "index.html" => "",
+		_ => path,
+	};
 	format!(
-		"[{}]({}{}/latest/{}/{})",
+		"[{}]({}{}/{}{})",
 		title,
-		doc_uri.unwrap_or(DEFAULT_DOC_URI),
-		sibling.replace('_', "-"),
+		links.crate_root(&sibling.replace('_', "-")),
 		sibling,
-		&url[sibling_end + 1..].replace("index.html", "")
+		path,
+		fragment
 	)
 }
 
-fn make_relative_doc_link(title: &str, url: &str, pkg_name: &str, doc_uri: Option<&str>) -> String {
+/// Link to `url` within the docs of `pkg_name`, its `documentation` (`doc_uri`) takes
+/// precedence over `links`
+fn make_relative_doc_link(
+	title: &str,
+	url: &str,
+	pkg_name: &str,
+	doc_uri: Option<&str>,
+	links: &DocLinks,
+) -> String {
 	format!(
-		"[{}]({}{}/latest/{}/{})",
+		"[{}]({}{}/{})",
 		title,
-		doc_uri.unwrap_or(DEFAULT_DOC_URI),
-		if doc_uri.is_none() { pkg_name } else { "" },
+		match doc_uri {
+			Some(doc_uri) => format!("{}/latest/", doc_uri),
+			None => links.crate_root(pkg_name),
+		},
 		pkg_name.replace('-', "_"),
 		url
 	)
@@ -275,11 +439,29 @@ fn make_relative_doc_link(title: &str, url: &str, pkg_name: &str, doc_uri: Optio
 
 #[cfg(test)]
 mod tests {
-	use crate::commands::readme::{make_relative_doc_link, make_sibling_doc_link};
+	use crate::commands::readme::{
+		make_relative_doc_link, make_sibling_doc_link, normalize_readme, DocLinks, DEFAULT_DOC_URI,
+	};
+
+	fn links(base: &str) -> DocLinks {
+		DocLinks {
+			base: base.to_owned(),
+			versions: [("pallet-staking", "4.0.0"), ("pallet-balances", "4.1.0")]
+				.iter()
+				.map(|(n, v)| (n.to_string(), v.to_string()))
+				.collect(),
+		}
+	}
 
 	#[test]
 	fn test_make_relative_doc_link() {
-		let doc_uri = make_relative_doc_link("`Call`", "enum.Call.html", "pallet-staking", None);
+		let doc_uri = make_relative_doc_link(
+			"`Call`",
+			"enum.Call.html",
+			"pallet-staking",
+			None,
+			&links(DEFAULT_DOC_URI),
+		);
 		assert_eq!(
 			doc_uri,
 			"[`Call`](https://docs.rs/pallet-staking/latest/pallet_staking/enum.Call.html)"
@@ -294,6 +476,7 @@ mod tests {
 			"enum.Call.html",
 			"pallet-timestamp",
 			Some("https://docs.rs/pallet-timestamp"),
+			&links("https://docs.example.com/"),
 		);
 		assert_eq!(
 			doc_uri,
@@ -304,10 +487,89 @@ mod tests {
 
 	#[test]
 	fn test_make_sibling_doc_link() {
-		let doc_uri = make_sibling_doc_link("Balances", "pallet_balances/index.html", None);
+		let doc_uri = make_sibling_doc_link(
+			"Balances",
+			"pallet_balances/index.html",
+			&links(DEFAULT_DOC_URI),
+		);
 		assert_eq!(
 			doc_uri,
 			"[Balances](https://docs.rs/pallet-balances/latest/pallet_balances/)".to_owned()
 		)
 	}
+
+	#[test]
+	fn test_custom_doc_base_url() {
+		let links = links("https://docs.example.com/");
+		assert_eq!(
+			make_relative_doc_link("`Call`", "enum.Call.html", "pallet-staking", None, &links),
+			"[`Call`](https://docs.example.com/pallet-staking/latest/pallet_staking/enum.Call.html)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("Balances", "pallet_balances/index.html", &links),
+			"[Balances](https://docs.example.com/pallet-balances/latest/pallet_balances/)"
+		);
+	}
+
+	#[test]
+	fn test_doc_base_url_template() {
+		let links = links("https://docs.example.com/{crate}/{version}");
+		assert_eq!(
+			make_relative_doc_link("`Call`", "enum.Call.html", "pallet-staking", None, &links),
+			"[`Call`](https://docs.example.com/pallet-staking/4.0.0/pallet_staking/enum.Call.html)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("Balances", "pallet_balances/index.html", &links),
+			"[Balances](https://docs.example.com/pallet-balances/4.1.0/pallet_balances/)"
+		);
+		// not in the workspace
+		assert_eq!(
+			make_sibling_doc_link("Other", "other/index.html", &links),
+			"[Other](https://docs.example.com/other/latest/other/)"
+		);
+	}
+
+	#[test]
+	fn test_doc_links_keep_fragments() {
+		let links = links(DEFAULT_DOC_URI);
+		assert_eq!(
+			make_relative_doc_link(
+				"`bar`",
+				"struct.Foo.html#method.bar",
+				"pallet-staking",
+				None,
+				&links
+			),
+			"[`bar`](https://docs.rs/pallet-staking/latest/pallet_staking/struct.Foo.html#method.bar)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("`bar`", "pallet_balances/struct.Foo.html#method.bar", &links),
+			"[`bar`](https://docs.rs/pallet-balances/latest/pallet_balances/struct.Foo.html#method.bar)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("Setup", "pallet_balances/index.html#setup", &links),
+			"[Setup](https://docs.rs/pallet-balances/latest/pallet_balances/#setup)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("Setup", "pallet_balances#setup", &links),
+			"[Setup](https://docs.rs/pallet-balances/latest/pallet_balances/#setup)"
+		);
+		assert_eq!(
+			make_sibling_doc_link("Module", "pallet_balances/pallet/index.html#calls", &links),
+			"[Module](https://docs.rs/pallet-balances/latest/pallet_balances/pallet/#calls)"
+		);
+		// only an `index.html` page is a module's one
+		assert_eq!(
+			make_sibling_doc_link("Index", "pallet_balances/struct.index.html", &links),
+			"[Index](https://docs.rs/pallet-balances/latest/pallet_balances/struct.index.html)"
+		);
+	}
+
+	#[test]
+	fn test_normalize_readme() {
+		let readme = "# crate\n\nSome docs.\n";
+		assert_eq!(normalize_readme(readme), normalize_readme("# crate\n\nSome docs."));
+		assert_eq!(normalize_readme(readme), normalize_readme("# crate\r\n\r\nSome docs.  \r\n\n"));
+		assert_ne!(normalize_readme(readme), normalize_readme("# crate\nSome docs.\n"));
+	}
 }
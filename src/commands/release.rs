@@ -1,22 +1,231 @@
-use crate::commands::add_owner;
+use crate::{
+	commands::add_owner,
+	metrics,
+	util::{
+		config_like, dependency_levels, published_checksum, published_versions,
+		refresh_published_versions, resolve_token,
+	},
+};
+use anyhow::Context;
 use cargo::{
-	core::{package::Package, resolver::features::CliFeatures, Workspace},
-	ops::{self, publish, PublishOpts},
+	core::{package::Package, resolver::features::CliFeatures, SourceId, Workspace},
+	ops::{self, publish, read_package, PublishOpts},
+	sources::CRATES_IO_REGISTRY,
+	util::{config::Config, Filesystem},
 };
+use cargo_util::Sha256;
+use git2::{ObjectType, Repository};
 
-use std::{thread, time::Duration};
+use std::{
+	collections::HashMap,
+	path::Path,
+	sync::{Arc, Mutex},
+	thread,
+	time::{Duration, Instant},
+};
 
-pub fn release(
+/// Creates the annotated git tags for the crates published, pointing to `HEAD`
+struct Tagger {
+	repo: Repository,
+	template: String,
+}
+
+impl Tagger {
+	fn new(ws: &Workspace<'_>, template: String) -> Result<Self, anyhow::Error> {
+		let repo = Repository::discover(ws.root()).context("Workspace isn't within a git repo")?;
+		repo.signature().context("No git user configured to tag as")?;
+		Ok(Tagger { repo, template })
+	}
+
+	fn name(&self, pkg: &Package) -> String {
+		self.template
+			.replace("{name}", &pkg.name())
+			.replace("{version}", &pkg.version().to_string())
+	}
+
+	fn tag(&self, c: &Config, pkg: &Package, dry_run: bool) -> Result<(), anyhow::Error> {
+		let name = self.name(pkg);
+		if dry_run {
+			c.shell().status("Tagging", format!("{} (dry run, not created)", name))?;
+			return Ok(())
+		}
+		let head = self.repo.head()?.peel(ObjectType::Commit)?;
+		if let Ok(existing) = self.repo.revparse_single(&format!("refs/tags/{}", name)) {
+			if existing.peel(ObjectType::Commit)?.id() == head.id() {
+				c.shell().status("Skipping", format!("tag {} (already exists)", name))?;
+				return Ok(())
+			}
+			anyhow::bail!("Tag {} already exists for another commit", name)
+		}
+		c.shell().status("Tagging", &name)?;
+		self.repo
+			.tag(
+				&name,
+				&head,
+				&self.repo.signature()?,
+				&format!("{} {}", pkg.name(), pkg.version()),
+				false,
+			)
+			.with_context(|| format!("Creating tag {} failed", name))?;
+		Ok(())
+	}
+}
+
+/// The registry to publish `pkg` to: `registry` if given, otherwise the first one its `publish`
+/// list names, unless that allows crates.io. `None` means crates.io.
+pub fn target_registry(pkg: &Package, registry: Option<&str>) -> Option<String> {
+	if let Some(registry) = registry {
+		return Some(registry.to_owned())
+	}
+	match pkg.publish() {
+		Some(registries) if !registries.iter().any(|r| r == CRATES_IO_REGISTRY) =>
+			registries.first().cloned(),
+		_ => None,
+	}
+}
+
+/// Drop the packages not eligible for publishing to `registry` (crates.io if `None`) once
+/// the `excluded` registries are removed from the ones their manifest allows.
+pub fn filter_excluded_registries(
 	packages: Vec<Package>,
-	ws: Workspace<'_>,
-	dry_run: bool,
-	token: Option<String>,
-	owner: Option<String>,
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+	excluded: &[String],
+) -> Result<Vec<Package>, anyhow::Error> {
+	let mut eligible = Vec::new();
+	for pkg in packages {
+		let target = target_registry(&pkg, registry);
+		let target = target.as_deref().unwrap_or(CRATES_IO_REGISTRY);
+		let allowed = match pkg.publish() {
+			Some(registries) => registries.iter().any(|r| r == target),
+			None => true,
+		};
+		if allowed && !excluded.iter().any(|r| r == target) {
+			eligible.push(pkg);
+		} else {
+			ws.config()
+				.shell()
+				.status("Skipping", format!("{} (no eligible registry left)", pkg.name()))?;
+		}
+	}
+	Ok(eligible)
+}
+
+/// Drop the packages whose exact version is already published on their target registry (see
+/// `target_registry`), so an interrupted release can just be run again.
+///
+/// Follows `util::disable_registry_cache` as set on the calling thread.
+pub fn skip_published(
+	packages: Vec<Package>,
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+) -> Result<Vec<Package>, anyhow::Error> {
+	ws.config().shell().status("Syncing", "Versions from registry")?;
+	let mut by_registry = HashMap::<_, Vec<_>>::new();
+	for pkg in packages.iter() {
+		by_registry.entry(target_registry(pkg, registry)).or_default().push(pkg.name());
+	}
+	let mut published = HashMap::new();
+	for (target, names) in by_registry {
+		let versions = published_versions(ws, target.as_deref(), names)?;
+		published.extend(versions.into_iter().map(|(name, v)| ((target.clone(), name), v)));
+	}
+
+	let mut remaining = Vec::new();
+	for pkg in packages {
+		let key = (target_registry(&pkg, registry), pkg.name());
+		if published.get(&key).map(|v| v.contains(pkg.version())).unwrap_or(false) {
+			ws.config().shell().status(
+				"Skipping",
+				format!("{} {} (already published)", pkg.name(), pkg.version()),
+			)?;
+		} else {
+			remaining.push(pkg);
+		}
+	}
+	Ok(remaining)
+}
+
+/// Make sure `registry` (crates.io if `None`) lists the version of `pkg` just published, with the
+/// checksum of the `.crate` file packaged for the upload in `target_dir`.
+fn verify_published(
+	ws: &Workspace<'_>,
+	target_dir: &Filesystem,
+	registry: Option<&str>,
+	pkg: &Package,
+) -> Result<(), anyhow::Error> {
+	ws.config()
+		.shell()
+		.status("Verifying", format!("{} {} on the registry", pkg.name(), pkg.version()))?;
+	let listed = published_checksum(ws, registry, pkg)?.ok_or_else(|| {
+		anyhow::anyhow!(
+			"{} {} isn't listed by the registry after publishing it, not publishing its dependents",
+			pkg.name(),
+			pkg.version()
+		)
+	})?;
+	let file = target_dir
+		.join("package")
+		.join(format!("{}-{}.crate", pkg.name(), pkg.version()))
+		.into_path_unlocked();
+	let uploaded = Sha256::new().update_path(&file)?.finish_hex();
+	if listed != uploaded {
+		anyhow::bail!(
+			"The registry lists {} {} with checksum {}, but the file uploaded has {}, not \
+			publishing its dependents",
+			pkg.name(),
+			pkg.version(),
+			listed,
+			uploaded
+		)
+	}
+	Ok(())
+}
+
+/// Poll `registry` (crates.io if `None`) until `pkg` shows up in its index, backing off from one
+/// second up to half a minute between the attempts. Fails once `timeout` has passed.
+fn wait_for_index(
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+	pkg: &Package,
+	timeout: Duration,
 ) -> Result<(), anyhow::Error> {
 	let c = ws.config();
-	let opts = PublishOpts {
+	let start = Instant::now();
+	let mut backoff = Duration::from_secs(1);
+	c.shell().status(
+		"Waiting",
+		format!("for {} {} to show up in the index", pkg.name(), pkg.version()),
+	)?;
+	loop {
+		let published = refresh_published_versions(ws, registry, [pkg.name()])?;
+		if published.get(&pkg.name()).map(|v| v.contains(pkg.version())).unwrap_or(false) {
+			return Ok(())
+		}
+		let elapsed = start.elapsed();
+		if elapsed >= timeout {
+			anyhow::bail!(
+				"{} {} didn't show up in the index within {}s, its dependents can't be published yet. \
+				Pass a longer `--index-wait-timeout`, or `--no-wait` to not wait at all.",
+				pkg.name(),
+				pkg.version(),
+				timeout.as_secs()
+			)
+		}
+		thread::sleep(backoff.min(timeout - elapsed));
+		backoff = (backoff * 2).min(Duration::from_secs(30));
+	}
+}
+
+fn publish_opts(
+	c: &Config,
+	dry_run: bool,
+	token: Option<String>,
+	registry: Option<String>,
+) -> PublishOpts<'_> {
+	PublishOpts {
 		verify: false,
-		token: token.clone(),
+		token,
 		dry_run,
 		config: c,
 		allow_dirty: true,
@@ -24,41 +233,319 @@ pub fn release(
 		jobs: None,
 		to_publish: ops::Packages::Default,
 		targets: Default::default(),
-		registry: None,
+		registry,
 		cli_features: CliFeatures {
 			features: Default::default(),
 			all_features: false,
 			uses_default_features: true,
 		},
 		keep_going: false,
-	};
+	}
+}
 
-	let delay = {
-		if packages.len() > 29 {
-			// more than 30, delay so we do not publish more than 30 in 10min.
-			21
-		} else {
-			// below the limit we just burst them out.
-			0
-		}
-	};
+/// Spaces out the start of the uploads by `delay`, across all of them in the run however many
+/// run at once
+#[derive(Clone)]
+struct RateLimit {
+	delay: Duration,
+	next: Arc<Mutex<Option<Instant>>>,
+}
 
-	c.shell().status("Publishing", "Packages")?;
-	for (idx, pkg) in packages.iter().enumerate() {
-		if idx > 0 && delay > 0 {
+impl RateLimit {
+	fn new(delay: Duration) -> Self {
+		RateLimit { delay, next: Default::default() }
+	}
+
+	/// Wait for the next free slot to start an upload
+	fn wait(&self, c: &Config) -> Result<(), anyhow::Error> {
+		if self.delay.is_zero() {
+			return Ok(())
+		}
+		let start = {
+			let mut next = self.next.lock().expect("not poisoned, nothing panics holding it");
+			let now = Instant::now();
+			let start = next.map_or(now, |n| n.max(now));
+			*next = Some(start + self.delay);
+			start
+		};
+		let now = Instant::now();
+		if start > now {
 			c.shell().status(
 				"Waiting",
 				"published 30 crates – API limites require us to wait in between.",
 			)?;
-			thread::sleep(Duration::from_secs(delay));
+			thread::sleep(start - now);
 		}
+		Ok(())
+	}
+}
 
-		let pkg_ws = Workspace::ephemeral(pkg.clone(), c, Some(ws.target_dir()), true)?;
-		c.shell().status("Publishing", &pkg)?;
-		publish(&pkg_ws, &opts)?;
-		if let Some(ref o) = owner {
-			add_owner(c, pkg, o.clone(), token.clone())?;
+/// A crate published right away, or still being published on a thread of its own, taking the
+/// time of the upload itself
+enum Upload {
+	Done(Result<(), anyhow::Error>),
+	Running(thread::JoinHandle<(Result<(), anyhow::Error>, Duration)>),
+}
+
+/// Publish the crate at `manifest` with the `Config` made by `config`, once `rate_limit` allows
+fn publish_isolated(
+	config: impl FnOnce() -> Result<Config, anyhow::Error>,
+	rate_limit: &RateLimit,
+	manifest: &Path,
+	target_dir: Filesystem,
+	dry_run: bool,
+	token: Option<String>,
+	registry: Option<String>,
+) -> (Result<(), anyhow::Error>, Duration) {
+	let c = match config() {
+		Ok(c) => c,
+		Err(e) => return (Err(e), Duration::default()),
+	};
+	let pkg_ws = rate_limit.wait(&c).and_then(|()| {
+		let source = SourceId::for_path(manifest.parent().expect("manifests are in a folder"))?;
+		let (pkg, _) = read_package(manifest, source, &c)?;
+		Workspace::ephemeral(pkg, &c, Some(target_dir), true)
+	});
+	let start = Instant::now();
+	let res =
+		pkg_ws.and_then(|pkg_ws| publish(&pkg_ws, &publish_opts(&c, dry_run, token, registry)));
+	(res, start.elapsed())
+}
+
+/// Publish `packages` in the given order to `registry`, or the one their `publish` list names
+/// (see `target_registry`), and add `owner` to each. `token` is used for `registry`, the
+/// configured one for any other. Unless `index_wait` is `None`, each crate another one of
+/// `packages` depends on has to show up in the index within that time, before continuing.
+/// With a `tag_template`, each crate gets a git tag named after it once published.
+///
+/// The crates are published level by level of the dependency graph among them, up to
+/// `concurrency` of a level at the same time. If any crate of a level fails, the rest of the
+/// level is still published but none of the later ones.
+///
+/// With `verify`, each crate has to be listed by the registry, with the checksum of the file
+/// uploaded, before publishing anything else after its level. Not checked on a `dry_run`.
+///
+/// The crates are packaged in `target_dir`, relative to the current directory, instead of the
+/// `target` directory of `ws` if given.
+///
+/// Follows `util::disable_registry_cache` and `metrics::enable` as set on the calling thread.
+#[allow(clippy::too_many_arguments)]
+pub fn release(
+	packages: Vec<Package>,
+	ws: Workspace<'_>,
+	dry_run: bool,
+	token: Option<String>,
+	owner: Option<String>,
+	registry: Option<String>,
+	index_wait: Option<Duration>,
+	tag_template: Option<String>,
+	concurrency: usize,
+	verify: bool,
+	target_dir: Option<&Path>,
+) -> Result<(), anyhow::Error> {
+	let c = ws.config();
+	let target_dir = target_dir
+		.map(|p| Filesystem::new(c.cwd().join(p)))
+		.unwrap_or_else(|| ws.target_dir());
+	// fail before publishing anything if we couldn't tag
+	let tagger = match tag_template {
+		Some(template) => Some(Tagger::new(&ws, template)?),
+		None => None,
+	};
+
+	// more than 30, delay so we do not publish more than 30 in 10min. Below the limit we just
+	// burst them out.
+	let rate_limit = RateLimit::new(Duration::from_secs(if packages.len() > 29 { 21 } else { 0 }));
+
+	c.shell().status("Publishing", "Packages")?;
+	for level in dependency_levels(&packages) {
+		let level = level.into_iter().map(|idx| &packages[idx]).collect::<Vec<_>>();
+		let (mut published, mut failures) = (Vec::new(), Vec::new());
+		for batch in level.chunks(concurrency.max(1)) {
+			let mut uploads = Vec::new();
+			for pkg in batch {
+				let target = target_registry(pkg, registry.as_deref());
+				let token = if target == registry {
+					token.clone()
+				} else {
+					resolve_token(c, target.as_deref())?
+				};
+				match &target {
+					Some(target) if registry.is_none() =>
+						c.shell().status("Publishing", format!("{} to {}", pkg, target))?,
+					_ => c.shell().status("Publishing", pkg)?,
+				}
+
+				if batch.len() == 1 {
+					let res = rate_limit
+						.wait(c)
+						.and_then(|()| {
+							Workspace::ephemeral((*pkg).clone(), c, Some(target_dir.clone()), true)
+						})
+						.and_then(|pkg_ws| {
+							let opts = publish_opts(c, dry_run, token.clone(), target.clone());
+							metrics::time(
+								"publish",
+								Some(&pkg.name()),
+								|_| 1,
+								|| publish(&pkg_ws, &opts),
+							)
+						});
+					uploads.push((pkg, token, target, Upload::Done(res)));
+				} else {
+					let (config, manifest) = (config_like(c), pkg.manifest_path().to_path_buf());
+					let (target_dir, upload_token, upload_target) =
+						(target_dir.clone(), token.clone(), target.clone());
+					let rate_limit = rate_limit.clone();
+					let handle = thread::spawn(move || {
+						publish_isolated(
+							config,
+							&rate_limit,
+							&manifest,
+							target_dir,
+							dry_run,
+							upload_token,
+							upload_target,
+						)
+					});
+					uploads.push((pkg, token, target, Upload::Running(handle)));
+				}
+			}
+
+			for (pkg, token, target, upload) in uploads {
+				let res = match upload {
+					Upload::Done(res) => res,
+					Upload::Running(handle) => {
+						let (res, duration) = handle.join().unwrap_or_else(|_| {
+							(Err(anyhow::anyhow!("Publishing panicked")), Default::default())
+						});
+						metrics::record("publish", Some(&pkg.name()), 1, duration);
+						res
+					},
+				};
+				let res = res.and_then(|()| {
+					if let Some(ref o) = owner {
+						add_owner(c, pkg, vec![o.clone()], token, target.clone())?;
+					}
+					if let Some(tagger) = &tagger {
+						tagger.tag(c, pkg, dry_run)?;
+					}
+					Ok(())
+				});
+				match res {
+					Ok(()) => published.push((pkg, target)),
+					Err(e) => failures.push((pkg, e)),
+				}
+			}
+		}
+
+		if !failures.is_empty() {
+			anyhow::bail!(
+				"Publishing failed for {} crate(s), not continuing with the ones depending on them:\n{}",
+				failures.len(),
+				failures
+					.iter()
+					.map(|(pkg, e)| format!("  {} {}: {:#}", pkg.name(), pkg.version(), e))
+					.collect::<Vec<_>>()
+					.join("\n")
+			)
+		}
+
+		for (pkg, target) in published {
+			let needed = packages
+				.iter()
+				.any(|p| p.dependencies().iter().any(|d| d.package_name() == pkg.name()));
+			match index_wait {
+				Some(timeout) if (needed || verify) && !dry_run => metrics::time(
+					"index wait",
+					Some(&pkg.name()),
+					|_| 1,
+					|| wait_for_index(&ws, target.as_deref(), pkg, timeout),
+				)?,
+				_ => {},
+			}
+			if verify && !dry_run {
+				metrics::time(
+					"verify",
+					Some(&pkg.name()),
+					|_| 1,
+					|| verify_published(&ws, &target_dir, target.as_deref(), pkg),
+				)?;
+			}
 		}
 	}
 	Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use assert_fs::prelude::*;
+
+	#[test]
+	fn tags() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"]).unwrap();
+		let repo = Repository::init(temp.path()).unwrap();
+		repo.config().unwrap().set_str("user.name", "unleash").unwrap();
+		repo.config().unwrap().set_str("user.email", "unleash@example.com").unwrap();
+		{
+			let mut index = repo.index().unwrap();
+			index.add_all(["*"], git2::IndexAddOption::DEFAULT, None).unwrap();
+			let tree = repo.find_tree(index.write_tree().unwrap()).unwrap();
+			let sig = repo.signature().unwrap();
+			repo.commit(Some("HEAD"), &sig, &sig, "initial", &tree, &[]).unwrap();
+		}
+
+		let config = Config::default().unwrap();
+		let manifest = temp.path().join("crateA").join("Cargo.toml");
+		let source = SourceId::for_path(temp.path()).unwrap();
+		let (pkg, _) = read_package(&manifest, source, &config).unwrap();
+		let tagger = Tagger { repo, template: "release/{name}-v{version}".to_owned() };
+
+		tagger.tag(&config, &pkg, true).unwrap();
+		assert!(tagger.repo.revparse_single("release/crateA-v0.1.0").is_err());
+
+		tagger.tag(&config, &pkg, false).unwrap();
+		let tag = tagger.repo.revparse_single("release/crateA-v0.1.0").unwrap();
+		assert_eq!(tag.as_tag().unwrap().message(), Some("crateA 0.1.0"));
+		// running the release again is fine
+		tagger.tag(&config, &pkg, false).unwrap();
+	}
+
+	#[test]
+	fn excluded_registries() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"]).unwrap();
+		let publish_to = |name: &str, registries: &str| {
+			temp.child(name)
+				.child("Cargo.toml")
+				.write_str(&format!(
+					"[package]\nname = \"{}\"\nversion = \"0.1.0\"\npublish = [{}]\n",
+					name, registries
+				))
+				.unwrap();
+		};
+		// only allowed on the excluded one, vs. crates.io still being allowed
+		publish_to("crateB", r#""internal""#);
+		publish_to("crateC", r#""internal", "crates-io""#);
+
+		let config = Config::default().unwrap();
+		let ws = Workspace::new(&temp.path().join("Cargo.toml"), &config).unwrap();
+		let names = |registry: Option<&str>, excluded: &[&str]| {
+			let excluded = excluded.iter().map(|r| r.to_string()).collect::<Vec<_>>();
+			let packages = ws.members().cloned().collect();
+			filter_excluded_registries(packages, &ws, registry, &excluded)
+				.unwrap()
+				.iter()
+				.map(|p| p.name().to_string())
+				.collect::<Vec<_>>()
+		};
+
+		assert_eq!(names(None, &[]), ["crateA", "crateB", "crateC"]);
+		assert_eq!(names(None, &["internal"]), ["crateA", "crateC"]);
+		assert_eq!(names(None, &["crates-io"]), ["crateB"]);
+		// explicitly publishing elsewhere, only the unrestricted crateA may go there
+		assert_eq!(names(Some("other"), &["internal"]), ["crateA"]);
+	}
+}
@@ -1,13 +1,17 @@
-use crate::util::{edit_each, edit_each_dep, members_deep, DependencyAction, DependencyEntry};
+use crate::util::{
+	edit_each, edit_each_dep_all, members_deep, with_target_tables, DependencyAction,
+	DependencyEntry, DependencySection, PackageProgress,
+};
 use cargo::core::{package::Package, Workspace};
 use log::trace;
 use std::collections::HashMap;
-use toml_edit::{Item, Value};
+use toml_edit::{InlineTable, Item, Table, Value};
 
 fn check_for_update(
 	name: String,
 	wrap: DependencyEntry<'_>,
 	updates: &HashMap<String, String>,
+	include_non_path: bool,
 ) -> DependencyAction {
 	let new_name = if let Some(v) = updates.get(&name) {
 		v
@@ -17,17 +21,18 @@ fn check_for_update(
 
 	match wrap {
 		DependencyEntry::Inline(info) => {
-			if !info.contains_key("path") {
+			if !include_non_path && !info.contains_key("path") {
 				return DependencyAction::Untouched // entry isn't local
 			}
 
 			trace!("We renamed {:} to {:}", name, new_name);
-			info.get_or_insert(" package", Value::from(new_name.to_string()).decorated(" ", " "));
+			info.insert("package", Value::from(new_name.to_string()));
+			info.fmt();
 
 			DependencyAction::Mutated
 		},
 		DependencyEntry::Table(info) => {
-			if !info.contains_key("path") {
+			if !include_non_path && !info.contains_key("path") {
 				return DependencyAction::Untouched // entry isn't local
 			}
 
@@ -38,16 +43,51 @@ fn check_for_update(
 	}
 }
 
+/// Turn the plain requirements (`name = "1.0"`) on renamed crates in the dependency sections
+/// of `root` into `{ version = "1.0", package = "new-name" }`, as `edit_each_dep` skips them.
+fn update_plain_entries(root: &mut Table, updates: &HashMap<String, String>) -> u32 {
+	let mut counter = 0;
+	for case in [DependencySection::Regular, DependencySection::Dev, DependencySection::Build] {
+		if let Some(Item::Table(deps)) = root.get_mut(case.key()) {
+			for (name, item) in deps.iter_mut() {
+				let (new_name, value) = match (updates.get(name.get()), item) {
+					(Some(new_name), Item::Value(value)) if value.is_str() => (new_name, value),
+					_ => continue,
+				};
+				trace!("We renamed {:} to {:}", name, new_name);
+				let mut info = InlineTable::new();
+				info.insert("version", value.clone().decorated("", ""));
+				info.insert("package", Value::from(new_name.to_string()));
+				info.fmt();
+				let decor = value.decor().clone();
+				*value = Value::InlineTable(info);
+				*value.decor_mut() = decor;
+				counter += 1;
+			}
+		}
+	}
+	counter
+}
+
 /// For packages matching predicate set to mapper given version, if any. Update all members
-/// dependencies if necessary.
-pub fn rename<M, P>(ws: &Workspace<'_>, predicate: P, mapper: M) -> Result<(), anyhow::Error>
+/// dependencies if necessary. Only path dependencies are updated, unless `include_non_path`
+/// is set, in which case git and registry dependencies are updated, too.
+///
+/// Follows `util::set_dependency_depth`, `util::set_quiet`, `dry_run::enable` and `metrics::enable`
+/// as set on the calling thread.
+pub fn rename<M, P>(
+	ws: &Workspace<'_>,
+	predicate: P,
+	mapper: M,
+	include_non_path: bool,
+) -> Result<(), anyhow::Error>
 where
 	P: Fn(&Package) -> bool,
 	M: Fn(&Package) -> Option<String>,
 {
 	let c = ws.config();
 
-	let updates = edit_each(members_deep(ws).iter().filter(|p| predicate(p)), |p, doc| {
+	let updates = edit_each(members_deep(ws)?.iter().filter(|p| predicate(p)), |p, doc| {
 		Ok(mapper(p).map(|new_name| {
 			c.shell()
 				.status("Renaming", format!("{:} -> {:}", p.name(), new_name))
@@ -67,36 +107,31 @@ where
 	}
 
 	c.shell().status("Updating", "Dependency tree")?;
-	edit_each(members_deep(ws).iter(), |p, doc| {
-		c.shell().status("Updating", p.name())?;
+	let members = members_deep(ws)?;
+	let progress = PackageProgress::new(c, "Updating", members.len());
+	let total = edit_each(members.iter(), |p, doc| {
+		progress.tick(p)?;
 		let root = doc.as_table_mut();
-		let mut updates_count = 0;
-		updates_count += edit_each_dep(root, |a, _, b, _| check_for_update(a, b, &updates));
-
-		if let Some(Item::Table(table)) = root.get_mut("target") {
-			let keys = table
-				.iter()
-				.filter_map(|(k, v)| if v.is_table() { Some(k.to_owned()) } else { None })
-				.collect::<Vec<_>>();
-
-			for k in keys {
-				if let Some(Item::Table(root)) = table.get_mut(&k) {
-					updates_count +=
-						edit_each_dep(root, |a, _, b, _| check_for_update(a, b, &updates));
-				}
-			}
+		let mut updates_count = edit_each_dep_all(root, |a, _, b, _| {
+			check_for_update(a, b, &updates, include_non_path)
+		});
+		if include_non_path {
+			updates_count += with_target_tables(root, |t| update_plain_entries(t, &updates));
 		}
 
 		if updates_count == 0 {
-			c.shell().status("Done", "No dependency updates")?;
+			progress.detail("Done", "No dependency updates")?;
 		} else if updates_count == 1 {
-			c.shell().status("Done", "One dependency updated")?;
+			progress.detail("Done", "One dependency updated")?;
 		} else {
-			c.shell().status("Done", format!("{} dependencies updated", updates_count))?;
+			progress.detail("Done", format!("{} dependencies updated", updates_count))?;
 		}
 
-		Ok(())
-	})?;
+		Ok(updates_count)
+	})?
+	.iter()
+	.sum::<u32>();
+	c.shell().status("Done", format!("{} dependencies updated in total", total))?;
 
 	Ok(())
 }
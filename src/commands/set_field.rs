@@ -3,18 +3,29 @@ use cargo::core::package::Package;
 
 use toml_edit::{Item, Table, Value};
 
-/// Deactivate the Dev Dependencies Section of the given toml
+/// Set the field `key` within `root_key` of each manifest to `value`.
+///
+/// `key` may contain dots, in which case each segment but the last denotes a table to walk
+/// into. Missing tables are created, existing non-table entries are considered an error.
+/// With `only_if_missing`, manifests already having the field are left untouched. Returns
+/// the number of manifests written and skipped.
+///
+/// Follows `dry_run::enable` as set on the calling thread.
 pub fn set_field<'a, I>(
 	iter: I,
 	root_key: String,
 	key: String,
 	value: Value,
-) -> Result<(), anyhow::Error>
+	only_if_missing: bool,
+) -> Result<(usize, usize), anyhow::Error>
 where
 	I: Iterator<Item = &'a Package>,
 {
-	let _ = edit_each(iter, |p, doc| {
-		let table = {
+	let mut path = key.split('.').collect::<Vec<_>>();
+	let field = path.pop().expect("Split always yields at least one item. qed");
+
+	let written = edit_each(iter, |p, doc| {
+		let mut table = {
 			let t =
 				doc.as_table_mut().entry(&root_key).or_insert_with(|| Item::Table(Table::new()));
 			if let Item::Table(inner) = t {
@@ -27,8 +38,29 @@ where
 				);
 			}
 		};
-		let _ = table.insert(&key, Item::Value(value.clone().decorated(" ", "")));
-		Ok(())
-	});
-	Ok(())
+		for segment in path.iter() {
+			let t = table.entry(segment).or_insert_with(|| {
+				let mut t = Table::new();
+				t.set_implicit(true);
+				Item::Table(t)
+			});
+			table = if let Item::Table(inner) = t {
+				inner
+			} else {
+				anyhow::bail!(
+					"Error in manifest of {:}: {:} in {:} is not a table.",
+					p.name(),
+					segment,
+					key
+				);
+			};
+		}
+		if only_if_missing && table.contains_key(field) {
+			return Ok(false)
+		}
+		let _ = table.insert(field, Item::Value(value.clone().decorated(" ", "")));
+		Ok(true)
+	})?;
+	let count = written.iter().filter(|w| **w).count();
+	Ok((count, written.len() - count))
 }
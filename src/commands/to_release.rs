@@ -1,44 +1,141 @@
-use crate::util::members_deep;
+use crate::{
+	metrics,
+	options::StatsFormat,
+	util::{members_deep, published_versions, recreate_cycle},
+};
+use anyhow::Context;
 use cargo::{
-	core::{package::Package, Dependency, Source, SourceId, Workspace},
-	sources::registry::RegistrySource,
+	core::{dependency::DepKind, package::Package, Workspace},
+	sources::PathSource,
+	util::interning::InternedString,
 };
 use log::{trace, warn};
 use petgraph::{
 	dot::{self, Dot},
 	graph::{EdgeReference, NodeIndex},
-	visit::EdgeRef,
+	unionfind::UnionFind,
+	visit::{EdgeFiltered, EdgeRef},
 	Directed, Graph,
 };
+use serde::{Deserialize, Serialize};
 use std::{
 	collections::{HashMap, HashSet},
-	fs::OpenOptions,
+	fs::{self, OpenOptions},
 	io::Write,
-	path::PathBuf,
+	path::{Path, PathBuf},
 };
 
 /// Generate the packages we should be releasing
+///
+/// The dependency edges listed in `break_edges` as `(from, to)` are ignored for the order,
+/// but still show up (dashed) in the dot graph. With `isolate_cycles`, crates that are part of
+/// a cycle, or depend on one, are skipped instead of failing. With `stats`, summary statistics
+/// of the graph of the crates to release are printed in that format.
+///
+/// Follows `util::set_dependency_depth`, `util::disable_registry_cache` and `metrics::enable` as
+/// set on the calling thread.
 pub fn packages_to_release<F, D>(
 	ws: &Workspace<'_>,
 	predicate: F,
 	write_dot_graph: D,
+	break_edges: &[(String, String)],
+	isolate_cycles: bool,
+	stats: Option<StatsFormat>,
 ) -> Result<Vec<Package>, anyhow::Error>
 where
 	F: Fn(&Package) -> bool,
 	D: Into<Option<PathBuf>>,
 {
-	packages_to_release_inner::<F, D>(ws, predicate, write_dot_graph).map_err(
-		|ErrorWithCycles(cycles, e)| {
-			let named = cycles
-				.iter()
-				.map(|cycle| cycle.iter().map(|pkg| pkg.name().as_str()).collect::<Vec<_>>())
-				.collect::<Vec<_>>();
-			e.context(format!("Cycles: {:?}", named))
-		},
+	packages_to_release_inner::<F, D>(
+		ws,
+		predicate,
+		write_dot_graph,
+		break_edges,
+		isolate_cycles,
+		stats,
 	)
+	.map_err(|ErrorWithCycles(cycles, e)| {
+		let paths = cycles.iter().map(|cycle| render_cycle(cycle)).collect::<Vec<_>>();
+		e.context(format!("Cycles:\n  {}", paths.join("\n  ")))
+	})
 }
 
-type DependencyCycle = Vec<Package>;
+/// A crate to release, as stored in a plan
+#[derive(Serialize, Deserialize)]
+struct PlanEntry {
+	name: String,
+	version: String,
+}
+
+/// Write `packages` in their release order to `path`, as a JSON array of `{"name", "version"}`
+pub fn write_plan(path: &Path, packages: &[Package]) -> Result<(), anyhow::Error> {
+	let plan = packages
+		.iter()
+		.map(|p| PlanEntry { name: p.name().to_string(), version: p.version().to_string() })
+		.collect::<Vec<_>>();
+	fs::write(path, serde_json::to_string_pretty(&plan)?)
+		.with_context(|| format!("Writing plan to {} failed", path.display()))
+}
+
+/// Read the packages to release, in order, from the plan at `path` written by `write_plan`.
+/// Fails if any of them isn't in the workspace (anymore) at the planned version.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn read_plan(ws: &Workspace<'_>, path: &Path) -> Result<Vec<Package>, anyhow::Error> {
+	let content = fs::read_to_string(path)
+		.with_context(|| format!("Reading plan from {} failed", path.display()))?;
+	let plan: Vec<PlanEntry> = serde_json::from_str(&content)
+		.with_context(|| format!("Parsing plan {} failed", path.display()))?;
+	let members = members_deep(ws)?;
+
+	let mut packages = Vec::with_capacity(plan.len());
+	let mut problems = Vec::new();
+	for entry in plan {
+		match members.iter().find(|m| m.name() == entry.name.as_str()) {
+			Some(pkg) if pkg.version().to_string() == entry.version => packages.push(pkg.clone()),
+			Some(pkg) => problems.push(format!(
+				"{} is at {}, planned was {}",
+				entry.name,
+				pkg.version(),
+				entry.version
+			)),
+			None => problems.push(format!("{} isn't in the workspace", entry.name)),
+		}
+	}
+	if !problems.is_empty() {
+		anyhow::bail!("The plan doesn't match the workspace:\n  {}", problems.join("\n  "))
+	}
+	Ok(packages)
+}
+
+/// The crates along a cycle, each with the kind of its dependency on the next one
+type DependencyCycle = Vec<(Package, DepKind)>;
+
+/// Render as `a -(normal)-> b -(build)-> a`
+fn render_cycle(cycle: &[(Package, DepKind)]) -> String {
+	let mut path = cycle
+		.iter()
+		.map(|(pkg, kind)| format!("{} -({})-> ", pkg.name(), kind_name(*kind)))
+		.collect::<String>();
+	path.push_str(cycle.first().map(|(p, _)| p.name().as_str()).unwrap_or(""));
+	path
+}
+
+fn kind_name(kind: DepKind) -> &'static str {
+	match kind {
+		DepKind::Normal => "normal",
+		DepKind::Development => "dev",
+		DepKind::Build => "build",
+	}
+}
+
+/// A dependency of one crate on another in the release graph
+#[derive(Debug, Clone, Copy)]
+struct Edge {
+	kind: DepKind,
+	/// Ignored for the order, as requested by the user
+	broken: bool,
+}
 
 /// Error with additional cycle annotations.
 struct ErrorWithCycles(Vec<DependencyCycle>, anyhow::Error);
@@ -49,10 +146,42 @@ impl<T: Into<anyhow::Error>> From<T> for ErrorWithCycles {
 	}
 }
 
+/// Find the local crates the root manifest's `[patch]` and `[replace]` sections redirect
+/// dependencies to. Returns these packages and a map from the name they are depended upon by
+/// to their actual name.
+fn local_redirects(
+	ws: &Workspace<'_>,
+) -> Result<(Vec<Package>, HashMap<InternedString, InternedString>), anyhow::Error> {
+	let patches = ws.root_patch()?;
+	let redirects = patches
+		.values()
+		.flatten()
+		.map(|dep| (dep.package_name(), dep))
+		.chain(ws.root_replace().iter().map(|(spec, dep)| (spec.name(), dep)));
+
+	let mut packages = Vec::new();
+	let mut names = HashMap::new();
+	for (name, dep) in redirects {
+		let source = dep.source_id();
+		if !source.is_path() {
+			continue
+		}
+		let dst = source.url().to_file_path().expect("It was just checked before. qed");
+		let pkg = PathSource::new(&dst, source, ws.config()).root_package()?;
+		trace!("{} is redirected to local {}", name, pkg.name());
+		names.insert(name, pkg.name());
+		packages.push(pkg);
+	}
+	Ok((packages, names))
+}
+
 fn packages_to_release_inner<F, D>(
 	ws: &Workspace<'_>,
 	predicate: F,
 	write_dot_graph: D,
+	break_edges: &[(String, String)],
+	isolate_cycles: bool,
+	stats: Option<StatsFormat>,
 ) -> Result<Vec<Package>, ErrorWithCycles>
 where
 	F: Fn(&Package) -> bool,
@@ -64,93 +193,151 @@ where
 		.status("Resolving", "Dependency Tree")
 		.expect("Writing to Shell doesn't fail");
 
-	let mut graph = Graph::<Package, (), Directed, u32>::new();
-	let members = members_deep(ws);
+	let mut graph = Graph::<Package, Edge, Directed, u32>::new();
+	let mut members = members_deep(ws)?;
+
+	// patched crates must be released before their dependents, as any other local crate
+	let (redirected, redirects) = local_redirects(ws)?;
+	for pkg in redirected {
+		if !members.iter().any(|m| m.name() == pkg.name()) {
+			members.push(pkg);
+		}
+	}
 
 	let (members, to_ignore): (Vec<_>, Vec<_>) = members.iter().partition(|m| predicate(m));
 
 	let ignored = to_ignore.into_iter().map(|m| m.name()).collect::<HashSet<_>>();
 
-	ws.config()
-		.shell()
-		.status("Syncing", "Versions from crates.io")
-		.expect("Writing to Shell doesn't fail");
-
+	let published = if ws.config().offline() {
+		ws.config().shell().status(
+			"Skipping",
+			"Syncing versions from crates.io (offline), assuming nothing is published",
+		)?;
+		HashMap::new()
+	} else {
+		ws.config()
+			.shell()
+			.status("Syncing", "Versions from crates.io")
+			.expect("Writing to Shell doesn't fail");
+
+		metrics::time(
+			"registry sync",
+			None,
+			|_| members.len(),
+			|| published_versions(ws, None, members.iter().map(|m| m.name())),
+		)
+		.or_else(|e| {
+			ws.config()
+				.shell()
+				.warn(format!(
+					"Syncing with crates.io failed, assuming nothing is published: {:#}",
+					e
+				))
+				.map(|_| HashMap::new())
+		})?
+	};
+	// publishing the exact version again would fail, any other version is new to the registry.
+	// Yanked versions aren't in `published`: such a crate isn't available to depend on, so it
+	// isn't skipped. crates.io refuses the same version again though, it has to be unyanked or
+	// bumped.
 	let mut already_published = HashSet::new();
-	let mut registry = RegistrySource::remote(
-		SourceId::crates_io(ws.config()).expect(
-			"Your main registry (usually crates.io) can't be read. Please check your .cargo/config",
-		),
-		&Default::default(),
-		ws.config(),
-	)
-	.expect("Failed getting remote registry");
-	let lock = ws.config().acquire_package_cache_lock();
-
-	registry.invalidate_cache();
-
 	for m in members.iter() {
-		let dep = Dependency::parse(m.name(), Some(&m.version().to_string()), registry.source_id())
-			.expect("Parsing our dependency doesn't fail");
-
-		let _ = registry
-			.query(&dep, &mut |_| {
-				already_published.insert(m.name());
-			})
-			.map(|e| e.expect("Quering the local registry doesn't fail"));
+		if published.get(&m.name()).map(|v| v.contains(m.version())).unwrap_or(false) {
+			ws.config()
+				.shell()
+				.status("Skipping", format!("{} {} (already published)", m.name(), m.version()))?;
+			already_published.insert(m.name());
+		}
 	}
 
-	// drop the global package lock
-	drop(lock);
-
-	let map = members
-		.iter()
-		.filter_map(|&member| {
-			if ignored.contains(&member.name()) || already_published.contains(&member.name()) {
-				return None
-			}
-			Some((member.name(), graph.add_node(member.clone())))
-		})
-		.collect::<HashMap<_, _>>();
-
-	for member in members {
-		let current_index = match map.get(&member.name()) {
-			Some(i) => i,
-			_ => continue, // ignore entries we are not expected to publish
-		};
-
-		for dep in member.dependencies() {
-			if let Some(dep_index) = map.get(&dep.package_name()) {
-				graph.add_edge(*current_index, *dep_index, ());
-			} else if already_published.contains(&dep.package_name()) {
-				trace!("All good, it's on crates.io");
-			} else {
-				// we are looking at a dependency, we won't include in the set of
-				// ones we are about to publish. Let's make sure, this won't block
-				// us from doing so though.
-				trace!("Checking dependency for problems: {}", dep.package_name());
-				let source = dep.source_id();
-				if source.is_default_registry() {
-					trace!("All good, it's on crates.io")
-				} else if source.is_path() && dep.is_locked() {
-					// this is a pretty big indicator that something is going to fail later...
-					if ignored.contains(&dep.package_name()) {
-						warn!(
-							"{} lock depends on {}, which is expected to not be published. This might fail.",
-							member.name(),
-							dep.package_name()
-						)
+	metrics::time(
+		"graph build",
+		None,
+		|edges| *edges,
+		|| {
+			let map = members
+				.iter()
+				.filter_map(|&member| {
+					if ignored.contains(&member.name()) ||
+						already_published.contains(&member.name())
+					{
+						return None
+					}
+					Some((member.name(), graph.add_node(member.clone())))
+				})
+				.collect::<HashMap<_, _>>();
+
+			for member in members {
+				let current_index = match map.get(&member.name()) {
+					Some(i) => i,
+					_ => continue, // ignore entries we are not expected to publish
+				};
+
+				// this covers regular and build dependencies, which both have to be on the
+				// registry before the dependent can be published. Dev-dependencies are only
+				// present if they weren't stripped before (`--include-dev-deps`).
+				for dep in member.dependencies() {
+					let name =
+						redirects.get(&dep.package_name()).copied().unwrap_or(dep.package_name());
+					if let Some(dep_index) = map.get(&name) {
+						let broken = break_edges.iter().any(|(from, to)| {
+							member.name() == from.as_str() && name == to.as_str()
+						});
+						graph.add_edge(
+							*current_index,
+							*dep_index,
+							Edge { kind: dep.kind(), broken },
+						);
+					} else if already_published.contains(&dep.package_name()) {
+						trace!("All good, it's on crates.io");
+					} else {
+						// we are looking at a dependency, we won't include in the set of
+						// ones we are about to publish. Let's make sure, this won't block
+						// us from doing so though.
+						trace!("Checking dependency for problems: {}", dep.package_name());
+						let source = dep.source_id();
+						if source.is_default_registry() {
+							trace!("All good, it's on crates.io")
+						} else if source.is_path() && dep.is_locked() {
+							// this is a pretty big indicator that something is going to fail
+							// later...
+							if ignored.contains(&dep.package_name()) {
+								warn!(
+									"{} lock depends on {}, which is expected to not be published. This might fail.",
+									member.name(),
+									dep.package_name()
+								)
+							}
+						}
 					}
 				}
 			}
+			graph.edge_count()
+		},
+	);
+
+	for (from, to) in break_edges {
+		let found = graph.edge_references().any(|e| {
+			e.weight().broken &&
+				graph[e.source()].name() == from.as_str() &&
+				graph[e.target()].name() == to.as_str()
+		});
+		if !found {
+			warn!("No dependency of {} on {} to break found", from, to);
 		}
 	}
+	let unbroken = EdgeFiltered::from_fn(&graph, |e| !e.weight().broken);
 
 	// cannot use `toposort` for graphs that are cyclic in a undirected sense
 	// but are not in a directed way
 	let mut cycles = vec![];
 	let mut toposorted_indices = vec![];
-	let strongly_connected_sets = petgraph::algo::kosaraju_scc(&graph);
+	let strongly_connected_sets = metrics::time(
+		"toposort",
+		None,
+		|sets: &Vec<_>| sets.len(),
+		|| petgraph::algo::kosaraju_scc(&unbroken),
+	);
 	for strongly_connected in strongly_connected_sets {
 		match strongly_connected.len() {
 			0 => unreachable!("Strongly connected components are at least size 1. qed"),
@@ -165,22 +352,62 @@ where
 	}
 
 	if !cycles.is_empty() {
-		assert!(petgraph::algo::is_cyclic_directed(&graph));
-		let cycles = cycles
+		assert!(petgraph::algo::is_cyclic_directed(&unbroken));
+		let cycle_nodes = cycles;
+		let cycles = cycle_nodes
 			.iter()
 			.map(|nodes| {
-				nodes
-					.iter()
-					.map(|i| graph.node_weight(*i).unwrap())
-					.cloned()
+				recreate_cycle(&graph, nodes, |e| !e.broken)
+					.into_iter()
+					.map(|e| {
+						let (from, _) = graph.edge_endpoints(e).expect("Edge exists. qed");
+						(graph[from].clone(), graph[e].kind)
+					})
 					.collect::<Vec<_>>()
 			})
 			.collect::<Vec<_>>();
-		return Err(ErrorWithCycles(cycles, anyhow::anyhow!("Contains cycles")))
+		if !isolate_cycles {
+			return Err(ErrorWithCycles(cycles, anyhow::anyhow!("Contains cycles")))
+		}
+
+		let mut skipped = HashSet::new();
+		for (nodes, cycle) in cycle_nodes.iter().zip(cycles) {
+			ws.config().shell().warn(format!(
+				"Skipping {} as they form the dependency cycle {}",
+				nodes.iter().map(|i| graph[*i].name().as_str()).collect::<Vec<_>>().join(", "),
+				render_cycle(&cycle)
+			))?;
+			skipped.extend(nodes.iter().copied());
+		}
+		// in release order, so the dependencies of each crate have been decided on before
+		let mut retained = Vec::with_capacity(toposorted_indices.len());
+		for idx in toposorted_indices {
+			let blocker = graph
+				.edges(idx)
+				.find(|e| !e.weight().broken && skipped.contains(&e.target()))
+				.map(|e| e.target());
+			if let Some(blocker) = blocker {
+				ws.config().shell().status(
+					"Skipping",
+					format!("{}: depends on skipped {}", graph[idx].name(), graph[blocker].name()),
+				)?;
+				skipped.insert(idx);
+			} else {
+				retained.push(idx);
+			}
+		}
+		toposorted_indices = retained;
 	}
 
 	// the output of `kosaraju_scc` is in reverse topological order, leafs first, which matches
 
+	match stats {
+		Some(StatsFormat::Text) => print!("{}", graph_stats(&graph, &toposorted_indices)),
+		Some(StatsFormat::Json) =>
+			println!("{}", serde_json::to_string(&graph_stats(&graph, &toposorted_indices))?),
+		None => {},
+	}
+
 	let packages = toposorted_indices
 		.into_iter()
 		.map(|i| graph.node_weight(i).unwrap().clone())
@@ -189,34 +416,134 @@ where
 	Ok(packages)
 }
 
+/// Summary of the graph of crates to release
+#[derive(Serialize, Debug)]
+struct GraphStats {
+	crates: usize,
+	dependencies: usize,
+	/// The longest chain of crates depending on each other, dependencies first. Its length
+	/// bounds how many crates have to be published one after another.
+	critical_path: Vec<String>,
+	/// The crates with the most dependents, with their number of dependents
+	most_depended_upon: Vec<(String, usize)>,
+	/// Sets of crates independent of each other, that could be released in parallel
+	components: usize,
+}
+
+impl std::fmt::Display for GraphStats {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "Crates: {}", self.crates)?;
+		writeln!(f, "Dependencies: {}", self.dependencies)?;
+		writeln!(
+			f,
+			"Longest chain: {} ({})",
+			self.critical_path.len(),
+			self.critical_path.join(" -> ")
+		)?;
+		if self.most_depended_upon.is_empty() {
+			writeln!(f, "Most depended upon: none")?;
+		} else {
+			writeln!(
+				f,
+				"Most depended upon: {}",
+				self.most_depended_upon
+					.iter()
+					.map(|(name, count)| format!("{} ({})", name, count))
+					.collect::<Vec<_>>()
+					.join(", ")
+			)?;
+		}
+		writeln!(f, "Independent components: {}", self.components)
+	}
+}
+
+/// Compute the `GraphStats` of the crates at `indices` of `graph`, given in release order.
+/// Broken edges and those to crates not among `indices` don't count.
+fn graph_stats(graph: &Graph<Package, Edge, Directed, u32>, indices: &[NodeIndex]) -> GraphStats {
+	let included = indices.iter().copied().collect::<HashSet<_>>();
+	let edges = graph
+		.edge_references()
+		.filter(|e| {
+			!e.weight().broken && included.contains(&e.source()) && included.contains(&e.target())
+		})
+		.collect::<Vec<_>>();
+
+	// longest chain ending in each crate, as its length and the previous crate on it
+	let mut chains: HashMap<NodeIndex, (usize, Option<NodeIndex>)> = HashMap::new();
+	for idx in indices {
+		let longest = edges
+			.iter()
+			.filter(|e| e.source() == *idx)
+			.filter_map(|e| chains.get(&e.target()).map(|(len, _)| (*len, e.target())))
+			.max_by_key(|(len, _)| *len);
+		chains.insert(*idx, longest.map(|(len, dep)| (len + 1, Some(dep))).unwrap_or((1, None)));
+	}
+	let mut critical_path = Vec::new();
+	let mut current = indices.iter().max_by_key(|i| chains[i].0).copied();
+	while let Some(idx) = current {
+		critical_path.push(graph[idx].name().to_string());
+		current = chains[&idx].1;
+	}
+	critical_path.reverse();
+
+	let mut dependents: HashMap<NodeIndex, usize> = HashMap::new();
+	for e in edges.iter() {
+		*dependents.entry(e.target()).or_default() += 1;
+	}
+	let mut most_depended_upon = dependents
+		.into_iter()
+		.map(|(idx, count)| (graph[idx].name().to_string(), count))
+		.collect::<Vec<_>>();
+	most_depended_upon.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then(a.cmp(b)));
+	most_depended_upon.truncate(5);
+
+	let mut sets = UnionFind::new(graph.node_count());
+	for e in edges.iter() {
+		sets.union(e.source().index(), e.target().index());
+	}
+	let components = indices.iter().map(|i| sets.find(i.index())).collect::<HashSet<_>>().len();
+
+	GraphStats {
+		crates: indices.len(),
+		dependencies: edges.len(),
+		critical_path,
+		most_depended_upon,
+		components,
+	}
+}
+
 /// Render a graphviz (aka dot graph) to a file.
 fn graphviz<'i, I: IntoIterator<Item = &'i Vec<NodeIndex>>, W: Write>(
-	graph: &Graph<Package, (), Directed, u32>,
+	graph: &Graph<Package, Edge, Directed, u32>,
 	cycles: I,
 	dest: &mut W,
 ) -> anyhow::Result<()> {
 	let cycle_indices = cycles.into_iter().flat_map(|y| y.iter()).copied().collect::<HashSet<_>>();
 	let config = &[dot::Config::EdgeNoLabel, dot::Config::NodeNoLabel][..];
-	let get_edge_attributes =
-		|_graph: &Graph<Package, (), Directed, u32>, edge_ref: EdgeReference<'_, ()>| -> String {
-			let source = edge_ref.source();
-			let target = edge_ref.target();
-			if cycle_indices.contains(&target) && cycle_indices.contains(&source) {
-				r#"color=red"#
-			} else {
-				""
-			}
-			.to_owned()
-		};
-	let get_node_attributes =
-		|_graph: &Graph<Package, (), Directed, u32>, (idx, pkg): (NodeIndex, &Package)| -> String {
-			let label = format!(r#"label="{}:{}" "#, pkg.name(), pkg.version());
-			if cycle_indices.contains(&idx) {
-				label + "color=red"
-			} else {
-				label
-			}
-		};
+	let get_edge_attributes = |_graph: &Graph<Package, Edge, Directed, u32>,
+	                           edge_ref: EdgeReference<'_, Edge>|
+	 -> String {
+		let source = edge_ref.source();
+		let target = edge_ref.target();
+		if edge_ref.weight().broken {
+			r#"style=dashed"#
+		} else if cycle_indices.contains(&target) && cycle_indices.contains(&source) {
+			r#"color=red"#
+		} else {
+			""
+		}
+		.to_owned()
+	};
+	let get_node_attributes = |_graph: &Graph<Package, Edge, Directed, u32>,
+	                           (idx, pkg): (NodeIndex, &Package)|
+	 -> String {
+		let label = format!(r#"label="{}:{}" "#, pkg.name(), pkg.version());
+		if cycle_indices.contains(&idx) {
+			label + "color=red"
+		} else {
+			label
+		}
+	};
 
 	let dot = Dot::with_attr_getters(graph, config, &get_edge_attributes, &get_node_attributes);
 	dest.write_all(format!("{:?}", &dot).as_bytes())?;
@@ -391,7 +718,7 @@ publish = false
 					std::fs::create_dir_all(manifest_path.join("src")).unwrap();
 					std::fs::write(
 						manifest_path.join("Cargo.toml"),
-						toml::to_string(manifest.original()).unwrap().as_str().as_bytes(),
+						toml::to_string(manifest.original()).unwrap().as_bytes(),
 					)
 					.unwrap();
 					std::fs::write(
@@ -471,8 +798,9 @@ publish = false
 		wsb.add_crate("closing").version(1, 6, 9);
 
 		let ws = wsb.build(target_dir)?;
-		let to_release = packages_to_release(&ws, |_pkg| true, tmp.join("diamond.dot"))
-			.expect("There are no cycles in a diamond shaped, directed, dependency graph. qed");
+		let to_release =
+			packages_to_release(&ws, |_pkg| true, tmp.join("diamond.dot"), &[], false, None)
+				.expect("There are no cycles in a diamond shaped, directed, dependency graph. qed");
 		// must be in release order, so the leaf has to have a lower index, dependencies on the same
 		// level are ordered by there reverse appearance in the members declaration
 		assert_eq!(
@@ -494,14 +822,104 @@ publish = false
 
 		let ws = wsb.build(target_dir)?;
 		let ErrorWithCycles(cycles, _err) =
-			packages_to_release_inner(&ws, |_pkg| true, tmp.join("circular.dot")).unwrap_err();
+			packages_to_release_inner(&ws, |_pkg| true, tmp.join("circular.dot"), &[], false, None)
+				.unwrap_err();
 		assert_eq!(cycles.len(), 1);
 		assert_eq!(cycles[0].len(), 3);
 		// The start node is defined by the sequence in the members declaration
 		assert_eq!(
 			vec!["a", "b", "c"],
-			cycles[0].iter().map(|pkg| pkg.name().as_str()).collect::<Vec<_>>()
+			cycles[0].iter().map(|(pkg, _)| pkg.name().as_str()).collect::<Vec<_>>()
 		);
 		Ok(())
 	}
+
+	#[test]
+	fn circular_with_broken_edge() -> Result<()> {
+		let tmp = test_tmp_dir("circular-broken");
+		let target_dir = tmp.clone();
+
+		let mut wsb = WorkspaceBuilder::default();
+		wsb.add_crate("a").version(3, 0, 0).add_dependency("b", "*")?;
+		wsb.add_crate("b").version(2, 0, 0).add_dependency("c", "*")?;
+		wsb.add_crate("c").version(1, 0, 0).add_dependency("a", "*")?;
+
+		let ws = wsb.build(target_dir)?;
+		let broken = [("c".to_owned(), "a".to_owned())];
+		let to_release = packages_to_release(
+			&ws,
+			|_pkg| true,
+			tmp.join("circular-broken.dot"),
+			&broken,
+			false,
+			None,
+		)?;
+		assert_eq!(
+			vec!["c", "b", "a"],
+			to_release.iter().map(|pkg| pkg.name().as_str()).collect::<Vec<_>>()
+		);
+
+		// breaking an edge not part of the cycle doesn't help
+		let broken = [("a".to_owned(), "c".to_owned())];
+		assert!(packages_to_release(&ws, |_pkg| true, None, &broken, false, None).is_err());
+		Ok(())
+	}
+
+	#[test]
+	fn circular_isolated() -> Result<()> {
+		let tmp = test_tmp_dir("circular-isolated");
+		let target_dir = tmp.clone();
+
+		let mut wsb = WorkspaceBuilder::default();
+		wsb.add_crate("top").version(1, 0, 0).add_dependency("a", "*")?;
+		wsb.add_crate("a").version(3, 0, 0).add_dependency("b", "*")?;
+		wsb.add_crate("b").version(2, 0, 0).add_dependency("a", "*")?;
+		wsb.add_crate("other").version(1, 0, 0).add_dependency("leaf", "*")?;
+		wsb.add_crate("leaf").version(1, 0, 0);
+
+		let ws = wsb.build(target_dir)?;
+		let to_release = packages_to_release(&ws, |_pkg| true, None, &[], true, None)?;
+		assert_eq!(
+			vec!["leaf", "other"],
+			to_release.iter().map(|pkg| pkg.name().as_str()).collect::<Vec<_>>()
+		);
+		Ok(())
+	}
+
+	#[test]
+	fn stats() -> Result<()> {
+		let tmp = test_tmp_dir("stats");
+
+		let mut wsb = WorkspaceBuilder::default();
+		wsb.add_crate("top")
+			.version(1, 0, 0)
+			.add_dependency("dx", "*")?
+			.add_dependency("dy", "*")?;
+		wsb.add_crate("dx").version(1, 0, 0).add_dependency("closing", "*")?;
+		wsb.add_crate("dy").version(1, 0, 0).add_dependency("closing", "*")?;
+		wsb.add_crate("closing").version(1, 0, 0);
+		wsb.add_crate("alone").version(1, 0, 0);
+
+		let ws = wsb.build(tmp)?;
+		let mut graph = Graph::<Package, Edge, Directed, u32>::new();
+		let indices = ws
+			.members()
+			.map(|p| (p.name().as_str().to_owned(), graph.add_node(p.clone())))
+			.collect::<HashMap<_, _>>();
+		let edge = Edge { kind: DepKind::Normal, broken: false };
+		for (from, to) in [("top", "dx"), ("top", "dy"), ("dx", "closing"), ("dy", "closing")] {
+			graph.add_edge(indices[from], indices[to], edge);
+		}
+		let order = ["closing", "alone", "dy", "dx", "top"].map(|n| indices[n]);
+
+		let stats = graph_stats(&graph, &order);
+		assert_eq!(stats.crates, 5);
+		assert_eq!(stats.dependencies, 4);
+		assert_eq!(stats.critical_path.len(), 3);
+		assert_eq!(stats.critical_path[0], "closing");
+		assert_eq!(stats.critical_path[2], "top");
+		assert_eq!(stats.most_depended_upon[0], ("closing".to_owned(), 2));
+		assert_eq!(stats.components, 2);
+		Ok(())
+	}
 }
@@ -0,0 +1,135 @@
+use crate::util::members_deep;
+use cargo::core::{dependency::DepKind, package::Package, Dependency, Workspace};
+use petgraph::{Directed, Graph};
+use std::collections::{HashMap, HashSet};
+
+/// Render the release dependency tree of the crate `root` as ASCII art.
+///
+/// Only workspace-internal dependencies are shown unless `include_external` is set.
+/// Dev-dependencies are never shown, as they are not relevant for publishing. Crates
+/// taking part in a dependency cycle are marked with `(cycle)`, crates already expanded
+/// further up are marked with `(*)` and not expanded again.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn dependency_tree(
+	ws: &Workspace<'_>,
+	root: &str,
+	include_external: bool,
+	max_depth: Option<usize>,
+) -> Result<String, anyhow::Error> {
+	let members = members_deep(ws)?;
+	let by_name = members.iter().map(|p| (p.name().as_str(), p)).collect::<HashMap<_, _>>();
+
+	let root = by_name
+		.get(root)
+		.ok_or_else(|| anyhow::anyhow!("{} is not a member of the workspace", root))?;
+
+	let in_cycle = cycle_participants(&members, &by_name);
+
+	let mut tree = Tree {
+		by_name: &by_name,
+		in_cycle: &in_cycle,
+		include_external,
+		max_depth,
+		expanded: HashSet::new(),
+		out: vec![label(root, &in_cycle)],
+	};
+	tree.expanded.insert(root.name().as_str());
+	tree.render(root, "", 1);
+	Ok(tree.out.join("\n"))
+}
+
+fn relevant_deps(pkg: &Package) -> impl Iterator<Item = &Dependency> {
+	pkg.dependencies().iter().filter(|d| d.kind() != DepKind::Development)
+}
+
+/// Find all the crates that are part of a strongly connected component
+fn cycle_participants<'a>(
+	members: &'a [Package],
+	by_name: &HashMap<&'a str, &'a Package>,
+) -> HashSet<&'a str> {
+	let mut graph = Graph::<&str, (), Directed, u32>::new();
+	let indices = members
+		.iter()
+		.map(|p| (p.name().as_str(), graph.add_node(p.name().as_str())))
+		.collect::<HashMap<_, _>>();
+	for member in members {
+		for dep in relevant_deps(member) {
+			if by_name.contains_key(dep.package_name().as_str()) {
+				graph.add_edge(
+					indices[member.name().as_str()],
+					indices[dep.package_name().as_str()],
+					(),
+				);
+			}
+		}
+	}
+
+	petgraph::algo::kosaraju_scc(&graph)
+		.into_iter()
+		.filter(|scc| scc.len() > 1)
+		.flatten()
+		.map(|idx| graph[idx])
+		.collect()
+}
+
+fn label(pkg: &Package, in_cycle: &HashSet<&str>) -> String {
+	let mut l = format!("{} v{}", pkg.name(), pkg.version());
+	if in_cycle.contains(pkg.name().as_str()) {
+		l.push_str(" (cycle)");
+	}
+	l
+}
+
+struct Tree<'a, 'b> {
+	by_name: &'b HashMap<&'a str, &'a Package>,
+	in_cycle: &'b HashSet<&'a str>,
+	include_external: bool,
+	max_depth: Option<usize>,
+	expanded: HashSet<&'a str>,
+	out: Vec<String>,
+}
+
+impl<'a, 'b> Tree<'a, 'b> {
+	fn render(&mut self, pkg: &'a Package, prefix: &str, depth: usize) {
+		if self.max_depth.map(|m| depth > m).unwrap_or(false) {
+			return
+		}
+
+		let mut deps = relevant_deps(pkg)
+			.filter(|d| {
+				self.include_external || self.by_name.contains_key(d.package_name().as_str())
+			})
+			.collect::<Vec<_>>();
+		deps.sort_by_key(|d| d.package_name());
+		deps.dedup_by_key(|d| d.package_name());
+
+		let count = deps.len();
+		for (idx, dep) in deps.into_iter().enumerate() {
+			let last = idx + 1 == count;
+			let (branch, indent) =
+				if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+
+			match self.by_name.get(dep.package_name().as_str()).copied() {
+				Some(local) => {
+					let name = local.name().as_str();
+					let line = format!("{}{}{}", prefix, branch, label(local, self.in_cycle));
+					if self.expanded.contains(name) {
+						self.out.push(line + " (*)");
+						continue
+					}
+					self.out.push(line);
+					self.expanded.insert(name);
+					self.render(local, &format!("{}{}", prefix, indent), depth + 1);
+				},
+				None => self.out.push(format!(
+					"{}{}{} {} (external)",
+					prefix,
+					branch,
+					dep.package_name(),
+					dep.version_req()
+				)),
+			}
+		}
+	}
+}
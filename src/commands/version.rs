@@ -1,154 +1,497 @@
-use crate::util::{
-	edit_each, edit_each_dep, members_deep, DependencyAction, DependencyEntry, DependencySection,
+use crate::{
+	dry_run,
+	options::OutputFormat,
+	util::{
+		edit_each, edit_each_dep, edit_each_dep_all, members_deep, published_versions,
+		reload_workspace, render_table, DependencyAction, DependencyEntry, DependencySection,
+		PackageProgress,
+	},
 };
 use anyhow::Context;
-use cargo::core::{package::Package, Workspace};
+use cargo::{
+	core::{package::Package, Workspace},
+	util::interning::InternedString,
+};
 use log::trace;
 use semver::{Version, VersionReq};
-use std::collections::HashMap;
-use toml_edit::{Entry, Item, Value};
+use serde::Serialize;
+use std::{
+	cell::RefCell,
+	collections::{HashMap, HashSet},
+	fs,
+	path::Path,
+};
+use toml_edit::{Document, Item, Value};
+
+/// A package's version changed, as emitted with `OutputFormat::Json`
+#[derive(Serialize)]
+struct Bump {
+	#[serde(rename = "crate")]
+	krate: String,
+	from: String,
+	to: String,
+}
+
+/// The dependent reported for the requirements rewritten in the root's `[workspace.dependencies]`
+const WORKSPACE_DEPENDENCIES: &str = "[workspace.dependencies]";
 
+/// Whether the manifest `doc` inherits the version of its package (`version.workspace = true`)
+fn inherits_version(doc: &Document) -> bool {
+	doc.get("package")
+		.and_then(|p| p.get("version"))
+		.and_then(|v| v.get("workspace"))
+		.and_then(|w| w.as_bool())
+		.unwrap_or(false)
+}
+
+/// A version requirement on a dependency rewritten, `from` is `None` if there was none before
+struct Rewrite {
+	dependent: String,
+	dependency: String,
+	from: Option<String>,
+	to: String,
+}
+
+/// Returns the previous requirement (if any) and the new one, if the entry was rewritten
 fn check_for_update(
 	name: String,
 	wrap: DependencyEntry<'_>,
 	updates: &HashMap<String, Version>,
 	section: DependencySection,
 	force_update: bool,
-) -> DependencyAction {
-	let new_version = if let Some(v) = updates.get(&name) {
-		v
-	} else {
-		return DependencyAction::Untouched // we do not care about this entry
-	};
+) -> Option<(Option<String>, String)> {
+	// we do not care about entries not updated
+	let new_version = updates.get(&name)?;
 
 	match wrap {
 		DependencyEntry::Inline(info) => {
 			if !info.contains_key("path") {
-				return DependencyAction::Untouched // entry isn't local
+				return None // entry isn't local
 			}
 
 			trace!("We changed the version of {:} to {:}", name, new_version);
 			// this has been changed.
 			if let Some(v_req) = info.get_mut("version") {
-				let r = v_req
+				let current = v_req
 					.as_str()
 					.ok_or_else(|| anyhow::anyhow!("Version must be string"))
-					.and_then(|s| VersionReq::parse(s).context("Parsing failed"))
+					.expect("Cargo enforces us using semver versions. qed")
+					.to_owned();
+				let r = VersionReq::parse(&current)
+					.context("Parsing failed")
 					.expect("Cargo enforces us using semver versions. qed");
 				if force_update || !r.matches(new_version) {
 					trace!("Versions don't match anymore, updating.");
 					*v_req = Value::from(format!("{:}", new_version)).decorated(" ", "");
-					return DependencyAction::Mutated
+					return Some((Some(current), new_version.to_string()))
 				}
 			} else if section == DependencySection::Dev {
 				trace!("No version found on dev dependency, ignoring.");
-				return DependencyAction::Untouched
+				return None
 			} else {
 				// not yet present, we force set.
 				trace!("No version found, setting.");
 				// having a space here means we formatting it nicer inline
-				info.get_or_insert(
-					" version",
-					Value::from(format!("{:}", new_version)).decorated(" ", " "),
-				);
-				return DependencyAction::Mutated
+				info.insert("version", Value::from(format!("{:}", new_version)));
+				info.fmt();
+				return Some((None, new_version.to_string()))
 			}
 		},
 		DependencyEntry::Table(info) => {
 			if !info.contains_key("path") {
-				return DependencyAction::Untouched // entry isn't local
+				return None // entry isn't local
 			}
-			if let Some(new_version) = updates.get(&name) {
-				trace!("We changed the version of {:} to {:}", name, new_version);
-				// this has been changed.
-				if let Some(v_req) = info.get("version") {
-					let r = v_req
-						.as_str()
-						.ok_or_else(|| anyhow::anyhow!("Version must be string"))
-						.and_then(|s| VersionReq::parse(s).context("Parsing failed"))
-						.expect("Cargo enforces us using semver versions. qed");
-					if !force_update && r.matches(new_version) {
-						return DependencyAction::Untouched
-					}
-					trace!("Versions don't match anymore, updating.");
-				} else if section == DependencySection::Dev {
-					trace!("No version found on dev dependency {:}, ignoring.", name);
-					return DependencyAction::Untouched
-				} else {
-					trace!("No version found, setting.");
+			trace!("We changed the version of {:} to {:}", name, new_version);
+			// this has been changed.
+			let current = if let Some(v_req) = info.get("version") {
+				let current = v_req
+					.as_str()
+					.ok_or_else(|| anyhow::anyhow!("Version must be string"))
+					.expect("Cargo enforces us using semver versions. qed")
+					.to_owned();
+				let r = VersionReq::parse(&current)
+					.context("Parsing failed")
+					.expect("Cargo enforces us using semver versions. qed");
+				if !force_update && r.matches(new_version) {
+					return None
 				}
-				info["version"] =
-					Item::Value(Value::from(format!("{:}", new_version)).decorated(" ", ""));
-				return DependencyAction::Mutated
-			}
+				trace!("Versions don't match anymore, updating.");
+				Some(current)
+			} else if section == DependencySection::Dev {
+				trace!("No version found on dev dependency {:}, ignoring.", name);
+				return None
+			} else {
+				trace!("No version found, setting.");
+				None
+			};
+			info["version"] =
+				Item::Value(Value::from(format!("{:}", new_version)).decorated(" ", ""));
+			return Some((current, new_version.to_string()))
 		},
 	}
-	DependencyAction::Untouched
+	None
+}
+
+/// Write the `rewrites` to `path`, one line per requirement, grouped by dependent
+fn write_report(path: &Path, mut rewrites: Vec<Rewrite>) -> Result<(), anyhow::Error> {
+	rewrites.sort_by(|a, b| (&a.dependent, &a.dependency).cmp(&(&b.dependent, &b.dependency)));
+	let report = rewrites
+		.iter()
+		.map(|r| {
+			format!(
+				"{}: dep {} {} -> {}\n",
+				r.dependent,
+				r.dependency,
+				r.from.as_deref().unwrap_or("(none)"),
+				r.to
+			)
+		})
+		.collect::<String>();
+	fs::write(path, report).with_context(|| format!("Writing report to {} failed", path.display()))
 }
 
 /// For packages matching predicate set to mapper given version, if any. Update all members
-/// dependencies if necessary.
+/// dependencies if necessary. With `show_published`, the latest version on crates.io is
+/// reported along with each bump. With `report`, the dependency requirements rewritten are
+/// written to that file. With `OutputFormat::Json`, the bumps are printed as a JSON array of
+/// `{"crate", "from", "to"}` instead of reported on the shell.
+///
+/// Members inheriting their version (`version.workspace = true`) keep doing so, the version of
+/// `[workspace.package]` in the root manifest is bumped instead, and with it the one of any
+/// other member inheriting it.
+///
+/// Follows `util::set_dependency_depth`, `util::set_quiet`, `util::disable_registry_cache`,
+/// `dry_run::enable` and `metrics::enable` as set on the calling thread.
 pub fn set_version<M, P>(
 	ws: &Workspace<'_>,
 	predicate: P,
 	mapper: M,
 	force_update: bool,
+	show_published: bool,
+	report: Option<&Path>,
+	output: OutputFormat,
 ) -> Result<(), anyhow::Error>
 where
 	P: Fn(&Package) -> bool,
 	M: Fn(&Package) -> Option<Version>,
 {
 	let c = ws.config();
+	let members = members_deep(ws)?;
 
-	let updates = edit_each(members_deep(ws).iter().filter(|p| predicate(p)), |p, doc| {
-		Ok(mapper(p).map(|nv_version| {
-			c.shell()
-				.status("Bumping", format!("{:}: {:} -> {:}", p.name(), p.version(), nv_version))
-				.expect("Writing to the shell would have failed before. qed");
+	let published = if show_published {
+		c.shell().status("Syncing", "Versions from crates.io")?;
+		Some(published_versions(
+			ws,
+			None,
+			members.iter().filter(|p| predicate(p)).map(|p| p.name()),
+		)?)
+	} else {
+		None
+	};
+
+	// the version members inherit (`version.workspace = true`) is bumped in the root instead
+	let workspace_version = RefCell::new(None::<Version>);
+	let mut updates = edit_each(members.iter().filter(|p| predicate(p)), |p, doc| {
+		let nv_version = match mapper(p) {
+			Some(version) => version,
+			None => return Ok(None),
+		};
+		let message = match &published {
+			Some(published) => format!(
+				"{:}: local {:}, published {:}, bumping to {:}",
+				p.name(),
+				p.version(),
+				published
+					.get(&p.name())
+					.and_then(|v| v.iter().max())
+					.map(|v| v.to_string())
+					.unwrap_or_else(|| "none".to_owned()),
+				nv_version
+			),
+			None => format!("{:}: {:} -> {:}", p.name(), p.version(), nv_version),
+		};
+		if output == OutputFormat::Human {
+			c.shell().status("Bumping", message)?;
+		}
+		if inherits_version(doc) {
+			let mut inherited = workspace_version.borrow_mut();
+			match &*inherited {
+				Some(version) if version != &nv_version => anyhow::bail!(
+					"{} inherits the workspace version, which another member already bumps to {}",
+					p.name(),
+					version
+				),
+				_ => *inherited = Some(nv_version.clone()),
+			}
+		} else {
 			doc["package"]["version"] =
 				Item::Value(Value::from(nv_version.to_string()).decorated(" ", ""));
-			(p.name().as_str().to_owned(), nv_version)
-		}))
+		}
+		Ok(Some((p.name().as_str().to_owned(), (p.version().clone(), nv_version))))
 	})?
 	.into_iter()
 	.flatten()
-	.collect::<HashMap<_, _>>();
+	.collect::<Vec<_>>();
+
+	if let Some(version) = workspace_version.into_inner() {
+		let root_manifest = ws.root_manifest();
+		let mut doc: Document = dry_run::read(root_manifest)?.parse()?;
+		let package = doc
+			.get_mut("workspace")
+			.and_then(|w| w.get_mut("package"))
+			.and_then(|p| p.as_table_like_mut())
+			.context("Members inherit the version, but there is no [workspace.package] version")?;
+		let previous = package
+			.get("version")
+			.and_then(|v| v.as_str())
+			.context("Members inherit the version, but there is no [workspace.package] version")?
+			.to_owned();
+		package.insert("version", Item::Value(Value::from(version.to_string()).decorated(" ", "")));
+		dry_run::write(root_manifest, doc.to_string())?;
+		if output == OutputFormat::Human {
+			c.shell()
+				.status("Bumping", format!("[workspace.package]: {} -> {}", previous, version))?;
+		}
+
+		// the other members inheriting it are bumped along
+		for p in members.iter().filter(|p| !predicate(p)) {
+			let doc: Document = dry_run::read(p.manifest_path())?.parse()?;
+			if inherits_version(&doc) && p.version() != &version {
+				c.shell().warn(format!(
+					"{} wasn't selected, but inherits the workspace version and is bumped to {}",
+					p.name(),
+					version
+				))?;
+				updates
+					.push((p.name().as_str().to_owned(), (p.version().clone(), version.clone())));
+			}
+		}
+	}
+
+	if output == OutputFormat::Json {
+		let bumps = updates
+			.iter()
+			.map(|(name, (from, to))| Bump {
+				krate: name.clone(),
+				from: from.to_string(),
+				to: to.to_string(),
+			})
+			.collect::<Vec<_>>();
+		println!("{}", serde_json::to_string(&bumps)?);
+	}
+	let updates = updates.into_iter().map(|(name, (_, to))| (name, to)).collect::<HashMap<_, _>>();
 
 	c.shell().status("Updating", "Dependency tree")?;
-	edit_each(members_deep(ws).iter(), |p, doc| {
-		c.shell().status("Updating", p.name())?;
+	let rewrites = RefCell::new(Vec::new());
+	let update =
+		|dependent: &str, name: String, wrap: DependencyEntry<'_>, section| match check_for_update(
+			name.clone(),
+			wrap,
+			&updates,
+			section,
+			force_update,
+		) {
+			Some((from, to)) => {
+				rewrites.borrow_mut().push(Rewrite {
+					dependent: dependent.to_owned(),
+					dependency: name,
+					from,
+					to,
+				});
+				DependencyAction::Mutated
+			},
+			None => DependencyAction::Untouched,
+		};
+	let progress = PackageProgress::new(c, "Updating", members.len());
+	let mut total = edit_each(members.iter(), |p, doc| {
+		progress.tick(p)?;
 		let root = doc.as_table_mut();
-		let mut updates_count = 0;
-		updates_count += edit_each_dep(root, |name, _, wrap, section| {
-			check_for_update(name, wrap, &updates, section, force_update)
+		let updates_count = edit_each_dep_all(root, |name, _, wrap, section| {
+			update(&p.name(), name, wrap, section)
 		});
+		if updates_count == 0 {
+			progress.detail("Done", "No dependency updates")?;
+		} else if updates_count == 1 {
+			progress.detail("Done", "One dependency updated")?;
+		} else {
+			progress.detail("Done", format!("{} dependencies updated", updates_count))?;
+		}
+
+		Ok(updates_count)
+	})?
+	.iter()
+	.sum::<u32>();
+
+	// members inheriting a dependency (`workspace = true`) get the requirement from the root
+	let root_manifest = ws.root_manifest();
+	let mut doc: Document = dry_run::read(root_manifest)?.parse()?;
+	if let Some(Item::Table(workspace)) = doc.as_table_mut().get_mut("workspace") {
+		if workspace.contains_key("dependencies") {
+			c.shell().status("Updating", "Workspace dependencies")?;
+			// only `[workspace.dependencies]` has the key of a dependency section
+			let updates_count = edit_each_dep(workspace, |name, _, wrap, section| {
+				update(WORKSPACE_DEPENDENCIES, name, wrap, section)
+			});
+			// writing back what was read may still change its formatting
+			if updates_count > 0 {
+				dry_run::write(root_manifest, doc.to_string())?;
+				total += updates_count;
+			}
+		}
+	}
+	c.shell().status("Done", format!("{} dependencies updated in total", total))?;
+	if let Some(path) = report {
+		write_report(path, rewrites.into_inner())?;
+	}
+
+	if dry_run::enabled() {
+		c.shell()
+			.status("Skipping", "requirement check, nothing was written (dry run)")?;
+		return Ok(())
+	}
+	// only path dependencies are updated above, read the result back to check on the others
+	let ws = reload_workspace(ws.root_manifest(), c)?;
+	check_internal_requirements(&ws)
+}
 
-		if let Entry::Occupied(occupied) = root.entry("target") {
-			if let Item::Table(table) = occupied.get() {
-				let keys = table
-					.iter()
-					.filter_map(|(k, v)| if v.is_table() { Some(k.to_owned()) } else { None })
-					.collect::<Vec<_>>();
-
-				for k in keys {
-					if let Some(Item::Table(root)) = root.get_mut(&k) {
-						updates_count += edit_each_dep(root, |a, _, b, c| {
-							check_for_update(a, b, &updates, c, force_update)
-						});
-					}
+/// Ensure every dependency on a member of the workspace can be satisfied by that member's
+/// current version. Report the ones that can't as `(dependent, dependency, requirement,
+/// version)` and fail.
+fn check_internal_requirements(ws: &Workspace<'_>) -> Result<(), anyhow::Error> {
+	let members = members_deep(ws)?;
+	let versions = members.iter().map(|p| (p.name(), p.version())).collect::<HashMap<_, _>>();
+
+	let mut rows = Vec::new();
+	for pkg in members.iter() {
+		for dep in pkg.dependencies() {
+			if let Some(version) = versions.get(&dep.package_name()) {
+				if !dep.version_req().matches(version) {
+					rows.push([
+						pkg.name().to_string(),
+						dep.package_name().to_string(),
+						dep.version_req().to_string(),
+						version.to_string(),
+					]);
 				}
 			}
 		}
-		if updates_count == 0 {
-			c.shell().status("Done", "No dependency updates")?;
-		} else if updates_count == 1 {
-			c.shell().status("Done", "One dependency updated")?;
-		} else {
-			c.shell().status("Done", format!("{} dependencies updated", updates_count))?;
+	}
+
+	if rows.is_empty() {
+		return Ok(())
+	}
+	println!("{}", render_table(["dependent", "dependency", "requirement", "version"], &rows));
+	anyhow::bail!("{} dependency requirements can't be satisfied by the workspace", rows.len())
+}
+
+/// Verify the workspace is in lockstep on `version` after `set_version`: every `selected`
+/// package must have that version. Packages that weren't selected, but are depended upon by
+/// a selected one, while having a different version, are warned about.
+///
+/// Follows `util::set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn check_lockstep(
+	ws: &Workspace<'_>,
+	selected: &HashSet<InternedString>,
+	version: &Version,
+) -> Result<(), anyhow::Error> {
+	let c = ws.config();
+	let members = members_deep(ws)?;
+
+	let mismatched = members
+		.iter()
+		.filter(|p| selected.contains(&p.name()) && p.version() != version)
+		.map(|p| format!("{} ({})", p.name(), p.version()))
+		.collect::<Vec<_>>();
+	if !mismatched.is_empty() {
+		anyhow::bail!("Not all selected crates are at {}: {}", version, mismatched.join(", "))
+	}
+
+	for pkg in members
+		.iter()
+		.filter(|p| !selected.contains(&p.name()) && p.version() != version)
+	{
+		let dependents = members
+			.iter()
+			.filter(|m| {
+				selected.contains(&m.name()) &&
+					m.dependencies().iter().any(|d| d.package_name() == pkg.name())
+			})
+			.map(|m| m.name().as_str())
+			.collect::<Vec<_>>();
+		if !dependents.is_empty() {
+			c.shell().warn(format!(
+				"{} ({}) wasn't selected and isn't at {}, but is depended upon by {}",
+				pkg.name(),
+				pkg.version(),
+				version,
+				dependents.join(", ")
+			))?;
 		}
+	}
 
-		Ok(())
-	})?;
+	Ok(())
+}
 
+/// Rewrite the version requirement of every local path dependency of the packages matching
+/// predicate to the current version of the crate it points to, without changing any
+/// package's own version. With `report`, the requirements rewritten are written to that file.
+///
+/// Follows `util::set_dependency_depth`, `dry_run::enable` and `metrics::enable` as set on the
+/// calling thread.
+pub fn sync_dependencies<P>(
+	ws: &Workspace<'_>,
+	predicate: P,
+	report: Option<&Path>,
+) -> Result<(), anyhow::Error>
+where
+	P: Fn(&Package) -> bool,
+{
+	let c = ws.config();
+	let members = members_deep(ws)?;
+	let versions = members
+		.iter()
+		.map(|p| (p.name().as_str().to_owned(), p.version().clone()))
+		.collect::<HashMap<_, _>>();
+
+	let rewrites = RefCell::new(Vec::new());
+	let total = edit_each(members.iter().filter(|p| predicate(p)), |p, doc| {
+		Ok(edit_each_dep_all(doc.as_table_mut(), |name, _, wrap, section| {
+			let (from, to) = match check_for_update(name.clone(), wrap, &versions, section, true) {
+				// rewritten to what it was
+				Some((from, to)) if from.as_ref() != Some(&to) => (from, to),
+				_ => return DependencyAction::Untouched,
+			};
+			c.shell()
+				.status(
+					"Syncing",
+					format!(
+						"{}: {} {} -> {}",
+						p.name(),
+						name,
+						from.as_deref().unwrap_or("(none)"),
+						to
+					),
+				)
+				.expect("Writing to the shell works. qed");
+			rewrites.borrow_mut().push(Rewrite {
+				dependent: p.name().to_string(),
+				dependency: name,
+				from,
+				to,
+			});
+			DependencyAction::Mutated
+		}))
+	})?
+	.iter()
+	.sum::<u32>();
+
+	if total == 0 {
+		c.shell().status("Done", "All dependencies already in sync")?;
+	} else {
+		c.shell().status("Done", format!("{} dependencies synced", total))?;
+	}
+	if let Some(path) = report {
+		write_report(path, rewrites.into_inner())?;
+	}
 	Ok(())
 }
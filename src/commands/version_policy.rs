@@ -0,0 +1,88 @@
+use crate::{config::VersionPolicy, util::render_table};
+use cargo::{core::package::Package, util::OptVersionReq};
+use semver::VersionReq;
+
+/// Find what's wrong with `req` according to `policy`, if anything.
+fn violation(req: &VersionReq, stable: bool, policy: &VersionPolicy) -> Option<String> {
+	if req.comparators.is_empty() {
+		return if policy.forbid_wildcard { Some("wildcard".to_owned()) } else { None }
+	}
+	for c in req.comparators.iter() {
+		let components = 1 + c.minor.is_some() as u8 + c.patch.is_some() as u8;
+		if components < policy.min_components {
+			return Some(format!("less than {} version components", policy.min_components))
+		}
+		if policy.forbid_pre && stable && !c.pre.is_empty() {
+			return Some("pre-release requirement in stable crate".to_owned())
+		}
+	}
+	None
+}
+
+/// Check the requirements on all external (non-path) dependencies of `packages` against
+/// `policy`. Report each violation as `(crate, dependency, requirement)` in a table and fail
+/// if there are any.
+pub fn check_version_policy(
+	packages: &[Package],
+	policy: &VersionPolicy,
+) -> Result<(), anyhow::Error> {
+	let mut rows = Vec::new();
+	for pkg in packages {
+		let stable = pkg.version().pre.is_empty();
+		for dep in pkg.dependencies() {
+			if dep.source_id().is_path() {
+				continue
+			}
+			let req = match dep.version_req() {
+				OptVersionReq::Any => VersionReq::STAR,
+				OptVersionReq::Req(req) | OptVersionReq::Locked(_, req) => req.clone(),
+			};
+			// git dependencies without a version are taken care of by the other checks
+			if dep.source_id().is_git() && req.comparators.is_empty() {
+				continue
+			}
+			if let Some(problem) = violation(&req, stable, policy) {
+				rows.push([
+					pkg.name().to_string(),
+					dep.package_name().to_string(),
+					req.to_string(),
+					problem,
+				]);
+			}
+		}
+	}
+
+	if rows.is_empty() {
+		return Ok(())
+	}
+	println!("{}", render_table(["crate", "dependency", "requirement", "violation"], &rows));
+	anyhow::bail!("{} dependency requirements violate the version policy", rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn check(req: &str, stable: bool) -> Option<String> {
+		violation(&VersionReq::parse(req).unwrap(), stable, &VersionPolicy::default())
+	}
+
+	#[test]
+	fn default_policy() {
+		assert_eq!(check("1.2", true), None);
+		assert_eq!(check(">=1.2, <2.0", true), None);
+		assert!(check("1", true).is_some());
+		assert!(check(">=1.2, <2", true).is_some());
+		assert!(check("*", true).is_some());
+		assert!(check("1.0.0-rc.1", true).is_some());
+		assert_eq!(check("1.0.0-rc.1", false), None);
+	}
+
+	#[test]
+	fn relaxed_policy() {
+		let policy = VersionPolicy { min_components: 1, forbid_wildcard: false, forbid_pre: false };
+		for req in ["1", "*", "1.0.0-rc.1"] {
+			assert_eq!(violation(&VersionReq::parse(req).unwrap(), true, &policy), None);
+		}
+	}
+}
@@ -0,0 +1,202 @@
+//! The `.unleash.toml` file next to the workspace's root manifest, holding repository wide
+//! release policy. Without that file, the `[workspace.metadata.unleash]` table of the root
+//! manifest is used instead, the two aren't merged.
+//!
+//! The `[select]` and `[publish]` defaults apply where the command line doesn't say otherwise:
+//! flags given there take precedence over the config, which takes precedence over the built-in
+//! defaults.
+
+use anyhow::Context;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs::read_to_string, path::Path};
+
+/// Name of the config file, looked up in the folder of the root manifest
+pub const CONFIG_FILE: &str = ".unleash.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+	/// Rules the requirements on external dependencies must follow
+	#[serde(default)]
+	pub dependency_version_policy: VersionPolicy,
+	/// Owners of the crates, by glob on the crate name, e.g. `"pallet-*" = ["github:org:team"]`
+	#[serde(default)]
+	pub owners: BTreeMap<String, Vec<String>>,
+	/// Values to fill in for missing package metadata
+	#[serde(default)]
+	pub metadata_defaults: MetadataDefaults,
+	/// Defaults for selecting the packages of all commands
+	#[serde(default)]
+	pub select: SelectDefaults,
+	/// Defaults for publishing with `em-dragons`
+	#[serde(default)]
+	pub publish: PublishDefaults,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct SelectDefaults {
+	/// Like `--skip`, unless that or any of `--packages`, `--package-list`, `--changed-since` and
+	/// `--changed-between` is given
+	#[serde(default)]
+	pub skip: Vec<String>,
+	/// Like `--ignore-pre-version`, in the same cases as `skip`
+	#[serde(default)]
+	pub ignore_pre_version: Vec<String>,
+	/// Like `--ignore-publish`
+	#[serde(default)]
+	pub ignore_publish: bool,
+	/// Like `--include-pre-deps`
+	#[serde(default)]
+	pub include_pre_deps: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PublishDefaults {
+	/// Like `--registry`
+	pub registry: Option<String>,
+	/// Like `--exclude-registry`, unless that is given
+	#[serde(default)]
+	pub exclude_registry: Vec<String>,
+	/// The environment variable to read the token from, if `--token` and `CRATES_TOKEN` aren't
+	/// set. Keeps the token itself out of the repository.
+	pub token_env: Option<String>,
+	/// Like `--skip-published`
+	#[serde(default)]
+	pub skip_published: bool,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct MetadataDefaults {
+	/// The `license` of crates not giving one
+	pub license: Option<String>,
+	/// The `description` of crates not giving one, `{name}` is replaced by the crate's name
+	pub description: Option<String>,
+	/// The `repository` of crates not giving one, the url of the `origin` git remote if not
+	/// set
+	pub repository: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case", default, deny_unknown_fields)]
+pub struct VersionPolicy {
+	/// How many of `major.minor.patch` a requirement must give at least
+	pub min_components: u8,
+	/// Forbid `*` requirements
+	pub forbid_wildcard: bool,
+	/// Forbid requirements on pre-releases in crates that aren't pre-releases themselves
+	pub forbid_pre: bool,
+}
+
+impl Default for VersionPolicy {
+	fn default() -> Self {
+		VersionPolicy { min_components: 2, forbid_wildcard: true, forbid_pre: true }
+	}
+}
+
+/// Load the config for the workspace of `root_manifest`: the config file if there is one,
+/// otherwise `[workspace.metadata.unleash]`, the defaults if neither is given.
+pub fn load(root_manifest: &Path) -> Result<Config, anyhow::Error> {
+	let path = root_manifest
+		.parent()
+		.expect("Manifest is always within a folder. qed")
+		.join(CONFIG_FILE);
+	if path.exists() {
+		let content =
+			read_to_string(&path).with_context(|| format!("Reading {} failed", path.display()))?;
+		return toml::from_str(&content)
+			.with_context(|| format!("Parsing {} failed", path.display()))
+	}
+
+	let content = read_to_string(root_manifest)
+		.with_context(|| format!("Reading {} failed", root_manifest.display()))?;
+	from_manifest(&content).with_context(|| {
+		format!("Parsing [workspace.metadata.unleash] of {} failed", root_manifest.display())
+	})
+}
+
+/// The config in the `[workspace.metadata.unleash]` table of the manifest `content`
+fn from_manifest(content: &str) -> Result<Config, anyhow::Error> {
+	let manifest: toml::Value = toml::from_str(content)?;
+	match manifest
+		.get("workspace")
+		.and_then(|w| w.get("metadata"))
+		.and_then(|m| m.get("unleash"))
+	{
+		Some(table) => Ok(table.clone().try_into()?),
+		None => Ok(Config::default()),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn partial_policy_uses_defaults() {
+		let config: Config = toml::from_str(
+			r#"
+[dependency-version-policy]
+min-components = 3
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.dependency_version_policy.min_components, 3);
+		assert!(config.dependency_version_policy.forbid_wildcard);
+		assert!(toml::from_str::<Config>("unknown = 1").is_err());
+	}
+
+	#[test]
+	fn owners() {
+		let config: Config = toml::from_str(
+			r#"
+[owners]
+"pallet-*" = ["github:org:runtime", "alice"]
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.owners["pallet-*"], vec!["github:org:runtime", "alice"]);
+		assert!(!config.owners.contains_key("other"));
+	}
+
+	#[test]
+	fn metadata_defaults() {
+		let config: Config = toml::from_str(
+			r#"
+[metadata-defaults]
+license = "Apache-2.0"
+description = "The {name} crate"
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.metadata_defaults.license.as_deref(), Some("Apache-2.0"));
+		assert_eq!(config.metadata_defaults.description.as_deref(), Some("The {name} crate"));
+		assert_eq!(config.metadata_defaults.repository, None);
+	}
+
+	#[test]
+	fn workspace_metadata() {
+		let config = from_manifest(
+			r#"
+[workspace]
+members = ["a"]
+
+[workspace.metadata.unleash.select]
+skip = ["^test-"]
+
+[workspace.metadata.unleash.publish]
+token-env = "RELEASE_TOKEN"
+"#,
+		)
+		.unwrap();
+		assert_eq!(config.select.skip, vec!["^test-"]);
+		assert!(!config.select.ignore_publish);
+		assert_eq!(config.publish.token_env.as_deref(), Some("RELEASE_TOKEN"));
+
+		let config = from_manifest("[workspace]\nmembers = [\"a\"]\n").unwrap();
+		assert!(config.select.skip.is_empty());
+		assert!(from_manifest("[workspace.metadata.unleash]\nunknown = 1\n").is_err());
+	}
+}
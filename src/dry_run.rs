@@ -0,0 +1,139 @@
+//! Preview the changes to manifests instead of writing them.
+//!
+//! Once `enable` was called, `write` keeps the new content of a manifest in memory rather than
+//! on disk and `read` returns it, so commands editing the same manifest more than once see
+//! their earlier edits. `diffs` shows all of them as unified diffs against the files.
+
+use std::{
+	cell::RefCell,
+	collections::BTreeMap,
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+/// The original and the changed content of each manifest
+type Changes = BTreeMap<PathBuf, (String, String)>;
+
+thread_local! {
+	static CHANGES: RefCell<Option<Changes>> = RefCell::new(None);
+}
+
+/// Start keeping changes in memory, on the calling thread only
+pub fn enable() {
+	CHANGES.with(|c| *c.borrow_mut() = Some(Changes::new()));
+}
+
+/// Whether changes are kept in memory on the calling thread
+pub fn enabled() -> bool {
+	CHANGES.with(|c| c.borrow().is_some())
+}
+
+/// Run `f` writing to the files directly even if enabled, for temporary changes that are
+/// reverted before the command finishes
+pub fn bypass<T>(f: impl FnOnce() -> T) -> T {
+	let changes = CHANGES.with(|c| c.borrow_mut().take());
+	let result = f();
+	CHANGES.with(|c| *c.borrow_mut() = changes);
+	result
+}
+
+/// The content of the file at `path`, including the changes kept in memory
+pub fn read(path: &Path) -> io::Result<String> {
+	match CHANGES.with(|c| c.borrow().as_ref().and_then(|c| c.get(path)).map(|(_, n)| n.clone())) {
+		Some(content) => Ok(content),
+		None => fs::read_to_string(path),
+	}
+}
+
+/// Write `content` to the file at `path`, or keep it in memory if enabled
+pub fn write(path: &Path, content: String) -> io::Result<()> {
+	CHANGES.with(|c| match c.borrow_mut().as_mut() {
+		Some(changes) => {
+			match changes.get_mut(path) {
+				Some((_, new)) => *new = content,
+				None => {
+					// a file created by the command is shown as added in full
+					let old = match fs::read_to_string(path) {
+						Err(e) if e.kind() == io::ErrorKind::NotFound => String::new(),
+						res => res?,
+					};
+					changes.insert(path.to_path_buf(), (old, content));
+				},
+			}
+			Ok(())
+		},
+		None => fs::write(path, content),
+	})
+}
+
+/// The unified diff of the files changed so far, paths relative to `base` if within it
+pub fn diffs(base: &Path) -> String {
+	CHANGES.with(|c| {
+		let changes = c.borrow();
+		let mut out = String::new();
+		for (path, (old, new)) in changes.iter().flat_map(|c| c.iter()) {
+			if old == new {
+				continue
+			}
+			let name = path.strip_prefix(base).unwrap_or(path).display().to_string();
+			let (old, new) = (lines(old), lines(new));
+			let diff = difflib::unified_diff(&old, &new, &name, &name, "", "", 3);
+			for (i, line) in diff.iter().enumerate() {
+				if i < 2 {
+					// headers, without the empty dates
+					out.push_str(line.trim_end());
+					out.push('\n');
+				} else {
+					out.push_str(line);
+					if !line.ends_with('\n') {
+						out.push_str("\n\\ No newline at end of file\n");
+					}
+				}
+			}
+		}
+		out
+	})
+}
+
+fn lines(content: &str) -> Vec<&str> {
+	content.split_inclusive('\n').collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keeps_changes_in_memory() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		let path = temp.path().join("Cargo.toml");
+		fs::write(&path, "[package]\nname = \"a\"\nversion = \"0.1.0\"\n").unwrap();
+
+		enable();
+		write(&path, read(&path).unwrap().replace("0.1.0", "0.2.0")).unwrap();
+		write(&path, read(&path).unwrap().replace("\"a\"", "\"b\"")).unwrap();
+
+		assert_eq!(
+			fs::read_to_string(&path).unwrap(),
+			"[package]\nname = \"a\"\nversion = \"0.1.0\"\n"
+		);
+		assert_eq!(read(&path).unwrap(), "[package]\nname = \"b\"\nversion = \"0.2.0\"\n");
+		assert_eq!(
+			diffs(temp.path()),
+			"--- Cargo.toml\n+++ Cargo.toml\n@@ -1,3 +1,3 @@\n [package]\n-name = \"a\"\n-version = \"0.1.0\"\n+name = \"b\"\n+version = \"0.2.0\"\n"
+		);
+	}
+
+	#[test]
+	fn creates_files_in_memory() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		let path = temp.path().join("README.md");
+
+		enable();
+		write(&path, "# a\n".to_owned()).unwrap();
+
+		assert!(!path.exists());
+		assert_eq!(read(&path).unwrap(), "# a\n");
+		assert_eq!(diffs(temp.path()), "--- README.md\n+++ README.md\n@@ -0,0 +1 @@\n+# a\n");
+	}
+}
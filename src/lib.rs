@@ -0,0 +1,27 @@
+//! Tooling to manage releasing of crates in massive workspaces/monorepos.
+//!
+//! This is what `cargo unleash` is built upon, so release scripts can use it directly rather
+//! than shelling out. The main entry points are:
+//!
+//!  - [`commands::packages_to_release`] to find the crates to release in dependency order, selected
+//!    by any predicate
+//!  - [`commands::set_version`] to change versions and update the dependents accordingly
+//!  - [`commands::check`] to verify the crates can be packaged and build in isolation
+//!  - [`commands::release`] to publish them
+//!  - [`util::changed_packages`] to find the crates changed between git references
+//!
+//! Options of these beyond plain flags are found in [`options`].
+//!
+//! A few settings apply to a whole run rather than a single call, they are kept per thread:
+//! [`util::set_dependency_depth`], [`util::set_quiet`], [`util::disable_registry_cache`],
+//! [`dry_run::enable`] and [`metrics::enable`]. Each function following any of them says so;
+//! when calling those from other threads, make the settings there, too.
+
+pub mod commands;
+pub mod config;
+pub mod dry_run;
+pub mod metrics;
+pub mod options;
+#[cfg(feature = "select-script")]
+pub mod script;
+pub mod util;
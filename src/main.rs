@@ -1,7 +1,5 @@
 use structopt::StructOpt;
 mod cli;
-mod commands;
-mod util;
 
 use cli::Opt;
 
@@ -0,0 +1,118 @@
+//! Record how long the stages of a run take, to find bottlenecks on large workspaces.
+//!
+//! Recording is off unless `enable` was called, in which case `time` notes every run of a
+//! stage and `write` stores them together with a per-stage summary as JSON.
+
+use serde::Serialize;
+use std::{
+	cell::RefCell,
+	collections::BTreeMap,
+	fs::File,
+	path::Path,
+	time::{Duration, Instant},
+};
+
+#[derive(Serialize)]
+struct Record {
+	stage: &'static str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	package: Option<String>,
+	/// Number of items handled, e.g. crates discovered
+	count: usize,
+	duration_ms: f64,
+}
+
+#[derive(Serialize, Default)]
+struct Summary {
+	runs: usize,
+	count: usize,
+	duration_ms: f64,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+	stages: BTreeMap<&'static str, Summary>,
+	records: &'a [Record],
+}
+
+thread_local! {
+	static RECORDS: RefCell<Option<Vec<Record>>> = RefCell::new(None);
+}
+
+fn millis(d: Duration) -> f64 {
+	d.as_secs_f64() * 1000.0
+}
+
+/// Start recording, on the calling thread only
+pub fn enable() {
+	RECORDS.with(|r| *r.borrow_mut() = Some(Vec::new()));
+}
+
+/// Run `f` as `stage` (of `package`, if given) and record its duration, if enabled. `count`
+/// extracts the number of items handled from the result.
+pub fn time<R, F, C>(stage: &'static str, package: Option<&str>, count: C, f: F) -> R
+where
+	F: FnOnce() -> R,
+	C: FnOnce(&R) -> usize,
+{
+	let start = Instant::now();
+	let res = f();
+	record(stage, package, count(&res), start.elapsed());
+	res
+}
+
+/// Record a run of `stage` (of `package`, if given) handling `count` items that took
+/// `duration`, if enabled. For stages run elsewhere, e.g. on another thread.
+pub fn record(stage: &'static str, package: Option<&str>, count: usize, duration: Duration) {
+	RECORDS.with(|r| {
+		if let Some(records) = r.borrow_mut().as_mut() {
+			records.push(Record {
+				stage,
+				package: package.map(|p| p.to_owned()),
+				count,
+				duration_ms: millis(duration),
+			});
+		}
+	});
+}
+
+/// Write what was recorded so far as JSON to `path`
+pub fn write(path: &Path) -> Result<(), anyhow::Error> {
+	RECORDS.with(|r| {
+		let records = r.borrow();
+		let records = records.as_deref().unwrap_or_default();
+		let mut stages = BTreeMap::<_, Summary>::new();
+		for record in records {
+			let summary = stages.entry(record.stage).or_default();
+			summary.runs += 1;
+			summary.count += record.count;
+			summary.duration_ms += record.duration_ms;
+		}
+		let file = File::create(path)?;
+		serde_json::to_writer_pretty(file, &Report { stages, records })?;
+		Ok(())
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn records_only_when_enabled() {
+		assert_eq!(time("disabled", None, |_| 1, || 5), 5);
+		RECORDS.with(|r| assert!(r.borrow().is_none()));
+
+		enable();
+		time("discovery", None, |v: &Vec<u8>| v.len(), || vec![1, 2, 3]);
+		time("pack", Some("crateA"), |_| 1, || ());
+		RECORDS.with(|r| {
+			let records = r.borrow();
+			let records = records.as_ref().unwrap();
+			assert_eq!(records.len(), 2);
+			assert_eq!(records[0].stage, "discovery");
+			assert_eq!(records[0].count, 3);
+			assert_eq!(records[1].package.as_deref(), Some("crateA"));
+		});
+	}
+}
@@ -0,0 +1,102 @@
+//! Options of the commands that go beyond plain flags, shared by the command line interface
+//! and users of the library.
+
+use std::path::PathBuf;
+use structopt::{
+	clap::{arg_enum, AppSettings::*},
+	StructOpt,
+};
+
+arg_enum! {
+	#[derive(Debug, PartialEq, Eq)]
+	pub enum GenerateReadmeMode {
+		// Generate Readme only if it is missing.
+		IfMissing,
+		// Generate Readme & append to existing file.
+		Append,
+		// Generate Readme & overwrite existing file.
+		Overwrite,
+	}
+}
+
+arg_enum! {
+	#[derive(Debug, PartialEq, Eq)]
+	pub enum VersionReqStyle {
+		// Plain version, e.g. `1.2`, which cargo reads as a caret requirement.
+		Bare,
+		// Explicit caret requirement, e.g. `^1.2`.
+		Caret,
+	}
+}
+
+arg_enum! {
+	#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+	pub enum OutputFormat {
+		// Prose for humans to read.
+		Human,
+		// Structured records, for scripts to consume.
+		Json,
+	}
+}
+
+arg_enum! {
+	#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+	pub enum StatsFormat {
+		// Human readable lines.
+		Text,
+		// A single JSON object.
+		Json,
+	}
+}
+
+#[derive(StructOpt, Debug, Clone)]
+#[structopt(setting(ColorAuto), setting(ColoredHelp))]
+pub struct BuildOptions {
+	/// Actually build the package
+	///
+	/// By default, this only runs `cargo check` against the package
+	/// build. Set this flag to have it run an actual `build` instead.
+	#[structopt(long)]
+	pub build: bool,
+	/// Features to activate when checking the packages
+	///
+	/// Space or comma separated, can be given multiple times.
+	#[structopt(long)]
+	pub features: Vec<String>,
+	/// Activate all available features when checking the packages
+	#[structopt(long)]
+	pub all_features: bool,
+	/// Do not activate the `default` feature when checking the packages
+	#[structopt(long)]
+	pub no_default_features: bool,
+	/// Check for the given target triple
+	///
+	/// Can be given multiple times, the host is used if none is given.
+	#[structopt(long = "target")]
+	pub targets: Vec<String>,
+	/// Number of parallel jobs, defaults to # of CPUs
+	///
+	/// If given, up to this many crates not depending on each other are verified at the same
+	/// time, sharing the jobs. Otherwise one crate after the other.
+	#[structopt(short, long)]
+	pub jobs: Option<u32>,
+	/// Verify all packages, even those unchanged since they were last verified
+	///
+	/// Packages are fingerprinted by their most recently modified file, their dependencies and
+	/// the options above. Unless this is given, those that already passed with the same
+	/// fingerprint are not compiled again.
+	#[structopt(long)]
+	pub no_cache: bool,
+	/// Verify all packages, even if some fail
+	///
+	/// By default, the first package failing verification stops the check. With this, all
+	/// failures are reported at the end.
+	#[structopt(long)]
+	pub keep_going: bool,
+	/// Directory for the packed crates and their verification builds
+	///
+	/// Keeps them apart from the `target` directory of the workspace, e.g. to cache them
+	/// separately. The fingerprints of verified packages are kept there, too.
+	#[structopt(long, parse(from_os_str))]
+	pub target_dir: Option<PathBuf>,
+}
@@ -0,0 +1,49 @@
+//! Select packages by a [rhai](https://rhai.rs) script, for selections too complex for the
+//! command line options.
+//!
+//! The script is run once per package with these variables set and must evaluate to a bool:
+//!
+//!  - `name`: the package name
+//!  - `version`: its version, as a string
+//!  - `path`: the folder of its manifest
+//!  - `features`: the names of the features it declares
+//!  - `dependencies`: the names of the packages it depends upon
+
+use cargo::{core::package::Package, util::interning::InternedString};
+use rhai::{Array, Dynamic, Engine, Scope, AST};
+use std::{collections::HashSet, path::Path};
+
+fn names<I: Iterator<Item = InternedString>>(iter: I) -> Array {
+	iter.map(|n| Dynamic::from(n.to_string())).collect()
+}
+
+fn matches(engine: &Engine, ast: &AST, pkg: &Package) -> Result<bool, anyhow::Error> {
+	let mut scope = Scope::new();
+	scope.push("name", pkg.name().to_string());
+	scope.push("version", pkg.version().to_string());
+	scope.push("path", pkg.root().display().to_string());
+	scope.push("features", names(pkg.summary().features().keys().copied()));
+	scope.push("dependencies", names(pkg.dependencies().iter().map(|d| d.package_name())));
+	engine
+		.eval_ast_with_scope::<bool>(&mut scope, ast)
+		.map_err(|e| anyhow::anyhow!("Select script failed on {}: {}", pkg.name(), e))
+}
+
+/// Run the script at `path` on each of `packages`, return the names of those it selected.
+pub fn select_by_script(
+	path: &Path,
+	packages: &[Package],
+) -> Result<HashSet<InternedString>, anyhow::Error> {
+	let engine = Engine::new();
+	let ast = engine
+		.compile_file(path.to_path_buf())
+		.map_err(|e| anyhow::anyhow!("Loading select script {} failed: {}", path.display(), e))?;
+
+	let mut selected = HashSet::new();
+	for pkg in packages {
+		if matches(&engine, &ast, pkg)? {
+			selected.insert(pkg.name());
+		}
+	}
+	Ok(selected)
+}
@@ -1,56 +1,207 @@
+use crate::{dry_run, metrics};
 use anyhow::Context;
 use cargo::{
-	core::{package::Package, Workspace},
-	sources::PathSource,
+	core::{
+		package::Package,
+		shell::{Shell, Verbosity},
+		Dependency, Source, SourceId, Summary, Workspace,
+	},
+	ops::{registry_configuration, RegistryConfig},
+	sources::{registry::RegistrySource, PathSource, CRATES_IO_REGISTRY},
+	util::{config::Config, interning::InternedString, Progress, ProgressStyle},
+};
+use cargo_util::paths;
+use filetime::FileTime;
+use git2::{Commit, Repository, Status, StatusOptions};
+use log::{error, trace, warn};
+use petgraph::{
+	graph::{EdgeIndex, NodeIndex},
+	visit::EdgeRef,
+	Directed, Graph,
+};
+use semver::Version;
+use std::{
+	cell::{Cell, RefCell},
+	collections::{hash_map::Entry, HashMap, HashSet, VecDeque},
+	fs,
+	path::{Component, Path, PathBuf},
+	process::{Command, Stdio},
+	task::Poll,
 };
-use git2::Repository;
-use log::{trace, warn};
-use std::{collections::HashSet, fs};
 use toml_edit::{Document, InlineTable, Item, Table, Value};
 
-pub fn changed_packages<'a>(
-	ws: &'a Workspace,
+/// The commit `reference` (a tag, branch or commit) points to
+fn reference_commit<'r>(
+	repo: &'r Repository,
 	reference: &str,
+) -> Result<Commit<'r>, anyhow::Error> {
+	repo.revparse_single(reference)
+		.and_then(|d| d.peel_to_commit())
+		.with_context(|| format!("Reference {} not found in git repository", reference))
+}
+
+/// Fail if the git repository `path` is in has uncommitted changes, ignored files aside.
+/// Nothing to check if it isn't within a git repository.
+pub fn ensure_clean_tree(path: &Path) -> Result<(), anyhow::Error> {
+	let repo = match Repository::discover(path) {
+		Ok(repo) => repo,
+		Err(_) => return Ok(()),
+	};
+	let statuses = repo
+		.statuses(Some(
+			StatusOptions::new()
+				.include_untracked(true)
+				.recurse_untracked_dirs(true)
+				.include_ignored(false),
+		))
+		.context("Reading git status failed")?;
+	let dirty = statuses
+		.iter()
+		.filter(|s| !s.status().intersects(Status::CURRENT | Status::IGNORED))
+		.filter_map(|s| s.path().map(|p| p.to_owned()))
+		.collect::<Vec<_>>();
+	if !dirty.is_empty() {
+		let more = dirty.len().saturating_sub(10);
+		anyhow::bail!(
+			"The working tree has uncommitted changes, commit or stash them first: {}{}",
+			dirty[..dirty.len() - more].join(", "),
+			if more > 0 { format!(" and {} more", more) } else { String::new() }
+		)
+	}
+	Ok(())
+}
+
+/// Resolve `.` and `..` in `path` without looking at the file system
+fn normalize_path(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {},
+			Component::ParentDir => {
+				normalized.pop();
+			},
+			c => normalized.push(c),
+		}
+	}
+	normalized
+}
+
+/// Whether the changed `file` belongs to `pkg`: it is within the package's folder, is its
+/// `build` script or matches one of its `include` patterns, even if outside of the folder.
+pub(crate) fn affects_package(pkg: &Package, file: &Path) -> bool {
+	let root = pkg.root();
+	if file.starts_with(root) {
+		return true
+	}
+	if pkg
+		.targets()
+		.iter()
+		.any(|t| t.is_custom_build() && t.src_path().path() == Some(file))
+	{
+		return true
+	}
+	pkg.manifest().include().iter().any(|include| {
+		let included = normalize_path(&root.join(include));
+		file.starts_with(&included) ||
+			glob::Pattern::new(&included.to_string_lossy())
+				.map(|p| p.matches_path(file))
+				.unwrap_or(false)
+	})
+}
+
+/// Find the packages with changes between `from` and `to`, the current git HEAD if not given.
+/// Like `git diff from...to`, changes are taken since the commit `to` branched off of `from`,
+/// unless `two_dot`, which compares the two directly, like `git diff from..to`.
+///
+/// A change is attributed to a package if the file is within its folder, is its `build` script
+/// or matches its `include` patterns. A changed path dependency outside of the workspace marks
+/// the members depending on it (directly or not) changed, too. With `include_root`, changing a
+/// file right in the workspace's root folder, e.g. `Cargo.toml`, `Cargo.lock` or
+/// `rust-toolchain.toml`, marks all packages changed.
+///
+/// Follows `set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn changed_packages(
+	ws: &Workspace,
+	from: &str,
+	to: Option<&str>,
+	include_root: bool,
+	two_dot: bool,
 ) -> Result<HashSet<Package>, anyhow::Error> {
 	ws.config()
 		.shell()
-		.status("Calculating", format!("git diff since {:}", reference))
+		.status(
+			"Calculating",
+			match to {
+				Some(to) => format!("git diff between {:} and {:}", from, to),
+				None => format!("git diff since {:}", from),
+			},
+		)
 		.expect("Writing to Shell doesn't fail");
 
 	let path = ws.root();
-	let repo = Repository::open(&path).context("Workspace isn't a git repo")?;
-	let current_head = repo
-		.head()
-		.and_then(|b| b.peel_to_commit())
-		.and_then(|c| c.tree())
-		.context("Could not determine current git HEAD")?;
-	let main = repo
-		.resolve_reference_from_short_name(reference)
-		.and_then(|d| d.peel_to_commit())
-		.and_then(|c| c.tree())
-		.context("Reference not found in git repository")?;
+	let repo = Repository::discover(path).context("Workspace isn't within a git repo")?;
+	// path dependencies may be outside of the workspace, but within the repository
+	let repo_root = repo.workdir().context("Git repository has no working directory")?;
+	let old = reference_commit(&repo, from)?;
+	let new = match to {
+		Some(to) => reference_commit(&repo, to)?,
+		None => repo
+			.head()
+			.and_then(|b| b.peel_to_commit())
+			.context("Could not determine current git HEAD")?,
+	};
+	let old = if two_dot {
+		old
+	} else {
+		let base = repo.merge_base(old.id(), new.id()).with_context(|| {
+			format!("No common ancestor of {} and {}", from, to.unwrap_or("HEAD"))
+		})?;
+		repo.find_commit(base)?
+	};
+	let (old, new) = (old.tree()?, new.tree()?);
 
-	let diff = repo
-		.diff_tree_to_tree(Some(&current_head), Some(&main), None)
-		.context("Diffing failed")?;
+	let diff = repo.diff_tree_to_tree(Some(&old), Some(&new), None).context("Diffing failed")?;
 
 	let files = diff
 		.deltas()
-		.filter_map(|d| d.new_file().path())
-		.filter_map(|d| if d.is_file() { d.parent() } else { Some(d) })
-		.map(|l| path.join(l))
+		.filter_map(|d| d.new_file().path().or_else(|| d.old_file().path()))
+		.map(|l| repo_root.join(l))
 		.collect::<Vec<_>>();
 
 	trace!("Files changed since: {:#?}", files);
 
-	let mut packages = HashSet::new();
+	if include_root {
+		if let Some(f) = files.iter().find(|f| f.parent() == Some(path)) {
+			ws.config()
+				.shell()
+				.status("Changed", format!("{} in the root, considering all crates", f.display()))
+				.expect("Writing to Shell doesn't fail");
+			return Ok(members_deep(ws)?.into_iter().collect())
+		}
+	}
+
+	let all = members_deep(ws)?;
+	let mut packages = all
+		.iter()
+		.filter(|m| files.iter().any(|f| affects_package(m, f)))
+		.cloned()
+		.collect::<HashSet<_>>();
 
-	for m in members_deep(ws) {
-		let root = m.root();
-		for f in files.iter() {
-			if f.starts_with(root) {
-				packages.insert(m);
-				break
+	// the members don't see changes of path dependencies outside of the workspace otherwise
+	let mut changed_outside = packages
+		.iter()
+		.filter(|p| !ws.is_member(p))
+		.map(|p| p.name())
+		.collect::<Vec<_>>();
+	while let Some(name) = changed_outside.pop() {
+		for dependent in all.iter().filter(|m| {
+			m.dependencies()
+				.iter()
+				.any(|d| d.package_name() == name && d.source_id().is_path())
+		}) {
+			if packages.insert(dependent.clone()) {
+				trace!("{} depends on changed {}", dependent.name(), name);
+				changed_outside.push(dependent.name());
 			}
 		}
 	}
@@ -58,27 +209,440 @@ pub fn changed_packages<'a>(
 	Ok(packages)
 }
 
-// Find all members of the workspace, into the total depth
-pub fn members_deep(ws: &'_ Workspace) -> Vec<Package> {
-	let mut total_list = Vec::new();
-	for m in ws.members() {
-		total_list.push(m.clone());
-		for dep in m.dependencies() {
-			let source = dep.source_id();
-			if source.is_path() {
-				let dst = source.url().to_file_path().expect("It was just checked before. qed");
-				let mut src = PathSource::new(&dst, source, ws.config());
-				let pkg = src.root_package().expect("Path must have a package");
-				if !ws.is_member(&pkg) {
-					total_list.push(pkg);
+type PublishedCache = HashMap<(SourceId, InternedString), Vec<Version>>;
+
+thread_local! {
+	/// The versions found on the registries so far, `None` if disabled
+	static PUBLISHED: RefCell<Option<PublishedCache>> = RefCell::new(Some(HashMap::new()));
+}
+
+/// Always ask the registry in `published_versions`, instead of answering from what was found
+/// earlier in the run. Applies to the calling thread only.
+pub fn disable_registry_cache() {
+	PUBLISHED.with(|p| *p.borrow_mut() = None);
+}
+
+/// Split `names` into the ones with cached versions on `source_id`, and the ones to query
+fn cached_versions<I>(
+	source_id: SourceId,
+	names: I,
+) -> (HashMap<InternedString, Vec<Version>>, Vec<InternedString>)
+where
+	I: IntoIterator<Item = InternedString>,
+{
+	PUBLISHED.with(|p| {
+		let cache = p.borrow();
+		let mut found = HashMap::new();
+		let mut missing = Vec::new();
+		for name in names {
+			match cache.as_ref().and_then(|c| c.get(&(source_id, name))) {
+				Some(versions) =>
+					if !versions.is_empty() {
+						found.insert(name, versions.clone());
+					},
+				None => missing.push(name),
+			}
+		}
+		(found, missing)
+	})
+}
+
+fn cache_versions(source_id: SourceId, name: InternedString, versions: &[Version]) {
+	PUBLISHED.with(|p| {
+		if let Some(cache) = p.borrow_mut().as_mut() {
+			cache.insert((source_id, name), versions.to_vec());
+		}
+	})
+}
+
+fn registry_source_id(
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+) -> Result<SourceId, anyhow::Error> {
+	Ok(match registry {
+		Some(name) => SourceId::alt_registry(ws.config(), name)
+			.with_context(|| format!("Registry {} can't be read", name))?,
+		None => SourceId::crates_io(ws.config()).context(
+			"Your main registry (usually crates.io) can't be read. Please check your .cargo/config",
+		)?,
+	})
+}
+
+/// Query `registry` (the main one, usually crates.io, if `None`) for the versions of the crates
+/// `names` published there. Crates that were never published are missing from the result.
+/// Yanked versions are left out, as none are whitelisted for the query.
+///
+/// Crates already queried earlier in the run are answered from the cache, unless disabled via
+/// `disable_registry_cache`. See `refresh_published_versions` to always query.
+///
+/// Follows `disable_registry_cache` as set on the calling thread.
+pub fn published_versions<I>(
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+	names: I,
+) -> Result<HashMap<InternedString, Vec<Version>>, anyhow::Error>
+where
+	I: IntoIterator<Item = InternedString>,
+{
+	let (mut published, missing) = cached_versions(registry_source_id(ws, registry)?, names);
+	if !missing.is_empty() {
+		published.extend(refresh_published_versions(ws, registry, missing)?);
+	}
+	Ok(published)
+}
+
+/// The remote `registry` (the main one if `None`), its index not yet fetched
+fn remote_registry<'cfg>(
+	ws: &Workspace<'cfg>,
+	registry: Option<&str>,
+) -> Result<RegistrySource<'cfg>, anyhow::Error> {
+	let source_id = registry_source_id(ws, registry)?;
+	let mut registry = RegistrySource::remote(source_id, &Default::default(), ws.config())
+		.context("Failed getting remote registry")?;
+	registry.invalidate_cache();
+	Ok(registry)
+}
+
+/// The summaries of the versions of `dep` in `registry`'s index
+fn query_index(
+	registry: &mut RegistrySource<'_>,
+	dep: &Dependency,
+) -> Result<Vec<Summary>, anyhow::Error> {
+	let mut summaries = Vec::new();
+	loop {
+		match registry.query(dep, &mut |s| summaries.push(s)) {
+			Poll::Ready(res) => break res?,
+			Poll::Pending => registry.block_until_ready()?,
+		}
+	}
+	Ok(summaries)
+}
+
+/// Like `published_versions`, but always query `registry` and update the cache with the result
+///
+/// Follows `disable_registry_cache` as set on the calling thread.
+pub fn refresh_published_versions<I>(
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+	names: I,
+) -> Result<HashMap<InternedString, Vec<Version>>, anyhow::Error>
+where
+	I: IntoIterator<Item = InternedString>,
+{
+	let mut registry = remote_registry(ws, registry)?;
+	let _lock = ws.config().acquire_package_cache_lock()?;
+
+	let mut published = HashMap::new();
+	for name in names {
+		let dep = Dependency::parse(name, None, registry.source_id())
+			.expect("Parsing a dependency without version doesn't fail. qed");
+		let versions = query_index(&mut registry, &dep)?
+			.iter()
+			.map(|s| s.version().clone())
+			.collect::<Vec<_>>();
+		cache_versions(registry.source_id(), name, &versions);
+		if !versions.is_empty() {
+			published.insert(name, versions);
+		}
+	}
+	Ok(published)
+}
+
+/// The checksum `registry` (the main one if `None`) lists for the version of `pkg`, `None` if
+/// that isn't published there. Always queries the registry.
+pub fn published_checksum(
+	ws: &Workspace<'_>,
+	registry: Option<&str>,
+	pkg: &Package,
+) -> Result<Option<String>, anyhow::Error> {
+	let mut registry = remote_registry(ws, registry)?;
+	let _lock = ws.config().acquire_package_cache_lock()?;
+
+	let req = format!("={}", pkg.version());
+	let dep = Dependency::parse(pkg.name(), Some(&req), registry.source_id())?;
+	Ok(query_index(&mut registry, &dep)?
+		.iter()
+		.find(|s| s.version() == pkg.version())
+		.map(|s| s.checksum().unwrap_or_default().to_owned()))
+}
+
+/// Resolve the API token for `registry` (crates.io if `None`) the way `cargo publish` does.
+///
+/// That is, from `registry.token` or `registries.<name>.token` as stored by `cargo login` in
+/// `credentials.toml` (or set through config and environment), or by asking the configured
+/// `credential-process`. Returns `None` if nothing is configured.
+pub fn resolve_token(c: &Config, registry: Option<&str>) -> Result<Option<String>, anyhow::Error> {
+	let (exe, args) = match registry_configuration(c, registry)? {
+		RegistryConfig::None => return Ok(None),
+		RegistryConfig::Token(t) => return Ok(Some(t)),
+		RegistryConfig::Process(p) => p,
+	};
+
+	let name = registry.unwrap_or(CRATES_IO_REGISTRY);
+	let api_url = match registry {
+		None => "https://crates.io",
+		Some(_) if args.iter().any(|a| a.contains("{api_url}")) => anyhow::bail!(
+			"Credential process {} for registry {} needs the api url, please pass `--token`",
+			exe.display(),
+			name
+		),
+		Some(_) => "",
+	};
+	let output = Command::new(&exe)
+		.args(args.iter().map(|a| {
+			a.replace("{action}", "get")
+				.replace("{name}", name)
+				.replace("{api_url}", api_url)
+		}))
+		.env("CARGO_REGISTRY_NAME", name)
+		.env("CARGO_REGISTRY_API_URL", api_url)
+		.stdout(Stdio::piped())
+		.output()
+		.with_context(|| format!("Running credential process {} failed", exe.display()))?;
+	if !output.status.success() {
+		anyhow::bail!(
+			"Credential process {} failed for registry {}: {}",
+			exe.display(),
+			name,
+			output.status
+		)
+	}
+	let token = String::from_utf8(output.stdout)
+		.context("Credential process returned invalid UTF-8")?
+		.lines()
+		.next()
+		.map(|l| l.trim().to_owned())
+		.filter(|l| !l.is_empty());
+	if token.is_none() {
+		anyhow::bail!("Credential process {} didn't return a token for {}", exe.display(), name)
+	}
+	Ok(token)
+}
+
+/// Group `packages`, given in release order, by their depth in the dependency graph among them,
+/// as indices into `packages`: the ones of each level only depend on the ones of earlier levels.
+pub fn dependency_levels(packages: &[Package]) -> Vec<Vec<usize>> {
+	let mut depths = HashMap::new();
+	let mut levels: Vec<Vec<usize>> = Vec::new();
+	for (idx, pkg) in packages.iter().enumerate() {
+		let depth = pkg
+			.dependencies()
+			.iter()
+			.filter_map(|d| depths.get(&d.package_name()))
+			.map(|depth| depth + 1)
+			.max()
+			.unwrap_or(0);
+		depths.insert(pkg.name(), depth);
+		if levels.len() <= depth {
+			levels.resize_with(depth + 1, Vec::new);
+		}
+		levels[depth].push(idx);
+	}
+	levels
+}
+
+/// Prepare a `Config` set up like `c` to be created on another thread, as these can't be
+/// shared between threads. It reads the same configuration and credentials, and the settings
+/// of the calling thread (see `ThreadSettings`) are applied to the thread creating it.
+pub fn config_like(c: &Config) -> impl FnOnce() -> Result<Config, anyhow::Error> + Send {
+	let verbosity = c.shell().verbosity();
+	let (frozen, locked, offline) = (c.frozen(), c.locked(), c.offline());
+	let (cwd, home) = (c.cwd().to_path_buf(), c.home().clone().into_path_unlocked());
+	let nightly_features_allowed = c.nightly_features_allowed;
+	let settings = ThreadSettings::current();
+	move || {
+		settings.apply();
+		let mut c = Config::new(Shell::new(), cwd, home);
+		// `[unstable]` of the configuration is only read if allowed
+		c.nightly_features_allowed = nightly_features_allowed;
+		let (verbose, quiet) = (verbosity == Verbosity::Verbose, verbosity == Verbosity::Quiet);
+		c.configure(verbose as u32, quiet, None, frozen, locked, offline, &None, &[], &[])?;
+		c.values()?;
+		c.load_credentials()?;
+		Ok(c)
+	}
+}
+
+/// The per-thread settings of `set_quiet`, `set_dependency_depth` and
+/// `disable_registry_cache`, to carry them over to another thread
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ThreadSettings {
+	quiet: bool,
+	dependency_depth: Option<usize>,
+	registry_cache: bool,
+}
+
+impl ThreadSettings {
+	/// The settings of the calling thread
+	pub fn current() -> Self {
+		ThreadSettings {
+			quiet: QUIET.with(|q| q.get()),
+			dependency_depth: DEPENDENCY_DEPTH.with(|d| d.get()),
+			registry_cache: PUBLISHED.with(|p| p.borrow().is_some()),
+		}
+	}
+
+	/// Make these the settings of the calling thread. The versions it cached so far are kept,
+	/// unless the registry cache gets disabled.
+	pub fn apply(self) {
+		set_quiet(self.quiet);
+		set_dependency_depth(self.dependency_depth);
+		match (self.registry_cache, PUBLISHED.with(|p| p.borrow().is_some())) {
+			(false, true) => disable_registry_cache(),
+			(true, false) => PUBLISHED.with(|p| *p.borrow_mut() = Some(HashMap::new())),
+			_ => {},
+		}
+	}
+}
+
+/// Render rows as a plain text table with aligned columns, `header` first
+pub fn render_table<const N: usize>(header: [&str; N], rows: &[[String; N]]) -> String {
+	let header = header.map(|h| h.to_owned());
+	let mut widths = [0; N];
+	for row in [&header].into_iter().chain(rows.iter()) {
+		for (w, cell) in widths.iter_mut().zip(row.iter()) {
+			*w = (*w).max(cell.chars().count());
+		}
+	}
+	[&header]
+		.into_iter()
+		.chain(rows.iter())
+		.map(|row| {
+			row.iter()
+				.zip(widths.iter())
+				.map(|(cell, w)| format!("{:w$}", cell, w = w))
+				.collect::<Vec<_>>()
+				.join("  ")
+				.trim_end()
+				.to_owned()
+		})
+		.collect::<Vec<_>>()
+		.join("\n")
+}
+
+/// Find the shortest cycle through the strongly connected component `scc` of `graph`,
+/// starting and ending at its lowest node and only following edges `follow` accepts.
+/// Returns the edges along the way, empty if there is no such cycle.
+pub fn recreate_cycle<N, E, F>(
+	graph: &Graph<N, E, Directed, u32>,
+	scc: &[NodeIndex],
+	follow: F,
+) -> Vec<EdgeIndex>
+where
+	F: Fn(&E) -> bool,
+{
+	let start = match scc.iter().min() {
+		Some(s) => *s,
+		None => return vec![],
+	};
+	let members = scc.iter().collect::<HashSet<_>>();
+
+	// breadth first, remembering the edge we first reached each node by
+	let mut reached_by = HashMap::new();
+	let mut queue = VecDeque::from([start]);
+	while let Some(node) = queue.pop_front() {
+		for edge in graph.edges(node) {
+			if !follow(edge.weight()) || !members.contains(&edge.target()) {
+				continue
+			}
+			if edge.target() == start {
+				let mut path = vec![edge.id()];
+				let mut current = node;
+				while current != start {
+					let id = reached_by[&current];
+					path.push(id);
+					current = graph.edge_endpoints(id).expect("Edge was just visited. qed").0;
 				}
+				path.reverse();
+				return path
+			}
+			if let Entry::Vacant(e) = reached_by.entry(edge.target()) {
+				e.insert(edge.id());
+				queue.push_back(edge.target());
 			}
 		}
 	}
-	total_list
+	vec![]
+}
+
+/// Read the workspace of `root_manifest` (again). A `Workspace` doesn't see changes made to the
+/// manifests after it was read, so it has to be reloaded after every command that edits them,
+/// before anything else looks at the packages.
+pub fn reload_workspace<'c>(
+	root_manifest: &Path,
+	c: &'c Config,
+) -> Result<Workspace<'c>, anyhow::Error> {
+	Workspace::new(root_manifest, c).context("Reading workspace failed")
+}
+
+thread_local! {
+	static DEPENDENCY_DEPTH: Cell<Option<usize>> = Cell::new(None);
 }
 
-/// Run f on every package's manifest, write the doc. Fail on first error
+/// Only follow path dependencies outside of the workspace this many levels deep in
+/// `members_deep`, the ones of members being the first. Unlimited if `None`. Applies to the
+/// calling thread only.
+pub fn set_dependency_depth(depth: Option<usize>) {
+	DEPENDENCY_DEPTH.with(|d| d.set(depth));
+}
+
+/// The members of `ws` and the packages outside of it they depend on by path, directly or
+/// through other such packages, up to the depth given to `set_dependency_depth`. Path
+/// dependencies which can't be read are left out with a warning.
+///
+/// Follows `set_dependency_depth` and `metrics::enable` as set on the calling thread.
+pub fn members_deep(ws: &'_ Workspace) -> Result<Vec<Package>, anyhow::Error> {
+	let max_depth = DEPENDENCY_DEPTH.with(|d| d.get());
+	metrics::time(
+		"member discovery",
+		None,
+		|l: &Result<Vec<_>, _>| l.as_ref().map(|l| l.len()).unwrap_or_default(),
+		|| {
+			let mut total_list = ws.members().cloned().collect::<Vec<_>>();
+			// path dependencies may be shared by several packages, or even depend on each other
+			let mut seen = total_list.iter().map(|p| p.package_id()).collect::<HashSet<_>>();
+			let mut queue = total_list.iter().map(|p| (p.clone(), 0)).collect::<VecDeque<_>>();
+			while let Some((pkg, depth)) = queue.pop_front() {
+				if max_depth.map(|max| depth >= max).unwrap_or(false) {
+					continue
+				}
+				for dep in pkg.dependencies() {
+					let source = dep.source_id();
+					if !source.is_path() {
+						continue
+					}
+					let found = source
+						.url()
+						.to_file_path()
+						.map_err(|()| anyhow::anyhow!("{} isn't a local path", source.url()))
+						.and_then(|dst| {
+							PathSource::new(&dst, source, ws.config())
+								.root_package()
+								.with_context(|| format!("No package found at {}", dst.display()))
+						});
+					match found {
+						Ok(found) =>
+							if !ws.is_member(&found) && seen.insert(found.package_id()) {
+								total_list.push(found.clone());
+								queue.push_back((found, depth + 1));
+							},
+						Err(e) => ws.config().shell().warn(format!(
+							"Skipping path dependency {} of {}: {:#}",
+							dep.package_name(),
+							pkg.name(),
+							e
+						))?,
+					}
+				}
+			}
+			Ok(total_list)
+		},
+	)
+}
+
+/// Run f on every package's manifest, write the doc if it changed. Fail on first error
+///
+/// Follows `dry_run::enable` as set on the calling thread.
 pub fn edit_each<'a, I, F, R>(iter: I, f: F) -> Result<Vec<R>, anyhow::Error>
 where
 	F: Fn(&'a Package, &mut Document) -> Result<R, anyhow::Error>,
@@ -87,14 +651,122 @@ where
 	let mut results = Vec::new();
 	for pkg in iter {
 		let manifest_path = pkg.manifest_path();
-		let content = fs::read_to_string(manifest_path)?;
+		let content = dry_run::read(manifest_path)?;
 		let mut doc: Document = content.parse()?;
 		results.push(f(pkg, &mut doc)?);
-		fs::write(manifest_path, doc.to_string())?;
+		let updated = doc.to_string();
+		// leave the mtime alone if nothing changed, `check` fingerprints by it
+		if updated != content {
+			dry_run::write(manifest_path, updated)?;
+		}
 	}
 	Ok(results)
 }
 
+/// The original content and mtime of package manifests, written back when dropped
+pub struct ManifestBackup {
+	originals: HashMap<PathBuf, (String, FileTime)>,
+}
+
+impl ManifestBackup {
+	/// Keep the manifests of all packages of `iter` as they are now
+	pub fn snapshot<'a, I>(iter: I) -> Result<Self, anyhow::Error>
+	where
+		I: Iterator<Item = &'a Package>,
+	{
+		let mut originals = HashMap::new();
+		for pkg in iter {
+			let path = pkg.manifest_path();
+			originals.insert(path.to_path_buf(), (fs::read_to_string(path)?, paths::mtime(path)?));
+		}
+		Ok(ManifestBackup { originals })
+	}
+
+	/// Set the mtime of the changed manifests back to the original one, so the fingerprints
+	/// `check` keeps stay the same across runs patching the manifests the same way
+	pub fn reset_mtimes(&self) {
+		for (path, (_, mtime)) in self.originals.iter() {
+			paths::set_file_time_no_err(path, *mtime);
+		}
+	}
+
+	/// Don't restore the manifests, leave them as they are
+	pub fn keep_changes(mut self) {
+		self.originals.clear();
+	}
+}
+
+impl Drop for ManifestBackup {
+	fn drop(&mut self) {
+		for (path, (content, mtime)) in self.originals.drain() {
+			if fs::read_to_string(&path).map(|c| c == content).unwrap_or(false) {
+				continue
+			}
+			if let Err(e) = fs::write(&path, content) {
+				error!("Restoring {} failed: {}", path.display(), e);
+			} else {
+				paths::set_file_time_no_err(&path, mtime);
+			}
+		}
+	}
+}
+
+thread_local! {
+	static QUIET: Cell<bool> = Cell::new(false);
+}
+
+/// Leave out the status line for each package in favour of a counter, see `PackageProgress`.
+/// Applies to the calling thread only.
+pub fn set_quiet(quiet: bool) {
+	QUIET.with(|q| q.set(quiet));
+}
+
+/// Report going through `total` packages: a status line per package or, if `set_quiet`, a
+/// counter updated in place
+pub struct PackageProgress<'cfg> {
+	config: &'cfg Config,
+	verb: &'static str,
+	total: usize,
+	progress: Option<RefCell<(Progress<'cfg>, usize)>>,
+}
+
+impl<'cfg> PackageProgress<'cfg> {
+	/// Follows `set_quiet` as set on the calling thread.
+	pub fn new(config: &'cfg Config, verb: &'static str, total: usize) -> Self {
+		let progress = if QUIET.with(|q| q.get()) {
+			Some(RefCell::new((Progress::with_style(verb, ProgressStyle::Ratio, config), 0)))
+		} else {
+			None
+		};
+		PackageProgress { config, verb, total, progress }
+	}
+
+	/// Going on with `pkg`
+	pub fn tick(&self, pkg: &Package) -> Result<(), anyhow::Error> {
+		match &self.progress {
+			Some(progress) => {
+				let (progress, done) = &mut *progress.borrow_mut();
+				*done += 1;
+				progress.tick(*done, self.total, &format!(": {}", pkg.name()))?;
+			},
+			None => self.config.shell().status(self.verb, pkg.name())?,
+		}
+		Ok(())
+	}
+
+	/// Details on the current package, left out if quiet
+	pub fn detail<T: std::fmt::Display>(
+		&self,
+		status: &str,
+		message: T,
+	) -> Result<(), anyhow::Error> {
+		if self.progress.is_none() {
+			self.config.shell().status(status, message)?;
+		}
+		Ok(())
+	}
+}
+
 /// Wrap each the different dependency as a mutable item
 pub enum DependencyEntry<'a> {
 	Table(&'a mut Table),
@@ -124,7 +796,7 @@ pub enum DependencySection {
 }
 
 impl DependencySection {
-	fn key(&self) -> &'static str {
+	pub fn key(&self) -> &'static str {
 		match self {
 			DependencySection::Regular => "dependencies",
 			DependencySection::Dev => "dev-dependencies",
@@ -133,6 +805,81 @@ impl DependencySection {
 	}
 }
 
+/// Remove the dependency sections of root, which don't contain any entries (anymore). This
+/// includes the ones within `[target.*]` tables, which are removed themselves if nothing is
+/// left in them. Return how many tables were removed.
+pub fn prune_empty_sections(root: &mut Table) -> u32 {
+	fn prune_deps(table: &mut Table) -> u32 {
+		let mut counter = 0;
+		for case in [DependencySection::Regular, DependencySection::Dev, DependencySection::Build] {
+			let k = case.key();
+			let is_empty = match table.get(k) {
+				Some(Item::Table(t)) => t.is_empty(),
+				Some(Item::Value(Value::InlineTable(t))) => t.is_empty(),
+				_ => false,
+			};
+			if is_empty {
+				table.remove(k);
+				counter += 1;
+			}
+		}
+		counter
+	}
+
+	let mut counter = prune_deps(root);
+
+	if let Some(Item::Table(targets)) = root.get_mut("target") {
+		let keys = targets
+			.iter()
+			.filter_map(|(k, v)| if v.is_table() { Some(k.to_owned()) } else { None })
+			.collect::<Vec<_>>();
+		for k in keys {
+			if let Some(Item::Table(target)) = targets.get_mut(&k) {
+				counter += prune_deps(target);
+				if target.is_empty() {
+					targets.remove(&k);
+					counter += 1;
+				}
+			}
+		}
+		if targets.is_empty() {
+			root.remove("target");
+			counter += 1;
+		}
+	}
+
+	counter
+}
+
+/// Run `f` on `root` and each of its `target` tables, which have dependency sections of their
+/// own. Returns the sum of what `f` returned.
+pub fn with_target_tables<F>(root: &mut Table, mut f: F) -> u32
+where
+	F: FnMut(&mut Table) -> u32,
+{
+	let mut counter = f(root);
+	if let Some(Item::Table(targets)) = root.get_mut("target") {
+		let keys = targets
+			.iter()
+			.filter_map(|(k, v)| if v.is_table() { Some(k.to_owned()) } else { None })
+			.collect::<Vec<_>>();
+		for k in keys {
+			if let Some(Item::Table(target)) = targets.get_mut(&k) {
+				counter += f(target);
+			}
+		}
+	}
+	counter
+}
+
+/// Like `edit_each_dep`, but for the dependency sections of all `target` tables, too
+pub fn edit_each_dep_all<F>(root: &mut Table, f: F) -> u32
+where
+	F: Fn(String, Option<String>, DependencyEntry, DependencySection) -> DependencyAction,
+{
+	with_target_tables(root, |table| edit_each_dep(table, &f))
+}
+
 /// Iterate through the dependency sections of root, find each
 /// dependency entry, that is a subsection and hand it and its name
 /// to f. Return the counter of how many times f returned true.
@@ -241,3 +988,295 @@ where
 	}
 	counter
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn config_like_carries_thread_settings() {
+		let c = Config::default().unwrap();
+		set_quiet(true);
+		set_dependency_depth(Some(1));
+		disable_registry_cache();
+		let settings = ThreadSettings::current();
+		let config = config_like(&c);
+
+		let other = std::thread::spawn(move || {
+			let fresh = ThreadSettings::current();
+			let c = config().unwrap();
+			(fresh, ThreadSettings::current(), c.home().clone().into_path_unlocked())
+		})
+		.join()
+		.unwrap();
+		assert_ne!(other.0, settings);
+		assert_eq!(other.1, settings);
+		assert_eq!(other.2, c.home().clone().into_path_unlocked());
+	}
+
+	#[test]
+	fn levels_of_dependency_graph() {
+		use cargo::ops::read_package;
+		let config = Config::default().unwrap();
+		let root =
+			std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/include-pre");
+		let source = SourceId::for_path(&root).unwrap();
+		let packages = ["leftpad", "unicode-width", "cu-left-pad", "crate-a"]
+			.iter()
+			.map(|name| {
+				read_package(&root.join(name).join("Cargo.toml"), source, &config).unwrap().0
+			})
+			.collect::<Vec<_>>();
+
+		assert_eq!(dependency_levels(&packages), vec![vec![0, 1], vec![2], vec![3]]);
+	}
+
+	#[test]
+	fn shared_path_dependency_once() {
+		let c = Config::default().unwrap();
+		let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("tests/fixtures/shared-path-dep/workspace/Cargo.toml");
+		let ws = reload_workspace(&root, &c).unwrap();
+		let mut names = members_deep(&ws)
+			.unwrap()
+			.iter()
+			.map(|p| p.name().to_string())
+			.collect::<Vec<_>>();
+		names.sort();
+		assert_eq!(names, ["crateA", "crateB", "sidekick"]);
+	}
+
+	#[test]
+	fn nested_path_dependencies() {
+		let c = Config::default().unwrap();
+		let root = Path::new(env!("CARGO_MANIFEST_DIR"))
+			.join("tests/fixtures/nested-path-deps/workspace/Cargo.toml");
+		let ws = reload_workspace(&root, &c).unwrap();
+		let names = |ws: &Workspace<'_>| {
+			members_deep(ws)
+				.unwrap()
+				.iter()
+				.map(|p| p.name().to_string())
+				.collect::<Vec<_>>()
+		};
+		// `level3` depending on `level1` again doesn't go round in circles
+		assert_eq!(names(&ws), ["crateA", "level1", "level2", "level3"]);
+
+		set_dependency_depth(Some(2));
+		assert_eq!(names(&ws), ["crateA", "level1", "level2"]);
+		set_dependency_depth(Some(0));
+		assert_eq!(names(&ws), ["crateA"]);
+	}
+
+	#[test]
+	fn stale_path_dependency_skipped() {
+		use assert_fs::prelude::*;
+		let temp = assert_fs::TempDir::new().unwrap();
+		temp.copy_from("tests/fixtures/stale-path-dep", &["*.toml", "*.rs"]).unwrap();
+		let c = Config::default().unwrap();
+		let ws = reload_workspace(&temp.path().join("workspace/Cargo.toml"), &c).unwrap();
+		// gone after the workspace was read
+		fs::remove_dir_all(temp.path().join("ghost")).unwrap();
+
+		let names = members_deep(&ws)
+			.unwrap()
+			.iter()
+			.map(|p| p.name().to_string())
+			.collect::<Vec<_>>();
+		assert_eq!(names, ["crateA"]);
+	}
+
+	#[test]
+	fn registry_cache() {
+		let c = Config::default().unwrap();
+		let crates_io = SourceId::crates_io(&c).unwrap();
+		let (a, b) = (InternedString::new("a"), InternedString::new("b"));
+		cache_versions(crates_io, a, &[Version::new(1, 0, 0)]);
+		cache_versions(crates_io, b, &[]);
+
+		let (found, missing) = cached_versions(crates_io, [a, b, InternedString::new("c")]);
+		assert_eq!(found.get(&a), Some(&vec![Version::new(1, 0, 0)]));
+		// known to not be published
+		assert!(!found.contains_key(&b));
+		assert_eq!(missing, [InternedString::new("c")]);
+
+		disable_registry_cache();
+		let (found, missing) = cached_versions(crates_io, [a]);
+		assert!(found.is_empty());
+		assert_eq!(missing, [a]);
+	}
+
+	#[test]
+	fn normalized_paths() {
+		assert_eq!(
+			normalize_path(Path::new("/ws/crateA/../shared/./build.rs")),
+			Path::new("/ws/shared/build.rs")
+		);
+		assert_eq!(normalize_path(Path::new("/ws/crateA/src")), Path::new("/ws/crateA/src"));
+	}
+
+	#[test]
+	fn prune_emptied_sections() {
+		let mut doc = r#"
+[package]
+name = "a"
+
+[dependencies]
+b = { path = "../b" }
+
+[dev-dependencies]
+c = { path = "../c" }
+
+[target.'cfg(unix)'.build-dependencies]
+d = { version = "1" }
+"#
+		.parse::<Document>()
+		.unwrap();
+
+		let root = doc.as_table_mut();
+		edit_each_dep(root, |name, _, _, _| {
+			if name == "c" {
+				DependencyAction::Remove
+			} else {
+				DependencyAction::Untouched
+			}
+		});
+		if let Some(Item::Table(targets)) = root.get_mut("target") {
+			if let Some(Item::Table(unix)) = targets.get_mut("cfg(unix)") {
+				edit_each_dep(unix, |_, _, _, _| DependencyAction::Remove);
+			}
+		}
+
+		assert_eq!(prune_empty_sections(root), 4);
+		assert!(root.contains_key("dependencies"));
+		assert!(!root.contains_key("dev-dependencies"));
+		assert!(!root.contains_key("target"));
+		assert!(!doc.to_string().contains("[dev-dependencies]"));
+	}
+
+	#[test]
+	fn edit_dependencies_of_targets() {
+		let mut doc = r#"
+[dependencies]
+a = { version = "1" }
+
+[target.'cfg(unix)'.dev-dependencies]
+b = { version = "1" }
+
+[target.'cfg(windows)'.build-dependencies.c]
+version = "1"
+"#
+		.parse::<Document>()
+		.unwrap();
+
+		let seen = RefCell::new(Vec::new());
+		let count = edit_each_dep_all(doc.as_table_mut(), |name, _, _, section| {
+			seen.borrow_mut().push((name, section.key()));
+			DependencyAction::Mutated
+		});
+		assert_eq!(count, 3);
+		assert_eq!(
+			seen.into_inner(),
+			[
+				("a".to_owned(), "dependencies"),
+				("b".to_owned(), "dev-dependencies"),
+				("c".to_owned(), "build-dependencies")
+			]
+		);
+	}
+
+	#[test]
+	fn recreate_shortest_cycle() {
+		let mut graph = Graph::<&str, bool, Directed, u32>::new();
+		let a = graph.add_node("a");
+		let b = graph.add_node("b");
+		let c = graph.add_node("c");
+		graph.add_edge(a, b, true);
+		graph.add_edge(b, c, true);
+		graph.add_edge(c, a, true);
+		graph.add_edge(b, a, false);
+
+		let names = |path: Vec<EdgeIndex>| {
+			path.iter()
+				.map(|e| graph[graph.edge_endpoints(*e).unwrap().0])
+				.collect::<Vec<_>>()
+		};
+		assert_eq!(names(recreate_cycle(&graph, &[c, b, a], |_| true)), vec!["a", "b"]);
+		assert_eq!(names(recreate_cycle(&graph, &[c, b, a], |e| *e)), vec!["a", "b", "c"]);
+		assert!(recreate_cycle(&graph, &[b, a], |e| *e).is_empty());
+	}
+
+	/// A `Config` with its cargo home in `temp`, holding the given `config.toml` and, if any,
+	/// `credentials.toml`, seeing only the environment `env`
+	fn token_config(
+		temp: &assert_fs::TempDir,
+		config: &str,
+		credentials: Option<&str>,
+		env: &[(&str, &str)],
+	) -> Config {
+		use cargo::core::Shell;
+		fs::write(temp.path().join("config.toml"), config).unwrap();
+		if let Some(credentials) = credentials {
+			fs::write(temp.path().join("credentials.toml"), credentials).unwrap();
+		}
+		let mut c = Config::new(Shell::new(), temp.path().to_owned(), temp.path().to_owned());
+		c.set_env(env.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+		// `credential-process` is unstable
+		c.nightly_features_allowed = true;
+		c.configure(
+			0,
+			true,
+			None,
+			false,
+			false,
+			true,
+			&None,
+			&["credential-process".to_owned()],
+			&[],
+		)
+		.unwrap();
+		c.load_credentials().unwrap();
+		c
+	}
+
+	#[test]
+	fn token_source_order() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		let token = |config: &str, credentials: Option<&str>, env: &[(&str, &str)]| {
+			resolve_token(&token_config(&temp, config, credentials, env), None).unwrap()
+		};
+		let config = "[registry]\ntoken = \"from-config\"\n";
+		let credentials = Some("[registry]\ntoken = \"from-credentials\"\n");
+
+		assert_eq!(token("", None, &[]), None);
+		assert_eq!(token(config, None, &[]).as_deref(), Some("from-config"));
+		// as stored by `cargo login`, over the config
+		assert_eq!(token(config, credentials, &[]).as_deref(), Some("from-credentials"));
+		// the environment over both
+		assert_eq!(
+			token(config, credentials, &[("CARGO_REGISTRY_TOKEN", "from-env")]).as_deref(),
+			Some("from-env")
+		);
+		// only asked if no token is configured at all
+		fs::remove_file(temp.path().join("credentials.toml")).unwrap();
+		let process = "[registry]\ncredential-process = \"echo from-{name}\"\n";
+		assert_eq!(token(process, None, &[]).as_deref(), Some("from-crates-io"));
+	}
+
+	#[test]
+	fn token_of_named_registry() {
+		let temp = assert_fs::TempDir::new().unwrap();
+		let c = token_config(
+			&temp,
+			"[registry]\ncredential-process = \"echo from-{name}\"\n",
+			Some("[registries.mine]\ntoken = \"from-credentials\"\n"),
+			&[("CARGO_REGISTRIES_OTHER_TOKEN", "from-env")],
+		);
+
+		// a token for the registry is more specific than the global credential process
+		assert_eq!(resolve_token(&c, Some("mine")).unwrap().as_deref(), Some("from-credentials"));
+		assert_eq!(resolve_token(&c, Some("other")).unwrap().as_deref(), Some("from-env"));
+		assert_eq!(resolve_token(&c, Some("third")).unwrap().as_deref(), Some("from-third"));
+		assert_eq!(resolve_token(&c, None).unwrap().as_deref(), Some("from-crates-io"));
+	}
+}
@@ -0,0 +1,183 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use std::{
+	io::{BufRead, BufReader, Read, Write},
+	net::TcpListener,
+	process::Command,
+	sync::{Arc, Mutex},
+	thread,
+};
+
+mod common;
+
+/// A registry API answering the owner requests for crates owned by `owners`, recording each
+/// request as `METHOD path body`
+struct Registry {
+	api: String,
+	requests: Arc<Mutex<Vec<String>>>,
+}
+
+impl Registry {
+	fn start(owners: &[&str]) -> Self {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let api = format!("http://{}", listener.local_addr().unwrap());
+		let requests = Arc::new(Mutex::new(Vec::new()));
+		let owners = Arc::new(Mutex::new(owners.iter().map(|o| o.to_string()).collect()));
+
+		let recorded = requests.clone();
+		thread::spawn(move || {
+			for stream in listener.incoming() {
+				let mut stream = stream.unwrap();
+				let request = read_request(&mut stream);
+				let response = respond(&request, &owners);
+				recorded.lock().unwrap().push(request);
+				write!(
+					stream,
+					"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+					 Connection: close\r\n\r\n{}",
+					response.len(),
+					response
+				)
+				.unwrap();
+			}
+		});
+		Registry { api, requests }
+	}
+
+	fn requests(&self) -> Vec<String> {
+		self.requests.lock().unwrap().clone()
+	}
+}
+
+fn read_request(stream: &mut std::net::TcpStream) -> String {
+	let mut reader = BufReader::new(stream);
+	let mut request_line = String::new();
+	reader.read_line(&mut request_line).unwrap();
+	let mut length = 0;
+	loop {
+		let mut header = String::new();
+		reader.read_line(&mut header).unwrap();
+		if header.trim().is_empty() {
+			break
+		}
+		if let Some((name, value)) = header.split_once(':') {
+			if name.eq_ignore_ascii_case("content-length") {
+				length = value.trim().parse().unwrap();
+			}
+		}
+	}
+	let mut body = vec![0; length];
+	reader.read_exact(&mut body).unwrap();
+	let mut parts = request_line.split_whitespace();
+	let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+	format!("{} {} {}", method, path, String::from_utf8(body).unwrap())
+		.trim_end()
+		.to_owned()
+}
+
+fn respond(request: &str, owners: &Mutex<Vec<String>>) -> String {
+	let mut owners = owners.lock().unwrap();
+	if request.starts_with("GET ") {
+		let users = owners
+			.iter()
+			.enumerate()
+			.map(|(id, login)| format!(r#"{{"id": {}, "login": "{}"}}"#, id, login))
+			.collect::<Vec<_>>();
+		return format!(r#"{{"users": [{}]}}"#, users.join(", "))
+	}
+
+	let body: serde_json::Value =
+		serde_json::from_str(request.splitn(3, ' ').nth(2).unwrap()).unwrap();
+	let login = body["users"][0].as_str().unwrap().to_owned();
+	if login == "mallory" {
+		return r#"{"errors": [{"detail": "must be logged in to perform that action"}]}"#.to_owned()
+	}
+	match owners.iter().position(|o| o == &login) {
+		Some(idx) => {
+			owners.remove(idx);
+			r#"{"ok": true, "msg": "owners successfully removed"}"#.to_owned()
+		},
+		None => format!(
+			r#"{{"errors": [{{"detail": "could not find owner with login `{}`"}}]}}"#,
+			login
+		),
+	}
+}
+
+/// A copy of the `simple-base` fixture and a git index of the registry `test`, pointing to the
+/// API at `api`
+fn workspace_with_registry(api: &str) -> Result<assert_fs::TempDir, Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let index = temp.child("index");
+	index.create_dir_all()?;
+	index
+		.child("config.json")
+		.write_str(&format!(r#"{{"dl": "{0}/dl", "api": "{0}"}}"#, api))?;
+	common::commit_all(index.path(), "index", &[])?;
+	Ok(temp)
+}
+
+fn add_owner(temp: &assert_fs::TempDir) -> Result<Command, Box<dyn std::error::Error>> {
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.env("CARGO_HOME", temp.path().join("cargo-home"))
+		.env(
+			"CARGO_REGISTRIES_TEST_INDEX",
+			format!("file://{}", temp.path().join("index").display()),
+		)
+		.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("add-owner")
+		.args(["--packages", "crateA", "--registry", "test", "--token", "secret"]);
+	Ok(cmd)
+}
+
+#[test]
+fn remove_and_list_owners() -> Result<(), Box<dyn std::error::Error>> {
+	let registry = Registry::start(&["alice", "bob"]);
+	let temp = workspace_with_registry(&registry.api)?;
+
+	add_owner(&temp)?
+		.args(["--remove", "bob", "--remove", "carol", "--list"])
+		.assert()
+		.success()
+		// not being an owner is only reported
+		.stderr(predicate::str::contains("carol is not an owner of crateA"))
+		// listed after the removal
+		.stdout(predicate::eq("alice\n"));
+
+	assert_eq!(
+		registry.requests(),
+		[
+			r#"DELETE /api/v1/crates/crateA/owners {"users":["bob"]}"#,
+			r#"DELETE /api/v1/crates/crateA/owners {"users":["carol"]}"#,
+			"GET /api/v1/crates/crateA/owners",
+		]
+	);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn remove_owner_fails_on_other_errors() -> Result<(), Box<dyn std::error::Error>> {
+	let registry = Registry::start(&["alice"]);
+	let temp = workspace_with_registry(&registry.api)?;
+
+	add_owner(&temp)?
+		.args(["--remove", "mallory", "--list"])
+		.assert()
+		.failure()
+		.stderr(predicate::str::contains("must be logged in to perform that action"));
+
+	// nothing listed after the failure
+	assert_eq!(
+		registry.requests(),
+		[r#"DELETE /api/v1/crates/crateA/owners {"users":["mallory"]}"#]
+	);
+
+	temp.close()?;
+	Ok(())
+}
@@ -1,5 +1,6 @@
 use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
+use predicates::prelude::*;
 use std::process::Command;
 
 #[test]
@@ -19,3 +20,242 @@ fn check_include_pre() -> Result<(), Box<dyn std::error::Error>> {
 	temp.close()?;
 	Ok(())
 }
+
+#[test]
+fn check_metadata_only() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("check")
+		.arg("--packages")
+		.arg("crate_a")
+		.arg("--include-pre-deps")
+		.arg("--metadata-only");
+	cmd.assert().success().stderr(predicates::str::contains("(metadata only)"));
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn check_in_parallel() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/parallel-check", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	// crateA and crateB are verified together, crateC needs both of them injected
+	cmd.arg("--manifest-path").arg(temp.path()).arg("check").arg("--jobs").arg("2");
+	cmd.assert().success().stderr(predicates::str::contains("Verfying crateC"));
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn check_skips_fresh() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+
+	let check = |extra: &[&str]| -> Result<_, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("check")
+			.arg("--packages")
+			.arg("crate_a")
+			.arg("--include-pre-deps")
+			.args(extra);
+		Ok(cmd.assert().success())
+	};
+
+	check(&[])?.stderr(predicates::str::contains("Verfying cu-left-pad"));
+	check(&[])?.stderr(predicates::str::contains("Fresh cu-left-pad"));
+	check(&["--no-cache"])?.stderr(predicates::str::contains("Verfying cu-left-pad"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn check_reports_failing_crate() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+	temp.child("cu-left-pad/src/lib.rs")
+		.write_str("pub fn broken() -> u32 { \"x\" }\n")?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("check")
+		.arg("--packages")
+		.arg("crate_a")
+		.arg("--include-pre-deps");
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("Verifying cu-left-pad failed, first error in"))
+		.stderr(predicates::str::contains("error[E0308]: mismatched types"));
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn check_keep_going() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+	temp.child("unicode-width/src/lib.rs")
+		.write_str("pub fn broken() -> u32 { \"x\" }\n")?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("check")
+		.arg("--packages")
+		.arg("crate_a")
+		.arg("--include-pre-deps")
+		.arg("--keep-going");
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("Verifying unicode-width failed"))
+		// not verified against the published one instead
+		.stderr(predicates::str::contains("Skipping cu-left-pad"))
+		.stderr(predicates::str::contains("(dependency failed)"))
+		.stderr(predicates::str::contains("Verifying cu-left-pad failed").not())
+		.stderr(predicates::str::contains("Verifying failed with 1 errors"));
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn validate_reports_all() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).arg("validate");
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("crateA: Bad metadata"))
+		.stderr(predicates::str::contains("crateC: Bad metadata"))
+		.stderr(predicates::str::contains("failed with 3 errors"));
+	temp.close()?;
+
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("validate")
+		.arg("--packages")
+		.arg("crate_a");
+	cmd.assert().success();
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn path_dependency_without_version() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/path-no-version", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).arg("validate");
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains(
+			"crateA: has dependencies defined as path without a version: crateB",
+		))
+		.stderr(predicates::str::contains("crateB:").not())
+		.stderr(predicates::str::contains("crateC:").not());
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dependency_source_audit() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dependency-sources", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).args([
+		"check",
+		"--dependency-source-audit",
+		"--include-dev-deps",
+	]);
+	cmd.assert()
+		.failure()
+		.stdout(predicates::str::contains(
+			"crateA  crateB      path (Normal)       ERROR: cargo refuses to publish without a version",
+		))
+		.stdout(predicates::str::contains(
+			"crateB  crateD      path (Development)  dropped: dev-dependency without version",
+		))
+		.stdout(predicates::str::contains(
+			"crateC  crateB      path (Normal)       released alongside, published as ^0.1.0",
+		))
+		.stdout(predicates::str::contains(
+			"crateC  leftpad     git (Normal)        git stripped, must be on the registry as ^0.2",
+		))
+		// only the path dependency without a version is blocking
+		.stderr(predicates::str::contains(
+			"1 dependencies would keep their crate from being published",
+		));
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dependency_source_audit_passes() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dependency-sources", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).args([
+		"check",
+		"--dependency-source-audit",
+		"--include-dev-deps",
+		"--metadata-only",
+		"--packages",
+		"crateB",
+		"crateD",
+	]);
+	cmd.assert()
+		.success()
+		.stdout(predicates::str::contains("crate   dependency  source              on publish"))
+		.stdout(predicates::str::contains("dropped: dev-dependency without version"))
+		.stdout(predicates::str::contains("ERROR").not());
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn check_custom_target_dir() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/include-pre", &["*.toml", "*.rs"])?;
+	let verify_target = temp.path().join("verify-target");
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.args(["check", "--packages", "crate_a", "--include-pre-deps", "--target-dir"])
+		.arg(&verify_target);
+	cmd.assert().success();
+
+	assert!(verify_target.join("unleash-check-fingerprints.json").exists());
+	assert!(verify_target.join("package").join("cu-left-pad-1.0.0-dev.crate").exists());
+	// the target directory of the workspace isn't used
+	assert!(!temp.path().join("target").join("package").exists());
+
+	temp.close()?;
+	Ok(())
+}
@@ -0,0 +1,29 @@
+//! Helpers shared by the integration tests
+
+use std::path::Path;
+
+/// Commit all files in `path` but `untracked` with `message`, on top of `HEAD` if any. Creates
+/// the git repository first if there is none, with a user to commit as.
+pub fn commit_all(
+	path: &Path,
+	message: &str,
+	untracked: &[&str],
+) -> Result<git2::Repository, Box<dyn std::error::Error>> {
+	let repo = git2::Repository::open(path).or_else(|_| git2::Repository::init(path))?;
+	let mut config = repo.config()?;
+	config.set_str("user.name", "unleash")?;
+	config.set_str("user.email", "unleash@example.com")?;
+	{
+		let sig = repo.signature()?;
+		let mut index = repo.index()?;
+		index.add_all(["*"], git2::IndexAddOption::DEFAULT, None)?;
+		for file in untracked {
+			index.remove_path(Path::new(file))?;
+		}
+		index.write()?;
+		let tree = repo.find_tree(index.write_tree()?)?;
+		let parent = repo.head().ok().and_then(|h| h.peel_to_commit().ok());
+		repo.commit(Some("HEAD"), &sig, &sig, message, &tree, &parent.iter().collect::<Vec<_>>())?;
+	}
+	Ok(repo)
+}
@@ -0,0 +1,132 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use std::process::Command;
+
+mod common;
+
+#[test]
+fn keep_dev_dependency() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+
+	let read = || -> Result<toml::Value, Box<dyn std::error::Error>> {
+		let content = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+		Ok(toml::from_str(&content)?)
+	};
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("de-dev-deps")
+		.arg("--keep-dev-dep")
+		.arg("crateC");
+	cmd.assert().success();
+
+	let dev_deps = read()?["dev-dependencies"].clone();
+	assert!(dev_deps.get("crateB").is_none());
+	assert!(dev_deps.get("crateC").is_some());
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).arg("de-dev-deps");
+	cmd.assert().success();
+
+	// nothing kept, the section is gone
+	assert!(read()?.get("dev-dependencies").is_none());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn restored_after_to_release() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+	let manifest = temp.path().join("crateA").join("Cargo.toml");
+	let before = std::fs::read_to_string(&manifest)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).arg("to-release");
+	cmd.assert().success();
+
+	assert_eq!(std::fs::read_to_string(&manifest)?, before);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn revert_dev_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+	common::commit_all(temp.path(), "initial", &[])?;
+	let manifest = temp.path().join("crateA").join("Cargo.toml");
+	let read = || -> Result<toml::Value, Box<dyn std::error::Error>> {
+		Ok(toml::from_str(&std::fs::read_to_string(&manifest)?)?)
+	};
+
+	let de_dev_deps = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path()).arg("de-dev-deps").args(args);
+		Ok(cmd)
+	};
+
+	de_dev_deps(&[])?.assert().success();
+	assert!(read()?.get("dev-dependencies").is_none());
+	// an intentional change after removing them
+	let changed = std::fs::read_to_string(&manifest)?.replace("0.1.0", "0.2.0");
+	std::fs::write(&manifest, changed)?;
+
+	de_dev_deps(&["--revert"])?.assert().success();
+	let restored = read()?;
+	assert_eq!(restored["package"]["version"].as_str(), Some("0.2.0"));
+	let dev_deps = restored["dev-dependencies"].clone();
+	assert!(dev_deps.get("crateB").is_some());
+	assert!(dev_deps.get("crateC").is_some());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn revert_untracked_manifest() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+	common::commit_all(temp.path(), "initial", &["crateC/Cargo.toml"])?;
+	Command::cargo_bin("cargo-unleash")?
+		.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("de-dev-deps")
+		.assert()
+		.success();
+	let before = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).args(["de-dev-deps", "--revert"]);
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("crateC/Cargo.toml isn't tracked in git"));
+	// crateA wasn't restored either
+	assert_eq!(std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?, before);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn revert_conflicts_with_keeping() -> Result<(), Box<dyn std::error::Error>> {
+	for args in [&["--keep-dev-dep", "crateB"][..], &["--prune-empty"]] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg("tests/fixtures/dev-deps")
+			.args(["de-dev-deps", "--revert"])
+			.args(args);
+		cmd.assert()
+			.failure()
+			.stderr(predicates::str::contains(format!("The argument '{}", args[0])))
+			.stderr(predicates::str::contains("cannot be used with '--revert'"));
+	}
+	Ok(())
+}
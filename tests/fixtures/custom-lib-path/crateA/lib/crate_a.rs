@@ -0,0 +1 @@
+//! Crate A lives in a custom library path
@@ -0,0 +1,2 @@
+//! The first binary
+fn main() {}
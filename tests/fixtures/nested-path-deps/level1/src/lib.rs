@@ -0,0 +1,3 @@
+pub fn level1() {
+	level2::level2()
+}
@@ -0,0 +1,3 @@
+pub fn level2() {
+	level3::level3()
+}
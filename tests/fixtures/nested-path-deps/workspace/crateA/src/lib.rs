@@ -0,0 +1 @@
+pub use level1::level1;
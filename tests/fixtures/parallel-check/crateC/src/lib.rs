@@ -0,0 +1,4 @@
+pub fn run() {
+	crateA::run();
+	crateB::run();
+}
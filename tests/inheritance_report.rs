@@ -0,0 +1,18 @@
+use assert_cmd::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+#[test]
+fn reports_inherited_fields() -> Result<(), Box<dyn std::error::Error>> {
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg("tests/fixtures/workspace-inheritance")
+		.arg("workspace-inheritance-report");
+	cmd.assert()
+		.success()
+		.stdout(predicate::str::contains("crateA  inherited  inherited  local      local    2/3"))
+		.stdout(predicate::str::contains("crateB  inherited  inherited  inherited  -        1/1"));
+
+	Ok(())
+}
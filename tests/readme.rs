@@ -0,0 +1,145 @@
+#![cfg(feature = "gen-readme")]
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use std::process::Command;
+
+#[test]
+fn check_readme_only() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA/src/main.rs")
+		.write_str("//! Crate A, see [`Thing`](struct.Thing.html)\nfn main() {}\n")?;
+
+	let gen_readme = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path()).arg("gen-readme").args(args);
+		Ok(cmd)
+	};
+
+	gen_readme(&["--check"])?
+		.assert()
+		.failure()
+		.stdout(predicates::str::contains("crateA  Missing"))
+		.stderr(predicates::str::contains("3 Readme files need to be generated"));
+	// nothing was written
+	assert!(!temp.path().join("crateA/README.md").exists());
+
+	gen_readme(&["--readme-mode", "overwrite", "--packages", "crateA"])?
+		.assert()
+		.success();
+	gen_readme(&["--check", "--packages", "crateA"])?
+		.assert()
+		.success()
+		.stdout(predicates::str::contains("crateA  Up-to-date"));
+
+	// only the final newline differs
+	let readme = std::fs::read_to_string(temp.path().join("crateA/README.md"))?;
+	temp.child("crateA/README.md").write_str(readme.trim_end())?;
+	gen_readme(&["--check", "--packages", "crateA"])?
+		.assert()
+		.success()
+		.stdout(predicates::str::contains("crateA  Up-to-date"));
+
+	temp.child("crateA/README.md").write_str("outdated\n")?;
+	gen_readme(&["--check", "--packages", "crateA"])?
+		.assert()
+		.failure()
+		.stdout(predicates::str::contains("crateA  Update needed"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dry_run_leaves_readme_unwritten() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA/src/main.rs").write_str("//! Crate A\nfn main() {}\n")?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.current_dir(temp.path()).args([
+		"--dry-run",
+		"gen-readme",
+		"--readme-mode",
+		"overwrite",
+		"--packages",
+		"crateA",
+	]);
+	cmd.assert()
+		.success()
+		.stdout(predicates::str::contains("--- crateA/README.md\n+++ crateA/README.md\n"))
+		.stdout(predicates::str::contains("+Crate A\n"))
+		.stdout(predicates::str::contains("+readme = \"README.md\""));
+
+	assert!(!temp.path().join("crateA/README.md").exists());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn entrypoint_from_manifest_targets() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/custom-lib-path", &["*.toml", "*.rs"])?;
+
+	let gen_readme = |package: &str| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path()).args([
+			"gen-readme",
+			"--readme-mode",
+			"overwrite",
+			"--packages",
+			package,
+		]);
+		Ok(cmd)
+	};
+
+	gen_readme("crateA")?.assert().success();
+	temp.child("crateA/README.md")
+		.assert(predicates::str::contains("Crate A lives in a custom library path"));
+
+	gen_readme("crateB")?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains("Ambiguous entrypoint"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn readme_template_override() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA/src/main.rs").write_str("//! Crate A\nfn main() {}\n")?;
+	temp.child("README.tpl").write_str("# Discovered\n\n{{readme}}\n")?;
+	temp.child("templates/custom.tpl")
+		.write_str("# Custom {{crate}}\n\n{{readme}}\n")?;
+
+	let gen_readme = |template: &str| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path()).args([
+			"gen-readme",
+			"--readme-mode",
+			"overwrite",
+			"--packages",
+			"crateA",
+			"--readme-template",
+		]);
+		cmd.arg(temp.path().join(template));
+		Ok(cmd)
+	};
+
+	gen_readme("templates/custom.tpl")?.assert().success();
+	temp.child("crateA/README.md")
+		.assert(predicates::str::starts_with("# Custom crateA\n\nCrate A"));
+
+	gen_readme("templates/missing.tpl")?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains("Readme template not found at"));
+
+	temp.close()?;
+	Ok(())
+}
@@ -0,0 +1,107 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use std::process::Command;
+
+fn crate_b_dependency(
+	temp: &assert_fs::TempDir,
+) -> Result<toml::Value, Box<dyn std::error::Error>> {
+	let content = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+	let manifest: toml::Value = toml::from_str(&content)?;
+	Ok(manifest["dependencies"]["crateB"].clone())
+}
+
+#[test]
+fn rename_keeps_git_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/rename-git", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("rename")
+		.arg("crateB")
+		.arg("crateX");
+	cmd.assert().success();
+
+	assert!(crate_b_dependency(&temp)?.get("package").is_none());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn rename_include_non_path() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/rename-git", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("rename")
+		.arg("crateB")
+		.arg("crateX")
+		.arg("--include-non-path");
+	cmd.assert().success();
+
+	let dep = crate_b_dependency(&temp)?;
+	assert_eq!(dep["package"], toml::Value::String("crateX".into()));
+	assert_eq!(dep["git"], toml::Value::String("https://github.com/paritytech/crateB".into()));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn rename_include_plain_requirements() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/rename-registry", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("rename")
+		.arg("crateB")
+		.arg("crateX")
+		.arg("--include-non-path");
+	cmd.assert()
+		.success()
+		.stderr(predicates::str::contains("2 dependencies updated in total"));
+
+	let content = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+	assert!(content
+		.contains("crateB = { version = \"0.2\", package = \"crateX\" } # from the registry\n"));
+	let manifest: toml::Value = toml::from_str(&content)?;
+	let dev_dep = &manifest["target"]["cfg(unix)"]["dev-dependencies"]["crateB"];
+	assert_eq!(dev_dep["version"].as_str(), Some("0.2"));
+	assert_eq!(dev_dep["package"].as_str(), Some("crateX"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn rename_target_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/target-deps", &["*.toml", "*.rs"])?;
+
+	for (old, new) in [("crateB", "crateX"), ("crateC", "crateY")] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+		cmd.arg("--manifest-path").arg(temp.path()).arg("rename").arg(old).arg(new);
+		cmd.assert().success();
+	}
+
+	let content = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+	let manifest: toml::Value = toml::from_str(&content)?;
+	let unix = &manifest["target"]["cfg(unix)"];
+	assert_eq!(unix["dependencies"]["crateB"]["package"].as_str(), Some("crateX"));
+	assert_eq!(unix["dev-dependencies"]["crateC"]["package"].as_str(), Some("crateY"));
+	let windows = &manifest["target"]["cfg(windows)"];
+	assert_eq!(windows["build-dependencies"]["crateB"]["package"].as_str(), Some("crateX"));
+
+	temp.close()?;
+	Ok(())
+}
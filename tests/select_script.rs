@@ -0,0 +1,25 @@
+#![cfg(feature = "select-script")]
+
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use std::process::Command;
+
+#[test]
+fn select_by_script() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("select.rhai")
+		.write_str(r#"name != "crateB" && version.starts_with("0.") || "std" in features"#)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--select-script")
+		.arg(temp.child("select.rhai").path());
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	assert_eq!(output.trim(), "crateA (0.1.0)");
+
+	temp.close()?;
+	Ok(())
+}
@@ -0,0 +1,202 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+#[test]
+fn set_nested_field() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("set")
+		.arg("--packages")
+		.arg("crateA")
+		.arg("--")
+		.arg("metadata.docs.rs.all-features")
+		.arg("true");
+	cmd.assert().success();
+
+	let read = |name: &str| -> Result<toml::Value, Box<dyn std::error::Error>> {
+		let content = std::fs::read_to_string(temp.path().join(name).join("Cargo.toml"))?;
+		Ok(toml::from_str(&content)?)
+	};
+
+	let crate_a = read("crateA")?;
+	assert_eq!(
+		crate_a["package"]["metadata"]["docs"]["rs"]["all-features"],
+		toml::Value::Boolean(true)
+	);
+	// the rest of the package section is left intact
+	assert_eq!(crate_a["package"]["version"], toml::Value::String("0.1.0".into()));
+	// wasn't selected
+	assert!(read("crateB")?["package"].get("metadata").is_none());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn set_nested_field_fails_on_scalar() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("set")
+		.arg("--packages")
+		.arg("crateA")
+		.arg("--")
+		.arg("version.major")
+		.arg("1");
+	cmd.assert().failure();
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn set_field_only_if_missing() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	for (name, value) in [("edition", "2021"), ("repository", "https://example.com")] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("set")
+			.arg("--only-if-missing")
+			.arg("--packages")
+			.arg("crateA")
+			.arg("--")
+			.arg(name)
+			.arg(value);
+		cmd.assert().success();
+	}
+
+	let content = std::fs::read_to_string(temp.path().join("crateA").join("Cargo.toml"))?;
+	let crate_a: toml::Value = toml::from_str(&content)?;
+	// already present, kept
+	assert_eq!(crate_a["package"]["edition"], toml::Value::String("2018".into()));
+	// missing, added
+	assert_eq!(crate_a["package"]["repository"], toml::Value::String("https://example.com".into()));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn fix_metadata_from_defaults() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child(".unleash.toml").write_str(
+		r#"[metadata-defaults]
+license = "MIT"
+description = "The {name} crate"
+"#,
+	)?;
+	temp.child("crateB/Cargo.toml").write_str(
+		r#"[package]
+name = "crateB"
+version = "2.0.0"
+edition = "2018"
+license = "Apache-2.0"
+"#,
+	)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	// no git remote to take the repository from
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("check-metadata")
+		.arg("--fix-metadata");
+	cmd.assert()
+		.failure()
+		.stdout(predicates::str::contains("repository is missing"))
+		.stderr(predicates::str::contains("need manual attention"));
+
+	let read = |name: &str| -> Result<toml::Value, Box<dyn std::error::Error>> {
+		let content = std::fs::read_to_string(temp.path().join(name).join("Cargo.toml"))?;
+		Ok(toml::from_str(&content)?)
+	};
+
+	let crate_a = read("crateA")?;
+	assert_eq!(crate_a["package"]["description"].as_str(), Some("The crateA crate"));
+	assert_eq!(crate_a["package"]["license"].as_str(), Some("MIT"));
+	// already set
+	assert_eq!(read("crateB")?["package"]["license"].as_str(), Some("Apache-2.0"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn fix_metadata_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child(".unleash.toml").write_str(
+		r#"[metadata-defaults]
+license = "MIT"
+description = "The {name} crate"
+"#,
+	)?;
+	let before = std::fs::read_to_string(temp.path().join("crateA/Cargo.toml"))?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).args([
+		"--dry-run",
+		"check-metadata",
+		"--fix-metadata",
+	]);
+	// what was filled in isn't reported as missing, although it wasn't written
+	cmd.assert()
+		.failure()
+		.stdout(predicates::str::contains("repository is missing"))
+		.stdout(predicates::str::contains("description is missing").not())
+		.stdout(predicates::str::contains("Neither license").not());
+
+	assert_eq!(std::fs::read_to_string(temp.path().join("crateA/Cargo.toml"))?, before);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn set_only_unpublishable() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/path-no-version", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("set")
+		.arg("--only-unpublishable")
+		.arg("--")
+		.arg("homepage")
+		.arg("https://internal.example.com");
+	cmd.assert().success();
+
+	let read = |name: &str| -> Result<toml::Value, Box<dyn std::error::Error>> {
+		let content = std::fs::read_to_string(temp.path().join(name).join("Cargo.toml"))?;
+		Ok(toml::from_str(&content)?)
+	};
+
+	// `publish = false`
+	assert_eq!(
+		read("crateC")?["package"]["homepage"],
+		toml::Value::String("https://internal.example.com".into())
+	);
+	assert!(read("crateA")?["package"].get("homepage").is_none());
+	assert!(read("crateB")?["package"].get("homepage").is_none());
+
+	temp.close()?;
+	Ok(())
+}
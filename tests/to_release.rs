@@ -0,0 +1,638 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use predicates::prelude::*;
+use std::process::Command;
+
+mod common;
+
+/// Run `to-release` on a copy of the fixture, return the released crate names in order
+fn release_order(fixture: &str) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from(fixture, &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).arg("to-release");
+	let output = cmd.assert().success().get_output().stdout.clone();
+
+	temp.close()?;
+	Ok(String::from_utf8(output)?
+		.trim()
+		.split(", ")
+		.map(|p| p.split(' ').next().unwrap_or_default().to_owned())
+		.collect())
+}
+
+#[test]
+fn patched_crates_are_released_first() -> Result<(), Box<dyn std::error::Error>> {
+	let order = release_order("tests/fixtures/patch-crates-io")?;
+	let position = |name: &str| order.iter().position(|p| p == name);
+
+	assert!(position("unleash-patched-fixture").is_some(), "Patched crate missing: {:?}", order);
+	assert!(position("unleash-patched-fixture") < position("crateA"));
+	Ok(())
+}
+
+#[test]
+fn build_dependencies_are_released_first() -> Result<(), Box<dyn std::error::Error>> {
+	// crateA is only a build-dependency of crateB, but listed first in the members
+	assert_eq!(release_order("tests/fixtures/build-deps")?, vec!["crateA", "crateB"]);
+	Ok(())
+}
+
+/// Commit everything in the repository at `path` and tag it as `tag`
+fn commit_and_tag(path: &std::path::Path, tag: &str) -> Result<(), Box<dyn std::error::Error>> {
+	let repo = common::commit_all(path, tag, &[])?;
+	let head = repo.head()?.peel_to_commit()?;
+	repo.tag_lightweight(tag, head.as_object(), false)?;
+	Ok(())
+}
+
+/// Copy of `simple-base` in a git repository with the tags `v1`, `v2` (changing crateB) and
+/// `v3` (changing crateC)
+fn tagged_repo() -> Result<assert_fs::TempDir, Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	commit_and_tag(temp.path(), "v1")?;
+	temp.child("crateB/src/main.rs")
+		.write_str("fn main() { println!(\"v2\"); }\n")?;
+	commit_and_tag(temp.path(), "v2")?;
+	temp.child("crateC/src/main.rs")
+		.write_str("fn main() { println!(\"v3\"); }\n")?;
+	commit_and_tag(temp.path(), "v3")?;
+	Ok(temp)
+}
+
+#[test]
+fn changed_between_references() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = tagged_repo()?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--changed-between")
+		.arg("v1..v2");
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	// changes after the range aren't considered, even though they are checked out
+	assert!(output.starts_with("crateB "), "Unexpected crates: {}", output);
+	assert!(!output.contains("crateC"), "Unexpected crates: {}", output);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn changed_since_until() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = tagged_repo()?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--changed-since")
+		.arg("v2")
+		.arg("--changed-until")
+		.arg("v3");
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	assert!(output.starts_with("crateC "), "Unexpected crates: {}", output);
+	assert!(!output.contains("crateB"), "Unexpected crates: {}", output);
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--changed-since")
+		.arg("v1")
+		.arg("--changed-until")
+		.arg("v4");
+	cmd.assert().failure().stderr(predicates::str::contains("v4 not found"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn changed_between_conflicts_with_since_until() -> Result<(), Box<dyn std::error::Error>> {
+	for other in [["--changed-since", "v1"], ["--changed-until", "v2"]] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg("tests/fixtures/simple-base")
+			.arg("to-release")
+			.args(["--changed-between", "v1..v2"])
+			.args(other);
+		cmd.assert().failure().stderr(predicate::str::contains(format!(
+			"The argument '{0} <{1}>' cannot be used with '--changed-between <changed-between>'",
+			other[0],
+			other[0].trim_start_matches('-')
+		)));
+	}
+	Ok(())
+}
+#[test]
+fn changed_root_files() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = tagged_repo()?;
+	temp.child("rust-toolchain.toml")
+		.write_str("[toolchain]\nchannel = \"stable\"\n")?;
+	commit_and_tag(temp.path(), "v4")?;
+
+	let changed = |include_root: bool| -> Result<String, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("to-release")
+			.arg("--changed-between")
+			.arg("v3..v4");
+		if include_root {
+			cmd.arg("--changed-include-root");
+		}
+		Ok(String::from_utf8(cmd.assert().success().get_output().stdout.clone())?)
+	};
+
+	assert!(changed(false)?.contains("No packages selected"));
+	let output = changed(true)?;
+	for name in ["crateA", "crateB", "crateC"] {
+		assert!(output.contains(name), "{} missing: {}", name, output);
+	}
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn changed_since_branch_point() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = tagged_repo()?;
+	// branch off at v2, so crateC was only changed on the other branch
+	let repo = git2::Repository::open(temp.path())?;
+	repo.reset(&repo.revparse_single("v2")?, git2::ResetType::Hard, None)?;
+	temp.child("crateA/src/main.rs")
+		.write_str("fn main() { println!(\"feature\"); }\n")?;
+	commit_and_tag(temp.path(), "feature")?;
+
+	let changed = |two_dot: bool| -> Result<String, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("to-release")
+			.arg("--changed-since")
+			.arg("v3");
+		if two_dot {
+			cmd.arg("--changed-two-dot");
+		}
+		Ok(String::from_utf8(cmd.assert().success().get_output().stdout.clone())?)
+	};
+
+	let output = changed(false)?;
+	assert!(output.starts_with("crateA "), "Unexpected crates: {}", output);
+	assert!(!output.contains("crateC"), "Unexpected crates: {}", output);
+	assert!(changed(true)?.contains("crateC"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn changed_path_dependency_outside() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/path-dep-outside", &["*.toml", "*.rs"])?;
+	commit_and_tag(temp.path(), "v1")?;
+	temp.child("sidekick/src/lib.rs")
+		.write_str("pub fn sidekick() { println!(\"v2\"); }\n")?;
+	commit_and_tag(temp.path(), "v2")?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.child("workspace").path())
+		.arg("to-release")
+		.arg("--changed-between")
+		.arg("v1..v2");
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	for name in ["sidekick", "crateA", "crateB"] {
+		assert!(output.contains(name), "{} missing: {}", name, output);
+	}
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn changelog_per_crate() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = tagged_repo()?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).arg("changelog").arg("v1");
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	assert!(output.contains("## crateB 2.0.0\n\n- v2 ("), "Unexpected changelog: {}", output);
+	assert!(output.contains("## crateC 3.1.0\n\n- v3 ("), "Unexpected changelog: {}", output);
+	// unchanged since
+	assert!(!output.contains("crateA"), "Unexpected changelog: {}", output);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn json_output() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/build-deps", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("--output")
+		.arg("json")
+		.arg("to-release");
+	let output = cmd.assert().success().get_output().stdout.clone();
+	let released: serde_json::Value = serde_json::from_slice(&output)?;
+	assert_eq!(
+		released,
+		serde_json::json!([
+			{"name": "crateA", "version": "0.1.0"},
+			{"name": "crateB", "version": "0.1.0"},
+		])
+	);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn stale_plan() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/build-deps", &["*.toml", "*.rs"])?;
+	let plan = temp.child("plan.json");
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--plan-out")
+		.arg(plan.path());
+	cmd.assert().success();
+	let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(plan.path())?)?;
+	assert_eq!(written[0]["name"], "crateA");
+	assert_eq!(written[1]["name"], "crateB");
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("bump-minor")
+		.arg("--packages")
+		.arg("crateB");
+	cmd.assert().success();
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("em-dragons")
+		.arg("--dry-run")
+		.arg("--plan-in")
+		.arg(plan.path());
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("crateB is at 0.2.0, planned was 0.1.0"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn plan_in_conflicts_with_ordering_options() -> Result<(), Box<dyn std::error::Error>> {
+	for (arg, value) in [("--break-edge", "crateA:crateB"), ("--dot-graph", "graph.dot")] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg("tests/fixtures/simple-base").args([
+			"em-dragons",
+			"--dry-run",
+			"--plan-in",
+			"plan.json",
+			arg,
+			value,
+		]);
+		cmd.assert()
+			.failure()
+			.stderr(predicates::str::contains(format!("The argument '{} ", arg)))
+			.stderr(predicates::str::contains("cannot be used with '--plan-in <plan-in>'"));
+	}
+	Ok(())
+}
+
+#[test]
+fn package_list() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	let list = temp.child("packages.txt");
+	list.write_str("# to release\ncrateC\n\nno-such-crate\n")?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--package-list")
+		.arg(list.path())
+		.arg("--packages")
+		.arg("crateA");
+	let assert = cmd.assert().success().stderr(predicates::str::contains(
+		"no-such-crate from the package list isn't in the workspace",
+	));
+	let output = String::from_utf8(assert.get_output().stdout.clone())?;
+	assert!(output.contains("crateA") && output.contains("crateC"), "Unexpected: {}", output);
+	assert!(!output.contains("crateB"), "Unexpected crates: {}", output);
+
+	let mut cmd = assert_cmd::Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("to-release")
+		.arg("--package-list")
+		.arg("-")
+		.write_stdin("crateB\n");
+	let output = String::from_utf8(cmd.assert().success().get_output().stdout.clone())?;
+	assert!(output.starts_with("crateB "), "Unexpected crates: {}", output);
+	assert!(!output.contains("crateA"), "Unexpected crates: {}", output);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn select_defaults_from_config() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	let to_release = |args: &[&str]| -> Result<String, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path()).arg("to-release").args(args);
+		Ok(String::from_utf8(cmd.assert().success().get_output().stdout.clone())?)
+	};
+
+	let manifest = temp.child("Cargo.toml");
+	let original = std::fs::read_to_string(manifest.path())?;
+	manifest.write_str(&format!(
+		"{}\n[workspace.metadata.unleash.select]\nskip = [\"crateB\"]\n",
+		original
+	))?;
+	let output = to_release(&[])?;
+	assert!(!output.contains("crateB") && output.contains("crateC"), "Unexpected: {}", output);
+
+	// the config file takes precedence over the manifest
+	temp.child(".unleash.toml").write_str("[select]\nskip = [\"crateC\"]\n")?;
+	let output = to_release(&[])?;
+	assert!(output.contains("crateB") && !output.contains("crateC"), "Unexpected: {}", output);
+
+	// and the command line over both
+	let output = to_release(&["--skip", "crateA"])?;
+	assert!(!output.contains("crateA") && output.contains("crateC"), "Unexpected: {}", output);
+	// explicit selections aren't skipped
+	let output = to_release(&["--packages", "crateC"])?;
+	assert!(output.contains("crateC"), "Unexpected: {}", output);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn list_selection() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/path-no-version", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("--output")
+		.arg("json")
+		.arg("list")
+		.arg("--ignore-publish")
+		.arg("--skip")
+		.arg("crateB");
+	let output = cmd.assert().success().get_output().stdout.clone();
+	let listed: serde_json::Value = serde_json::from_slice(&output)?;
+	assert_eq!(
+		listed,
+		serde_json::json!([
+			{"name": "crateA", "version": "0.1.0", "path": "crateA", "publishable": true},
+			{"name": "crateC", "version": "0.1.0", "path": "crateC", "publishable": false},
+		])
+	);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn offline_skips_registry_sync() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).arg("to-release");
+	cmd.assert()
+		.success()
+		.stdout(predicates::str::contains("crateA"))
+		.stderr(predicates::str::contains("Syncing versions from crates.io (offline)"))
+		.stderr(predicates::str::contains("Updating crates.io index").not());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn max_publish() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let em_dragons = |args: &[&str]| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--offline")
+			.arg("--manifest-path")
+			.arg(temp.path())
+			.args(["em-dragons", "--dry-run", "--no-check"])
+			.args(args);
+		Ok(cmd)
+	};
+
+	em_dragons(&["--max-publish", "2"])?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains(
+			"3 crates selected for publishing, 1 more than `--max-publish 2` allows",
+		));
+	// past the guard, publishing itself needs the registry
+	em_dragons(&["--max-publish", "2", "--yes"])?
+		.assert()
+		.stderr(predicates::str::contains("Releasing crateC"));
+	em_dragons(&["--max-publish", "3"])?
+		.assert()
+		.stderr(predicates::str::contains("Releasing crateC"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn publish_needs_confirmation() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = assert_cmd::Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--offline")
+		.arg("--manifest-path")
+		.arg(temp.path())
+		.args(["em-dragons", "--no-check"])
+		.write_stdin("y\n");
+	cmd.assert().failure().stderr(predicates::str::contains(
+		"Not publishing without confirmation, pass `--yes` when running non-interactively",
+	));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn publish_concurrency() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let em_dragons = |concurrency: &str| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).args([
+			"em-dragons",
+			"--dry-run",
+			"--no-check",
+			"--publish-concurrency",
+			concurrency,
+		]);
+		Ok(cmd)
+	};
+
+	em_dragons("0")?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains("`--publish-concurrency` needs to be at least 1"));
+	// offline, every crate of the level fails and all of them are reported
+	em_dragons("3")?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains("Publishing failed for 3 crate(s)"))
+		.stderr(predicates::str::contains("crateA 0.1.0:"))
+		.stderr(predicates::str::contains("crateC 3.1.0:"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn publish_failure_stops_later_levels() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	// crateB and crateC are published first, crateA only after both of them
+	temp.child("crateA/Cargo.toml").write_str(
+		r#"[package]
+name = "crateA"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dependencies]
+crateB = { path = "../crateB", version = "2.0.0" }
+crateC = { path = "../crateC", version = "3.1.0" }
+"#,
+	)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).args([
+		"em-dragons",
+		"--dry-run",
+		"--no-check",
+		"--publish-concurrency",
+		"2",
+	]);
+	// offline, both of the first level fail
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("Publishing failed for 2 crate(s)"))
+		.stderr(predicates::str::contains("crateB 2.0.0:"))
+		.stderr(predicates::str::contains("crateC 3.1.0:"))
+		.stderr(predicates::str::contains("Publishing crateA").not());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn publish_into_target_dir() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	// a registry supporting uploads, so the crates are packaged before the dry run stops
+	let index = temp.child("index");
+	index
+		.child("config.json")
+		.write_str(r#"{"dl": "http://127.0.0.1:1/dl", "api": "http://127.0.0.1:1"}"#)?;
+	common::commit_all(index.path(), "index", &[])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.env("CARGO_HOME", temp.path().join("cargo-home"))
+		.env("CARGO_REGISTRIES_TEST_INDEX", format!("file://{}", index.path().display()))
+		.arg("--manifest-path")
+		.arg(temp.path())
+		.args(["em-dragons", "--dry-run", "--no-check", "--registry", "test", "--target-dir"])
+		.arg(temp.path().join("out"));
+	cmd.assert().success();
+
+	temp.child("out/package/crateA-0.1.0.crate").assert(predicate::path::exists());
+	temp.child("target").assert(predicate::path::missing());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn verify_published_needs_index_wait() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--offline").arg("--manifest-path").arg(temp.path()).args([
+		"em-dragons",
+		"--dry-run",
+		"--no-check",
+		"--verify-published",
+		"--no-wait",
+	]);
+	cmd.assert().failure().stderr(predicates::str::contains(
+		"The argument '--no-wait' cannot be used with '--verify-published'",
+	));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dry_run_still_breaks_dev_dependency_cycles() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+	// crateA and crateB only depend on each other for development
+	temp.child("crateB/Cargo.toml").write_str(
+		r#"[package]
+name = "crateB"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dev-dependencies]
+crateA = { path = "../crateA", version = "0.1.0" }
+"#,
+	)?;
+	let read = |krate: &str| std::fs::read_to_string(temp.path().join(krate).join("Cargo.toml"));
+	let before = (read("crateA")?, read("crateB")?);
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).args(["--dry-run", "to-release"]);
+	cmd.assert()
+		.success()
+		.stdout(predicate::str::contains("crateA"))
+		.stdout(predicate::str::contains("crateB"));
+
+	// the dev-dependencies were only disabled while running
+	assert_eq!((read("crateA")?, read("crateB")?), before);
+
+	temp.close()?;
+	Ok(())
+}
@@ -0,0 +1,67 @@
+use assert_cmd::prelude::*;
+use assert_fs::prelude::*;
+use std::process::Command;
+
+/// Run `tree` with `args` on a copy of the `tree` fixture, return what it printed
+fn tree(args: &[&str]) -> Result<String, Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/tree", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).arg("tree").args(args);
+	let output = cmd.assert().success().get_output().stdout.clone();
+
+	temp.close()?;
+	Ok(String::from_utf8(output)?)
+}
+
+#[test]
+fn tree_of_workspace_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+	// dev-dependencies and external crates are left out, core is expanded only once
+	assert_eq!(
+		tree(&["app"])?,
+		"app v1.0.0
+├── core v0.1.0
+├── cycle-a v0.1.0 (cycle)
+│   └── cycle-b v0.1.0 (cycle)
+│       └── cycle-a v0.1.0 (cycle) (*)
+└── util v0.2.0
+    └── core v0.1.0 (*)
+"
+	);
+	Ok(())
+}
+
+#[test]
+fn tree_with_external_up_to_depth() -> Result<(), Box<dyn std::error::Error>> {
+	assert_eq!(
+		tree(&["app", "--external", "--depth", "1"])?,
+		"app v1.0.0
+├── core v0.1.0
+├── cycle-a v0.1.0 (cycle)
+├── serde ^1 (external)
+└── util v0.2.0
+"
+	);
+	Ok(())
+}
+
+#[test]
+fn tree_marks_only_cycle_participants() -> Result<(), Box<dyn std::error::Error>> {
+	assert_eq!(tree(&["util"])?, "util v0.2.0\n└── core v0.1.0\n");
+	assert_eq!(
+		tree(&["cycle-b"])?,
+		"cycle-b v0.1.0 (cycle)\n└── cycle-a v0.1.0 (cycle)\n    └── cycle-b v0.1.0 (cycle) (*)\n"
+	);
+	Ok(())
+}
+
+#[test]
+fn tree_of_unknown_crate() -> Result<(), Box<dyn std::error::Error>> {
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg("tests/fixtures/tree").arg("tree").arg("missing");
+	cmd.assert()
+		.failure()
+		.stderr(predicates::str::contains("missing is not a member of the workspace"));
+	Ok(())
+}
@@ -1,9 +1,12 @@
 use assert_cmd::prelude::*;
 use assert_fs::prelude::*;
 use cargo::{core::source::SourceId, ops::read_package, util::config::Config as CargoConfig};
+use predicates::prelude::*;
 use semver::Version;
 use std::process::Command;
 
+mod common;
+
 #[test]
 fn set_pre() -> Result<(), Box<dyn std::error::Error>> {
 	let cfg = CargoConfig::default()?;
@@ -67,3 +70,467 @@ fn bump_to_dev() -> Result<(), Box<dyn std::error::Error>> {
 	temp.close()?;
 	Ok(())
 }
+
+#[test]
+fn bump_to_rc() -> Result<(), Box<dyn std::error::Error>> {
+	let cfg = CargoConfig::default()?;
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let temp_path = temp.path().to_path_buf();
+	let source = SourceId::for_path(temp.path())?;
+
+	for expected in ["0.2.0-rc.1", "0.2.0-rc.2"] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("version")
+			.arg("bump-to-rc")
+			.arg("--packages")
+			.arg("crateA");
+		cmd.assert().success();
+
+		let (crate_a, _) =
+			read_package(&temp_path.join("crateA").join("Cargo.toml"), source, &cfg)?;
+		assert_eq!(crate_a.version(), &Version::parse(expected)?);
+	}
+
+	let (crate_b, _) = read_package(&temp_path.join("crateB").join("Cargo.toml"), source, &cfg)?;
+	assert_eq!(crate_b.version(), &Version::parse("2.0.0")?); // wasn't selected
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn set_all() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA").child("Cargo.toml").write_str(
+		r#"[package]
+name = "crateA"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dependencies]
+crateB = { path = "../crateB", version = "2.0.0" }
+crateC = { path = "../crateC", version = "3.1.0" }
+"#,
+	)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("set-all")
+		.arg("4.0.0")
+		.arg("--packages")
+		.arg("crateA")
+		.arg("crateB");
+	cmd.assert().success().stderr(predicate::str::contains(
+		"crateC (3.1.0) wasn't selected and isn't at 4.0.0, but is depended upon by crateA",
+	));
+
+	let manifest = |name: &str| -> Result<toml::Value, Box<dyn std::error::Error>> {
+		Ok(std::fs::read_to_string(temp.path().join(name).join("Cargo.toml"))?.parse()?)
+	};
+	let crate_a = manifest("crateA")?;
+	assert_eq!(crate_a["package"]["version"].as_str(), Some("4.0.0"));
+	assert_eq!(crate_a["dependencies"]["crateB"]["version"].as_str(), Some("4.0.0"));
+	assert_eq!(crate_a["dependencies"]["crateC"]["version"].as_str(), Some("3.1.0"));
+	assert_eq!(manifest("crateB")?["package"]["version"].as_str(), Some("4.0.0"));
+	assert_eq!(manifest("crateC")?["package"]["version"].as_str(), Some("3.1.0"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn sync_deps() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA").child("Cargo.toml").write_str(
+		r#"[package]
+name = "crateA"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dependencies]
+crateB = { path = "../crateB", version = "1.0" }
+
+[target.'cfg(unix)'.dependencies.crateC]
+path = "../crateC"
+version = "3.0.0"
+
+[dev-dependencies]
+crateC = { path = "../crateC" }
+"#,
+	)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path").arg(temp.path()).arg("version").arg("sync-deps");
+	cmd.assert().success();
+
+	let manifest: toml::Value =
+		std::fs::read_to_string(temp.path().join("crateA/Cargo.toml"))?.parse()?;
+	assert_eq!(manifest["dependencies"]["crateB"]["version"].as_str(), Some("2.0.0"));
+	assert_eq!(
+		manifest["target"]["cfg(unix)"]["dependencies"]["crateC"]["version"].as_str(),
+		Some("3.1.0")
+	);
+	// dev dependencies without a version are left alone
+	assert_eq!(manifest["dev-dependencies"]["crateC"].get("version"), None);
+	// versions of the crates themselves are untouched
+	assert_eq!(manifest["package"]["version"].as_str(), Some("0.1.0"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_breaks_non_path_requirement() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA").child("Cargo.toml").write_str(
+		r#"[package]
+name = "crateA"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dependencies]
+crateB = "=2.0.0"
+"#,
+	)?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("bump-major")
+		.arg("--packages")
+		.arg("crateB");
+	cmd.assert()
+		.failure()
+		.stdout(predicates::str::contains("crateA"))
+		.stdout(predicates::str::contains("3.0.0"))
+		.stderr(predicates::str::contains("can't be satisfied"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_report() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	temp.child("crateA").child("Cargo.toml").write_str(
+		r#"[package]
+name = "crateA"
+version = "0.1.0"
+authors = []
+edition = "2018"
+
+[dependencies]
+crateB = { path = "../crateB", version = "2.0" }
+crateC = { path = "../crateC" }
+"#,
+	)?;
+	let report = temp.child("report.txt");
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("--report")
+		.arg(report.path())
+		.arg("bump-major")
+		.arg("--packages")
+		.arg("crateB")
+		.arg("crateC");
+	cmd.assert().success();
+
+	report.assert("crateA: dep crateB 2.0 -> 3.0.0\ncrateA: dep crateC (none) -> 4.0.0\n");
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_json_output() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("--output")
+		.arg("json")
+		.arg("version")
+		.arg("bump-minor")
+		.arg("--packages")
+		.arg("crateB");
+	let output = cmd.assert().success().get_output().stdout.clone();
+	let bumps: serde_json::Value = serde_json::from_slice(&output)?;
+	assert_eq!(bumps, serde_json::json!([{"crate": "crateB", "from": "2.0.0", "to": "2.1.0"}]));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn require_clean_tree() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let bump = || -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("--require-clean")
+			.arg("version")
+			.arg("bump-patch")
+			.arg("--packages")
+			.arg("crateA");
+		Ok(cmd)
+	};
+
+	// not a git repository, nothing to protect
+	bump()?.assert().success();
+
+	git2::Repository::init(temp.path())?;
+	bump()?
+		.assert()
+		.failure()
+		.stderr(predicates::str::contains("uncommitted changes"))
+		.stderr(predicates::str::contains("crateA/Cargo.toml"));
+
+	common::commit_all(temp.path(), "initial", &[])?;
+	bump()?.assert().success();
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_commit() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	let repo = common::commit_all(temp.path(), "initial", &[])?;
+	// not made by the command, must not be committed
+	temp.child("crateC/src/main.rs").write_str("fn main() {}\n")?;
+	// nor what was staged before
+	temp.child("crateB/src/main.rs").write_str("fn main() {}\n")?;
+	let mut index = repo.index()?;
+	index.add_path(std::path::Path::new("crateB/src/main.rs"))?;
+	index.write()?;
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("--commit")
+		.arg("--commit-message")
+		.arg("Release {crates}")
+		.arg("bump-patch")
+		.arg("--packages")
+		.arg("crateA");
+	cmd.assert().success();
+
+	let head = repo.head()?.peel_to_commit()?;
+	assert_eq!(head.message(), Some("Release crateA 0.1.1"));
+	let statuses = repo.statuses(None)?;
+	let dirty = statuses
+		.iter()
+		.filter_map(|s| s.path().map(|p| p.to_owned()))
+		.collect::<Vec<_>>();
+	assert_eq!(dirty, vec!["crateB/src/main.rs".to_owned(), "crateC/src/main.rs".to_owned()]);
+	let staged = repo.status_file(std::path::Path::new("crateB/src/main.rs"))?;
+	assert_eq!(staged, git2::Status::INDEX_MODIFIED);
+	assert!(repo.status_file(std::path::Path::new("crateA/Cargo.toml"))?.is_empty());
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dry_run_shows_diff() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+	let read = |krate: &str| std::fs::read_to_string(temp.path().join(krate).join("Cargo.toml"));
+	let (before_a, before_b) = (read("crateA")?, read("crateB")?);
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.current_dir(temp.path())
+		.arg("--dry-run")
+		.arg("version")
+		.arg("bump-breaking")
+		.arg("--packages")
+		.arg("crateB");
+	cmd.assert().success().stdout(predicates::str::contains(
+		"--- crateB/Cargo.toml\n+++ crateB/Cargo.toml\n@@ -1,6 +1,6 @@\n [package]\n name = \"crateB\"\n-version = \"0.1.0\"\n+version = \"0.2.0\"\n",
+	))
+	// the dependent is updated, too
+	.stdout(predicates::str::contains(
+		"-crateB = { path = \"../crateB\", version = \"0.1.0\" }\n+crateB = { path = \"../crateB\", version = \"0.2.0\"",
+	));
+
+	// nothing was written
+	assert_eq!(read("crateA")?, before_a);
+	assert_eq!(read("crateB")?, before_b);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn dry_run_skips_checks_of_unwritten_changes() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+	let read = |krate: &str| std::fs::read_to_string(temp.path().join(krate).join("Cargo.toml"));
+	let before = [read("crateA")?, read("crateB")?, read("crateC")?];
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.current_dir(temp.path()).args(["--dry-run", "version", "set-all", "4.0.0"]);
+	// the crates on disk aren't at 4.0.0, checking them would fail
+	cmd.assert()
+		.success()
+		.stdout(predicate::str::contains("--- crateB/Cargo.toml\n+++ crateB/Cargo.toml\n"))
+		.stdout(predicate::str::contains("+version = \"4.0.0\"\n"))
+		.stderr(predicate::str::contains("Skipping requirement check"))
+		.stderr(predicate::str::contains("Skipping lockstep check"));
+
+	assert_eq!([read("crateA")?, read("crateB")?, read("crateC")?], before);
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn quiet_leaves_out_package_lines() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/dev-deps", &["*.toml", "*.rs"])?;
+
+	let bump = |quiet: bool| -> Result<Command, Box<dyn std::error::Error>> {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+		cmd.arg("--manifest-path").arg(temp.path());
+		if quiet {
+			cmd.arg("--quiet");
+		}
+		cmd.args(["version", "bump-breaking", "--packages", "crateB"]);
+		Ok(cmd)
+	};
+
+	bump(false)?
+		.assert()
+		.success()
+		.stderr(predicates::str::contains("Updating crateA"))
+		.stderr(predicates::str::contains("1 dependencies updated in total"));
+	bump(true)?
+		.assert()
+		.success()
+		.stderr(predicates::str::contains("Updating crateA").not())
+		.stderr(predicates::str::contains("No dependency updates").not())
+		.stderr(predicates::str::contains("1 dependencies updated in total"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_workspace_dependencies() -> Result<(), Box<dyn std::error::Error>> {
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/workspace-dependencies", &["*.toml", "*.rs"])?;
+	let report = temp.child("report.txt");
+
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+	cmd.arg("--manifest-path")
+		.arg(temp.path())
+		.arg("version")
+		.arg("--report")
+		.arg(report.path())
+		.arg("bump-major")
+		.arg("--packages")
+		.arg("crateB");
+	// crateA inherits the requirement, which is only satisfied once the root is updated
+	cmd.assert()
+		.success()
+		.stderr(predicates::str::contains("Bumping [workspace.package]: 1.2.0 -> 2.0.0"))
+		.stderr(predicates::str::contains(
+			"crateC wasn't selected, but inherits the workspace version and is bumped to 2.0.0",
+		));
+
+	let read = |path: &str| std::fs::read_to_string(temp.path().join(path));
+	let manifest: toml::Value = toml::from_str(&read("Cargo.toml")?)?;
+	assert_eq!(manifest["workspace"]["package"]["version"].as_str(), Some("2.0.0"));
+	let dep = &manifest["workspace"]["dependencies"]["crateB"];
+	assert_eq!(dep["version"].as_str(), Some("2.0.0"));
+	assert!(read("crateA/Cargo.toml")?.contains("crateB = { workspace = true }"));
+	// still inheriting the version
+	assert!(read("crateB/Cargo.toml")?.contains("version.workspace = true\n"));
+	assert!(read("crateC/Cargo.toml")?.contains("version = { workspace = true }\n"));
+
+	report.assert("[workspace.dependencies]: dep crateB 1.2.0 -> 2.0.0\n");
+
+	// committing includes the root manifest
+	let repo = common::commit_all(temp.path(), "initial", &[])?;
+	let mut cmd = Command::cargo_bin("cargo-unleash")?;
+	cmd.arg("--manifest-path").arg(temp.path()).args([
+		"version",
+		"--commit",
+		"bump-minor",
+		"--packages",
+		"crateB",
+	]);
+	cmd.assert().success();
+
+	let head = repo.head()?.peel_to_commit()?;
+	assert_eq!(head.message(), Some("Bump 2 crates"));
+	assert!(repo.statuses(None)?.is_empty());
+	let manifest: toml::Value = toml::from_str(&read("Cargo.toml")?)?;
+	assert_eq!(manifest["workspace"]["package"]["version"].as_str(), Some("2.1.0"));
+
+	temp.close()?;
+	Ok(())
+}
+
+#[test]
+fn bump_dev_to_rc() -> Result<(), Box<dyn std::error::Error>> {
+	let cfg = CargoConfig::default()?;
+	let temp = assert_fs::TempDir::new()?;
+	temp.copy_from("tests/fixtures/simple-base", &["*.toml", "*.rs"])?;
+
+	let temp_path = temp.path().to_path_buf();
+	let source = SourceId::for_path(temp.path())?;
+
+	// the breaking bump happens going to `-dev` already, the release candidate is of that
+	for (command, expected) in [("bump-to-dev", "0.2.0-dev"), ("bump-to-rc", "0.2.0-rc.1")] {
+		let mut cmd = Command::cargo_bin("cargo-unleash")?;
+
+		cmd.arg("--manifest-path")
+			.arg(temp.path())
+			.arg("version")
+			.arg(command)
+			.arg("--packages")
+			.arg("crateA");
+		cmd.assert().success();
+
+		let (crate_a, _) =
+			read_package(&temp_path.join("crateA").join("Cargo.toml"), source, &cfg)?;
+		assert_eq!(crate_a.version(), &Version::parse(expected)?);
+	}
+
+	temp.close()?;
+	Ok(())
+}